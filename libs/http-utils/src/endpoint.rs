@@ -5,7 +5,10 @@
 
 use anyhow::{Context, anyhow};
 use bytes::{Bytes, BytesMut};
-use hyper::header::{AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, HeaderName};
+use hyper::header::{
+    ACCEPT_ENCODING, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH,
+    CONTENT_TYPE, HeaderName, VARY,
+};
 use hyper::http::HeaderValue;
 use hyper::{Body, Method, Request, Response};
 use jsonwebtoken::TokenData;
@@ -507,16 +510,14 @@ enum Format {
 pub fn add_request_id_middleware<B: hyper::body::HttpBody + Send + Sync + 'static>()
 -> Middleware<B, ApiError> {
     Middleware::pre(move |req| async move {
-        let request_id = match req.headers().get(&X_REQUEST_ID_HEADER) {
-            Some(request_id) => request_id
-                .to_str()
-                .expect("extract request id value")
-                .to_owned(),
-            None => {
-                let request_id = uuid::Uuid::new_v4();
-                request_id.to_string()
-            }
-        };
+        // Adopt the id of e.g. a load balancer, unless it can't be echoed back as a header.
+        let request_id = req
+            .headers()
+            .get(&X_REQUEST_ID_HEADER)
+            .and_then(|request_id| request_id.to_str().ok())
+            .filter(|request_id| !request_id.is_empty())
+            .map(str::to_owned)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
         req.set_context(RequestId(request_id));
 
         Ok(req)
@@ -607,8 +608,69 @@ fn parse_token(header_value: &str) -> Result<&str, ApiError> {
     Ok(token)
 }
 
+/// A static credential accepted by [`auth_middleware`] through HTTP Basic authentication, for
+/// clients that cannot mint JWTs. Requests presenting it are granted `claims`.
+#[derive(Clone)]
+pub struct BasicAuthCredential {
+    username: String,
+    password: String,
+    claims: Claims,
+}
+
+impl std::fmt::Debug for BasicAuthCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BasicAuthCredential")
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+
+impl BasicAuthCredential {
+    pub fn new(username: String, password: String, claims: Claims) -> Self {
+        Self {
+            username,
+            password,
+            claims,
+        }
+    }
+
+    /// Checks the base64-encoded `user:password` part of a `Basic` authorization header.
+    fn check(&self, encoded: &str) -> Result<Claims, ApiError> {
+        let malformed = || ApiError::Unauthorized("malformed basic credentials".to_string());
+        let decoded = base64::decode(encoded).map_err(|_| malformed())?;
+        let decoded = std::str::from_utf8(&decoded).map_err(|_| malformed())?;
+        let (username, password) = decoded.split_once(':').ok_or_else(malformed)?;
+
+        // Compare both parts in full, so that the response time doesn't tell which one is wrong.
+        let username_ok = constant_time_eq(username.as_bytes(), self.username.as_bytes());
+        let password_ok = constant_time_eq(password.as_bytes(), self.password.as_bytes());
+        if username_ok & password_ok {
+            Ok(self.claims.clone())
+        } else {
+            warn!("Authentication error: invalid basic credentials for user {username:?}");
+            Err(ApiError::Unauthorized(
+                "invalid basic credentials".to_string(),
+            ))
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Authenticates requests with a JWT from the `Authorization: Bearer` header.
+///
+/// If `provide_basic_auth` returns a credential, `Authorization: Basic` matching it is accepted
+/// as well. Routes for which `provide_auth` returns `None` are not authenticated at all.
 pub fn auth_middleware<B: hyper::body::HttpBody + Send + Sync + 'static>(
     provide_auth: fn(&Request<Body>) -> Option<&SwappableJwtAuth>,
+    provide_basic_auth: fn(&Request<Body>) -> Option<&BasicAuthCredential>,
 ) -> Middleware<B, ApiError> {
     Middleware::pre(move |req| async move {
         if let Some(auth) = provide_auth(&req) {
@@ -617,14 +679,28 @@ pub fn auth_middleware<B: hyper::body::HttpBody + Send + Sync + 'static>(
                     let header_value = value.to_str().map_err(|_| {
                         ApiError::Unauthorized("malformed authorization header".to_string())
                     })?;
-                    let token = parse_token(header_value)?;
 
-                    let data: TokenData<Claims> = auth.decode(token).map_err(|err| {
-                        warn!("Authentication error: {err}");
-                        // Rely on From<AuthError> for ApiError impl
-                        err
-                    })?;
-                    req.set_context(data.claims);
+                    let claims = match header_value.split_once(' ') {
+                        Some(("Basic", credentials)) => match provide_basic_auth(&req) {
+                            Some(basic_auth) => basic_auth.check(credentials)?,
+                            None => {
+                                return Err(ApiError::Unauthorized(
+                                    "basic authentication is not enabled".to_string(),
+                                ));
+                            }
+                        },
+                        _ => {
+                            let token = parse_token(header_value)?;
+
+                            let data: TokenData<Claims> = auth.decode(token).map_err(|err| {
+                                warn!("Authentication error: {err}");
+                                // Rely on From<AuthError> for ApiError impl
+                                err
+                            })?;
+                            data.claims
+                        }
+                    };
+                    req.set_context(claims);
                 }
                 None => {
                     return Err(ApiError::Unauthorized(
@@ -669,6 +745,140 @@ pub fn add_response_header_middleware<B>(
     ))
 }
 
+/// Responses with a smaller body are sent uncompressed by [`compress_response_middleware`]: for
+/// them, the compression overhead outweighs the savings.
+pub const COMPRESSION_MIN_BODY_SIZE: u64 = 1024;
+
+/// Bodies at least this large are compressed on a blocking thread, to keep multi-megabyte
+/// responses from stalling the executor.
+const COMPRESSION_BLOCKING_BODY_SIZE: usize = 64 * 1024;
+
+/// A response `Content-Encoding` supported by [`compress_response_middleware`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContentEncoding {
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Picks an encoding accepted by an `Accept-Encoding` header, preferring gzip. Codings with
+    /// `q=0` are rejected; `*` only covers codings that aren't listed by name.
+    fn negotiate(accept_encoding: &str) -> Option<Self> {
+        // (coding, accepted) pairs, in header order.
+        let codings: Vec<(&str, bool)> = accept_encoding
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let coding = parts.next().filter(|coding| !coding.is_empty())?;
+                let rejected = parts.any(|param| {
+                    param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)
+                });
+                Some((coding, !rejected))
+            })
+            .collect();
+        let is_accepted = |name: &str| {
+            let named = codings
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(name));
+            match named {
+                Some((_, accepted)) => *accepted,
+                None => codings
+                    .iter()
+                    .any(|(coding, accepted)| *coding == "*" && *accepted),
+            }
+        };
+        [ContentEncoding::Gzip, ContentEncoding::Deflate]
+            .into_iter()
+            .find(|encoding| is_accepted(encoding.name()))
+    }
+
+    fn encode(self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        let level = flate2::Compression::default();
+        match self {
+            ContentEncoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            // HTTP "deflate" is the zlib format, not raw deflate.
+            ContentEncoding::Deflate => {
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), level);
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Compresses JSON response bodies of at least `min_body_size` bytes with an encoding from the
+/// request's `Accept-Encoding` header. Streamed bodies, whose size isn't known upfront, are sent
+/// as is.
+pub fn compress_response_middleware<B>(min_body_size: u64) -> Middleware<B, ApiError>
+where
+    B: hyper::body::HttpBody + Send + Sync + 'static,
+{
+    Middleware::post_with_info(move |response, request_info| async move {
+        compress_response(response, &request_info, min_body_size).await
+    })
+}
+
+async fn compress_response(
+    response: Response<Body>,
+    request_info: &RequestInfo,
+    min_body_size: u64,
+) -> Result<Response<Body>, ApiError> {
+    use hyper::body::HttpBody as _;
+
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let body_size = response.body().size_hint().exact();
+    if !is_json
+        || response.headers().contains_key(CONTENT_ENCODING)
+        || !body_size.is_some_and(|size| size >= min_body_size)
+    {
+        return Ok(response);
+    }
+    let Some(encoding) = request_info
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(ContentEncoding::negotiate)
+    else {
+        return Ok(response);
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| ApiError::InternalServerError(e.into()))?;
+    let compressed = if body.len() >= COMPRESSION_BLOCKING_BODY_SIZE {
+        tokio::task::spawn_blocking(move || encoding.encode(&body))
+            .await
+            .map_err(|e| ApiError::InternalServerError(e.into()))?
+    } else {
+        encoding.encode(&body)
+    }
+    .map_err(|e| ApiError::InternalServerError(e.into()))?;
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+    Ok(Response::from_parts(parts, Body::from(compressed)))
+}
+
 pub fn check_permission_with(
     req: &Request<Body>,
     check_permission: impl Fn(&Claims) -> Result<(), AuthError>,
@@ -680,24 +890,36 @@ pub fn check_permission_with(
     }
 }
 
+/// Builds a ready-to-call service for `router`, as if a request came from localhost.
 #[cfg(test)]
-mod tests {
+pub(crate) async fn test_service(
+    router: Router<Body, ApiError>,
+) -> routerify::RequestService<Body, ApiError> {
     use std::future::poll_fn;
     use std::net::{IpAddr, SocketAddr};
 
     use hyper::service::Service;
-    use routerify::RequestServiceBuilder;
+
+    let builder = routerify::RequestServiceBuilder::new(router).unwrap();
+    let remote_addr = SocketAddr::new(IpAddr::from_str("127.0.0.1").unwrap(), 80);
+    let mut service = builder.build(remote_addr);
+    if let Err(e) = poll_fn(|ctx| service.poll_ready(ctx)).await {
+        panic!("request service is not ready: {:?}", e);
+    }
+    service
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::StatusCode;
+    use hyper::service::Service;
 
     use super::*;
+    use crate::json::json_response;
 
     #[tokio::test]
     async fn test_request_id_returned() {
-        let builder = RequestServiceBuilder::new(make_router().build().unwrap()).unwrap();
-        let remote_addr = SocketAddr::new(IpAddr::from_str("127.0.0.1").unwrap(), 80);
-        let mut service = builder.build(remote_addr);
-        if let Err(e) = poll_fn(|ctx| service.poll_ready(ctx)).await {
-            panic!("request service is not ready: {:?}", e);
-        }
+        let mut service = test_service(make_router().build().unwrap()).await;
 
         let mut req: Request<Body> = Request::default();
         req.headers_mut()
@@ -710,14 +932,131 @@ async fn test_request_id_returned() {
         assert!(header_val == "42", "response header mismatch");
     }
 
+    #[test]
+    fn test_basic_auth_credential() {
+        let credential = BasicAuthCredential::new(
+            "ops".to_string(),
+            "hunter2".to_string(),
+            Claims::new(None, utils::auth::Scope::PageServerApi),
+        );
+
+        let claims = credential.check(&base64::encode("ops:hunter2")).unwrap();
+        assert_eq!(claims.scope, utils::auth::Scope::PageServerApi);
+
+        for bad in ["ops:hunter3", "op:hunter2", "ops:", "ops"] {
+            let err = credential.check(&base64::encode(bad)).unwrap_err();
+            assert!(matches!(err, ApiError::Unauthorized(_)), "{bad}: {err}");
+        }
+        let err = credential.check("not base64!").unwrap_err();
+        assert!(matches!(err, ApiError::Unauthorized(_)), "{err}");
+
+        assert!(!format!("{credential:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn test_negotiate_content_encoding() {
+        for (header, expected) in [
+            ("gzip", Some(ContentEncoding::Gzip)),
+            ("deflate, gzip;q=0.5", Some(ContentEncoding::Gzip)),
+            ("DEFLATE", Some(ContentEncoding::Deflate)),
+            ("gzip;q=0, deflate", Some(ContentEncoding::Deflate)),
+            ("*", Some(ContentEncoding::Gzip)),
+            ("gzip;q=0, *", Some(ContentEncoding::Deflate)),
+            ("gzip;q=0, deflate;q=0, *", None),
+            ("*;q=0", None),
+            ("br, zstd", None),
+            ("identity", None),
+            ("", None),
+        ] {
+            assert_eq!(ContentEncoding::negotiate(header), expected, "{header:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compress_response() {
+        let router = make_router()
+            .middleware(compress_response_middleware(COMPRESSION_MIN_BODY_SIZE))
+            .get("/small", |_| async { json_response(StatusCode::OK, "ok") })
+            .get("/large", |_| async {
+                json_response(StatusCode::OK, vec!["timeline"; 1000])
+            })
+            .get("/huge", |_| async {
+                json_response(StatusCode::OK, vec!["timeline"; 100_000])
+            })
+            .build()
+            .unwrap();
+        let mut service = test_service(router).await;
+
+        let mut call = async |path: &str, accept_encoding: Option<&str>| {
+            let mut req = Request::builder().uri(path);
+            if let Some(accept_encoding) = accept_encoding {
+                req = req.header(ACCEPT_ENCODING, accept_encoding);
+            }
+            let resp = service
+                .call(req.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let encoding = resp
+                .headers()
+                .get(CONTENT_ENCODING)
+                .map(|value| value.to_str().unwrap().to_owned());
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            (encoding, body)
+        };
+
+        let (encoding, uncompressed) = call("/large", None).await;
+        assert_eq!(encoding, None);
+
+        let (encoding, body) = call("/large", Some("gzip, deflate")).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        assert!(body.len() < uncompressed.len());
+        let mut decoded = Vec::new();
+        std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&body[..]), &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, uncompressed);
+
+        let (encoding, body) = call("/large", Some("deflate")).await;
+        assert_eq!(encoding.as_deref(), Some("deflate"));
+        let mut decoded = Vec::new();
+        std::io::Read::read_to_end(&mut flate2::read::ZlibDecoder::new(&body[..]), &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, uncompressed);
+
+        let (encoding, uncompressed) = call("/huge", None).await;
+        assert_eq!(encoding, None);
+        assert!(uncompressed.len() >= COMPRESSION_BLOCKING_BODY_SIZE);
+        let (encoding, body) = call("/huge", Some("gzip")).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        let mut decoded = Vec::new();
+        std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&body[..]), &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, uncompressed);
+
+        let (encoding, body) = call("/small", Some("gzip")).await;
+        assert_eq!(encoding, None);
+        assert_eq!(&body[..], b"\"ok\"");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_not_ascii() {
+        let mut service = test_service(make_router().build().unwrap()).await;
+
+        let mut req: Request<Body> = Request::default();
+        req.headers_mut().append(
+            &X_REQUEST_ID_HEADER,
+            HeaderValue::from_bytes(b"caf\xe9").unwrap(),
+        );
+
+        let resp: Response<hyper::body::Body> = service.call(req).await.unwrap();
+
+        let header_val = resp.headers().get(&X_REQUEST_ID_HEADER).unwrap();
+        let request_id = header_val.to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(request_id).is_ok(), "{request_id}");
+    }
+
     #[tokio::test]
     async fn test_request_id_empty() {
-        let builder = RequestServiceBuilder::new(make_router().build().unwrap()).unwrap();
-        let remote_addr = SocketAddr::new(IpAddr::from_str("127.0.0.1").unwrap(), 80);
-        let mut service = builder.build(remote_addr);
-        if let Err(e) = poll_fn(|ctx| service.poll_ready(ctx)).await {
-            panic!("request service is not ready: {:?}", e);
-        }
+        let mut service = test_service(make_router().build().unwrap()).await;
 
         let req: Request<Body> = Request::default();
         let resp: Response<hyper::body::Body> = service.call(req).await.unwrap();
@@ -39,6 +39,10 @@ pub enum ApiError {
     #[error("Timeout")]
     Timeout(Cow<'static, str>),
 
+    /// The server gave up on the request after its deadline.
+    #[error("Gateway timeout: {0}")]
+    GatewayTimeout(Cow<'static, str>),
+
     #[error("Request cancelled")]
     Cancelled,
 
@@ -47,50 +51,59 @@ pub enum ApiError {
 }
 
 impl ApiError {
+    /// The code reported in the response body: the one of a [`CodedError`] payload, or
+    /// [`ErrorCode::Internal`] for other internal errors.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            ApiError::NotFound(err) => err.downcast_ref::<CodedError>().map(|e| e.code),
+            ApiError::BadRequest(err) => err.downcast_ref::<CodedError>().map(|e| e.code),
+            ApiError::InternalServerError(err) => Some(
+                err.downcast_ref::<CodedError>()
+                    .map_or(ErrorCode::Internal, |e| e.code),
+            ),
+            _ => None,
+        }
+    }
+
     pub fn into_response(self) -> Response<Body> {
+        let code = self.code();
+        let (msg, status) = self.into_msg_and_status();
+        HttpErrorBody { msg, code }.to_response(status)
+    }
+
+    /// Like [`Self::into_response`], but reports `code` in the body, for errors whose payload
+    /// can't carry a [`CodedError`].
+    pub fn into_response_with_code(self, code: ErrorCode) -> Response<Body> {
+        let (msg, status) = self.into_msg_and_status();
+        HttpErrorBody {
+            msg,
+            code: Some(code),
+        }
+        .to_response(status)
+    }
+
+    fn into_msg_and_status(self) -> (String, StatusCode) {
         match self {
-            ApiError::BadRequest(err) => HttpErrorBody::response_from_msg_and_status(
+            ApiError::BadRequest(err) => (
                 format!("{err:#?}"), // use debug printing so that we give the cause
                 StatusCode::BAD_REQUEST,
             ),
-            ApiError::Forbidden(_) => {
-                HttpErrorBody::response_from_msg_and_status(self.to_string(), StatusCode::FORBIDDEN)
-            }
-            ApiError::Unauthorized(_) => HttpErrorBody::response_from_msg_and_status(
-                self.to_string(),
-                StatusCode::UNAUTHORIZED,
-            ),
-            ApiError::NotFound(_) => {
-                HttpErrorBody::response_from_msg_and_status(self.to_string(), StatusCode::NOT_FOUND)
+            ApiError::Forbidden(_) => (self.to_string(), StatusCode::FORBIDDEN),
+            ApiError::Unauthorized(_) => (self.to_string(), StatusCode::UNAUTHORIZED),
+            ApiError::NotFound(_) => (self.to_string(), StatusCode::NOT_FOUND),
+            ApiError::Conflict(_) => (self.to_string(), StatusCode::CONFLICT),
+            ApiError::PreconditionFailed(_) => (self.to_string(), StatusCode::PRECONDITION_FAILED),
+            ApiError::ShuttingDown => {
+                ("Shutting down".to_string(), StatusCode::SERVICE_UNAVAILABLE)
             }
-            ApiError::Conflict(_) => {
-                HttpErrorBody::response_from_msg_and_status(self.to_string(), StatusCode::CONFLICT)
+            ApiError::ResourceUnavailable(err) => {
+                (err.to_string(), StatusCode::SERVICE_UNAVAILABLE)
             }
-            ApiError::PreconditionFailed(_) => HttpErrorBody::response_from_msg_and_status(
-                self.to_string(),
-                StatusCode::PRECONDITION_FAILED,
-            ),
-            ApiError::ShuttingDown => HttpErrorBody::response_from_msg_and_status(
-                "Shutting down".to_string(),
-                StatusCode::SERVICE_UNAVAILABLE,
-            ),
-            ApiError::ResourceUnavailable(err) => HttpErrorBody::response_from_msg_and_status(
-                err.to_string(),
-                StatusCode::SERVICE_UNAVAILABLE,
-            ),
-            ApiError::TooManyRequests(err) => HttpErrorBody::response_from_msg_and_status(
-                err.to_string(),
-                StatusCode::TOO_MANY_REQUESTS,
-            ),
-            ApiError::Timeout(err) => HttpErrorBody::response_from_msg_and_status(
-                err.to_string(),
-                StatusCode::REQUEST_TIMEOUT,
-            ),
-            ApiError::Cancelled => HttpErrorBody::response_from_msg_and_status(
-                self.to_string(),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ),
-            ApiError::InternalServerError(err) => HttpErrorBody::response_from_msg_and_status(
+            ApiError::TooManyRequests(err) => (err.to_string(), StatusCode::TOO_MANY_REQUESTS),
+            ApiError::Timeout(err) => (err.to_string(), StatusCode::REQUEST_TIMEOUT),
+            ApiError::GatewayTimeout(_) => (self.to_string(), StatusCode::GATEWAY_TIMEOUT),
+            ApiError::Cancelled => (self.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+            ApiError::InternalServerError(err) => (
                 format!("{err:#}"), // use alternative formatting so that we give the cause without backtrace
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
@@ -107,18 +120,77 @@ fn from(_value: AuthError) -> Self {
     }
 }
 
+/// Machine-readable classification of an error in [`HttpErrorBody::code`], so that clients
+/// don't need to match on messages. Variants may be added, but existing ones are never renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    TenantNotFound,
+    TimelineNotFound,
+    TimelineConflict,
+    /// The timeline can't be deleted because it has child timelines.
+    TimelineHasChildren,
+    /// Another operation on the same object is in progress: retry later.
+    InProgress,
+    BadLsn,
+    RemoteStorageUnconfigured,
+    Internal,
+}
+
+/// An error with a machine-readable [`ErrorCode`], for the payload of [`ApiError::NotFound`],
+/// [`ApiError::BadRequest`] or [`ApiError::InternalServerError`]: [`ApiError::code`] reports it in
+/// the response body. Otherwise it formats and chains like the wrapped error.
+///
+/// Handlers that return other variants with a code build the [`HttpErrorBody`] themselves.
+#[derive(Debug)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    error: Box<dyn StdError + Send + Sync + 'static>,
+}
+
+impl CodedError {
+    pub fn new(
+        code: ErrorCode,
+        error: impl Into<Box<dyn StdError + Send + Sync + 'static>>,
+    ) -> Self {
+        CodedError {
+            code,
+            error: error.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl StdError for CodedError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.error.source()
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct HttpErrorBody {
     pub msg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
 }
 
 impl HttpErrorBody {
     pub fn from_msg(msg: String) -> Self {
-        HttpErrorBody { msg }
+        HttpErrorBody { msg, code: None }
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
     }
 
     pub fn response_from_msg_and_status(msg: String, status: StatusCode) -> Response<Body> {
-        HttpErrorBody { msg }.to_response(status)
+        HttpErrorBody::from_msg(msg).to_response(status)
     }
 
     pub fn to_response(&self, status: StatusCode) -> Response<Body> {
@@ -149,7 +221,7 @@ pub async fn route_error_handler(err: routerify::RouteError) -> Response<Body> {
 pub fn api_error_handler(api_error: ApiError) -> Response<Body> {
     // Print a stack trace for Internal Server errors
 
-    match api_error {
+    match &api_error {
         ApiError::Forbidden(_) | ApiError::Unauthorized(_) => {
             warn!("Error processing HTTP request: {api_error:#}")
         }
@@ -157,10 +229,75 @@ pub fn api_error_handler(api_error: ApiError) -> Response<Body> {
         ApiError::NotFound(_) => info!("Error processing HTTP request: {api_error:#}"),
         ApiError::InternalServerError(_) => error!("Error processing HTTP request: {api_error:?}"),
         ApiError::ShuttingDown => info!("Shut down while processing HTTP request"),
-        ApiError::Timeout(_) => info!("Timeout while processing HTTP request: {api_error:#}"),
+        ApiError::Timeout(_) | ApiError::GatewayTimeout(_) => {
+            info!("Timeout while processing HTTP request: {api_error:#}")
+        }
         ApiError::Cancelled => info!("Request cancelled while processing HTTP request"),
         _ => info!("Error processing HTTP request: {api_error:#}"),
     }
 
     api_error.into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn response_body(err: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = err.into_response();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_error_code() {
+        let (status, body) = response_body(ApiError::NotFound(
+            CodedError::new(ErrorCode::TenantNotFound, anyhow::anyhow!("tenant 1234")).into(),
+        ))
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            serde_json::json!({"msg": "NotFound: tenant 1234", "code": "tenant_not_found"})
+        );
+
+        let (status, body) =
+            response_body(ApiError::InternalServerError(anyhow::anyhow!("oops"))).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "internal");
+
+        let response = ApiError::Conflict("deletion in progress".to_string())
+            .into_response_with_code(ErrorCode::InProgress);
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "in_progress");
+
+        let (status, body) =
+            response_body(ApiError::BadRequest(anyhow::Error::new(CodedError::new(
+                ErrorCode::BadLsn,
+                anyhow::anyhow!("not an lsn").context("Invalid LSN"),
+            ))))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_lsn");
+        // The wrapped error's cause is still reported
+        let msg = body["msg"].as_str().unwrap();
+        assert!(
+            msg.contains("Invalid LSN") && msg.contains("not an lsn"),
+            "{msg}"
+        );
+
+        // The payload is still matched as usual
+        let err = ApiError::NotFound(CodedError::new(ErrorCode::TimelineNotFound, "gone").into());
+        assert!(matches!(err, ApiError::NotFound(_)));
+        assert_eq!(err.code(), Some(ErrorCode::TimelineNotFound));
+
+        // Errors without a code keep the old body, and old bodies parse without a code
+        let (_, body) = response_body(ApiError::Conflict("busy".to_string())).await;
+        assert_eq!(body, serde_json::json!({"msg": "Conflict: busy"}));
+        let parsed: HttpErrorBody = serde_json::from_value(body).unwrap();
+        assert_eq!(parsed.code, None);
+    }
+}
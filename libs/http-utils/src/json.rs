@@ -4,6 +4,7 @@
 use serde::{Deserialize, Serialize};
 
 use super::error::ApiError;
+use super::request::parse_query_param;
 
 /// Parse a json request body and deserialize it to the type `T`.
 pub async fn json_request<T: for<'de> Deserialize<'de>>(
@@ -53,9 +54,30 @@ pub fn json_response<T: Serialize>(
     status: StatusCode,
     data: T,
 ) -> Result<Response<Body>, ApiError> {
-    let json = serde_json::to_string(&data)
+    let json = serde_json::to_vec(&data)
         .context("Failed to serialize JSON response")
         .map_err(ApiError::InternalServerError)?;
+    json_bytes_response(status, json)
+}
+
+/// Like [`json_response`], but indents the output if the request has `?pretty=true`. Meant for
+/// endpoints that dump structures too large to eyeball in compact form.
+pub fn json_response_maybe_pretty<T: Serialize>(
+    request: &Request<Body>,
+    status: StatusCode,
+    data: T,
+) -> Result<Response<Body>, ApiError> {
+    let pretty: Option<bool> = parse_query_param(request, "pretty")?;
+    if !pretty.unwrap_or(false) {
+        return json_response(status, data);
+    }
+    let json = serde_json::to_vec_pretty(&data)
+        .context("Failed to serialize JSON response")
+        .map_err(ApiError::InternalServerError)?;
+    json_bytes_response(status, json)
+}
+
+fn json_bytes_response(status: StatusCode, json: Vec<u8>) -> Result<Response<Body>, ApiError> {
     let response = Response::builder()
         .status(status)
         .header(header::CONTENT_TYPE, "application/json")
@@ -21,16 +21,19 @@ pub fn get_request_param<'a>(
     }
 }
 
-pub fn parse_request_param<T: FromStr>(
-    request: &Request<Body>,
-    param_name: &str,
-) -> Result<T, ApiError> {
-    match get_request_param(request, param_name)?.parse() {
-        Ok(v) => Ok(v),
-        Err(_) => Err(ApiError::BadRequest(anyhow!(
-            "failed to parse {param_name}",
-        ))),
-    }
+/// Parses a path param. A malformed value is the client's fault, so it is always reported as a
+/// bad request naming the param, whatever the param's type.
+pub fn parse_request_param<T>(request: &Request<Body>, param_name: &str) -> Result<T, ApiError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = get_request_param(request, param_name)?;
+    value.parse().map_err(|e| {
+        ApiError::BadRequest(anyhow!(
+            "cannot parse path param {param_name} {value:?}: {e}"
+        ))
+    })
 }
 
 pub fn get_query_param<'a>(
@@ -92,3 +95,79 @@ pub async fn ensure_no_body(request: &mut Request<Body>) -> Result<(), ApiError>
         None => Ok(()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hyper::service::Service;
+    use hyper::{Response, StatusCode};
+    use utils::id::{TenantId, TimelineId};
+    use utils::lsn::Lsn;
+
+    use super::*;
+    use crate::endpoint::{make_router, test_service};
+    use crate::json::json_response;
+
+    #[tokio::test]
+    async fn test_parse_request_param_bad_request() {
+        let router = make_router()
+            .get(
+                "/tenant/:tenant_id/timeline/:timeline_id/lsn/:lsn",
+                |r| async move {
+                    let _: TenantId = parse_request_param(&r, "tenant_id")?;
+                    let _: TimelineId = parse_request_param(&r, "timeline_id")?;
+                    let _: Lsn = parse_request_param(&r, "lsn")?;
+                    json_response(StatusCode::OK, ())
+                },
+            )
+            .build()
+            .unwrap();
+        let mut service = test_service(router).await;
+
+        let tenant_id = TenantId::generate();
+        let timeline_id = TimelineId::generate();
+        for (tenant_id, timeline_id, lsn, bad_param) in [
+            (
+                tenant_id.to_string(),
+                timeline_id.to_string(),
+                "0/16B5A50",
+                None,
+            ),
+            (
+                "not-a-tenant".to_owned(),
+                timeline_id.to_string(),
+                "0/16B5A50",
+                Some("tenant_id"),
+            ),
+            // A well-formed UUID is still not a hex id
+            (
+                tenant_id.to_string(),
+                "3fa85f64-5717-4562-b3fc-2c963f66afa6".to_owned(),
+                "0/16B5A50",
+                Some("timeline_id"),
+            ),
+            (
+                tenant_id.to_string(),
+                timeline_id.to_string(),
+                "16B5A50",
+                Some("lsn"),
+            ),
+        ] {
+            let uri = format!("/tenant/{tenant_id}/timeline/{timeline_id}/lsn/{lsn}");
+            let req = Request::builder().uri(&uri).body(Body::empty()).unwrap();
+            let resp: Response<Body> = service.call(req).await.unwrap();
+            let status = resp.status();
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            let body = String::from_utf8_lossy(&body);
+            match bad_param {
+                None => assert_eq!(status, StatusCode::OK, "{uri}: {body}"),
+                Some(param) => {
+                    assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}: {body}");
+                    assert!(
+                        body.contains(&format!("cannot parse path param {param}")),
+                        "{uri}: {body}"
+                    );
+                }
+            }
+        }
+    }
+}
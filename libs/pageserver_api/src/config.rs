@@ -124,6 +124,9 @@ pub struct ConfigToml {
     #[serde_as(as = "serde_with::DisplayFromStr")]
     pub pg_auth_type: AuthType,
     pub auth_validation_public_key_path: Option<Utf8PathBuf>,
+    pub http_basic_auth: Option<HttpBasicAuthConfig>,
+    #[serde(with = "humantime_serde")]
+    pub http_request_timeout: Duration,
     pub remote_storage: Option<RemoteStorageConfig>,
     pub tenant_config: TenantConfigToml,
     #[serde_as(as = "serde_with::DisplayFromStr")]
@@ -134,6 +137,7 @@ pub struct ConfigToml {
     pub log_format: LogFormat,
     pub concurrent_tenant_warmup: NonZeroUsize,
     pub concurrent_tenant_size_logical_size_queries: NonZeroUsize,
+    pub concurrent_non_incremental_logical_size_queries: NonZeroUsize,
     #[serde(with = "humantime_serde")]
     pub metric_collection_interval: Duration,
     pub metric_collection_endpoint: Option<reqwest::Url>,
@@ -184,6 +188,25 @@ pub struct ConfigToml {
     pub dev_mode: bool,
 }
 
+/// A username and password accepted by the management API through HTTP Basic auth, as an
+/// alternative to JWTs for tooling that can't mint them. Only used with `http_auth_type = "NeonJWT"`,
+/// and grants the same access as a pageserver-scoped token.
+#[derive(Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpBasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for HttpBasicAuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpBasicAuthConfig")
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct DiskUsageEvictionTaskConfig {
     pub max_usage_pct: utils::serde_percent::Percent,
@@ -483,6 +506,9 @@ pub struct TenantConfigToml {
     /// Tenant level performance sampling ratio override. Controls the ratio of get page requests
     /// that will get perf sampling for the tenant.
     pub sampling_ratio: Option<Ratio>,
+    /// Trace every get page request of the tenant, regardless of `sampling_ratio`.
+    /// Turning it off again leaves `sampling_ratio` in effect.
+    pub trace_read_requests: bool,
 }
 
 pub mod defaults {
@@ -492,6 +518,7 @@ pub mod defaults {
 
     pub const DEFAULT_WAIT_LSN_TIMEOUT: &str = "300 s";
     pub const DEFAULT_WAL_REDO_TIMEOUT: &str = "60 s";
+    pub const DEFAULT_HTTP_REQUEST_TIMEOUT: &str = "60 s";
 
     pub const DEFAULT_SUPERUSER: &str = "cloud_admin";
     pub const DEFAULT_LOCALE: &str = if cfg!(target_os = "macos") {
@@ -509,6 +536,8 @@ pub mod defaults {
 
     pub const DEFAULT_CONCURRENT_TENANT_SIZE_LOGICAL_SIZE_QUERIES: usize = 1;
 
+    pub const DEFAULT_CONCURRENT_NON_INCREMENTAL_LOGICAL_SIZE_QUERIES: usize = 4;
+
     pub const DEFAULT_METRIC_COLLECTION_INTERVAL: &str = "10 min";
     pub const DEFAULT_METRIC_COLLECTION_ENDPOINT: Option<reqwest::Url> = None;
     pub const DEFAULT_SYNTHETIC_SIZE_CALCULATION_INTERVAL: &str = "10 min";
@@ -567,6 +596,9 @@ fn default() -> Self {
             http_auth_type: (AuthType::Trust),
             pg_auth_type: (AuthType::Trust),
             auth_validation_public_key_path: (None),
+            http_basic_auth: None,
+            http_request_timeout: (humantime::parse_duration(DEFAULT_HTTP_REQUEST_TIMEOUT)
+                .expect("cannot parse default http request timeout")),
             remote_storage: None,
             broker_endpoint: (storage_broker::DEFAULT_ENDPOINT
                 .parse()
@@ -583,6 +615,10 @@ fn default() -> Self {
                 DEFAULT_CONCURRENT_TENANT_SIZE_LOGICAL_SIZE_QUERIES,
             )
             .unwrap(),
+            concurrent_non_incremental_logical_size_queries: NonZeroUsize::new(
+                DEFAULT_CONCURRENT_NON_INCREMENTAL_LOGICAL_SIZE_QUERIES,
+            )
+            .unwrap(),
             metric_collection_interval: (humantime::parse_duration(
                 DEFAULT_METRIC_COLLECTION_INTERVAL,
             )
@@ -782,6 +818,7 @@ fn default() -> Self {
             gc_compaction_initial_threshold_kb: DEFAULT_GC_COMPACTION_INITIAL_THRESHOLD_KB,
             gc_compaction_ratio_percent: DEFAULT_GC_COMPACTION_RATIO_PERCENT,
             sampling_ratio: None,
+            trace_read_requests: false,
         }
     }
 }
@@ -233,7 +233,16 @@ pub enum ActivatingFrom {
 }
 
 /// A state of a timeline in pageserver's memory.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum_macros::VariantNames,
+    strum_macros::IntoStaticStr,
+)]
 pub enum TimelineState {
     /// The timeline is recognized by the pageserver but is not yet operational.
     /// In particular, the walreceiver connection loop is not running for this timeline.
@@ -334,6 +343,17 @@ pub fn is_import(&self) -> bool {
     }
 }
 
+/// Request body for `POST /v1/tenant/:tenant_shard_id/timeline/:timeline_id/branch`.
+///
+/// Unlike [`TimelineCreateRequest`], the ancestor is always given (in the path) and the branch
+/// point is mandatory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TimelineBranchRequest {
+    pub new_timeline_id: TimelineId,
+    pub ancestor_start_lsn: Lsn,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum ShardImportStatus {
     InProgress,
@@ -614,8 +634,15 @@ pub struct TenantConfigPatch {
     pub gc_compaction_ratio_percent: FieldPatch<u64>,
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub sampling_ratio: FieldPatch<Option<Ratio>>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub trace_read_requests: FieldPatch<bool>,
 }
 
+/// The fields of a [`TenantConfigPatch`] whose durations don't parse, with the reason for each.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid durations: {}", .0.iter().map(|(field, e)| format!("{field}: {e}")).collect::<Vec<_>>().join(", "))]
+pub struct TenantConfigPatchError(pub Vec<(&'static str, humantime::DurationError)>);
+
 /// Like [`crate::config::TenantConfigToml`], but preserves the information
 /// about which parameters are set and which are not.
 ///
@@ -743,13 +770,17 @@ pub struct TenantConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sampling_ratio: Option<Option<Ratio>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_read_requests: Option<bool>,
 }
 
 impl TenantConfig {
+    /// Fails if any duration of the patch doesn't parse, naming all such durations.
     pub fn apply_patch(
         self,
         patch: TenantConfigPatch,
-    ) -> Result<TenantConfig, humantime::DurationError> {
+    ) -> Result<TenantConfig, TenantConfigPatchError> {
         let Self {
             mut checkpoint_distance,
             mut checkpoint_timeout,
@@ -788,20 +819,26 @@ pub fn apply_patch(
             mut gc_compaction_initial_threshold_kb,
             mut gc_compaction_ratio_percent,
             mut sampling_ratio,
+            mut trace_read_requests,
         } = self;
 
+        let mut errors = Vec::new();
+        let mut parse_duration = |field, value: FieldPatch<String>| {
+            value
+                .map(|v| humantime::parse_duration(&v))
+                .unwrap_or_else(|e| {
+                    errors.push((field, e));
+                    FieldPatch::Noop
+                })
+        };
+
         patch.checkpoint_distance.apply(&mut checkpoint_distance);
-        patch
-            .checkpoint_timeout
-            .map(|v| humantime::parse_duration(&v))?
+        parse_duration("checkpoint_timeout", patch.checkpoint_timeout)
             .apply(&mut checkpoint_timeout);
         patch
             .compaction_target_size
             .apply(&mut compaction_target_size);
-        patch
-            .compaction_period
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut compaction_period);
+        parse_duration("compaction_period", patch.compaction_period).apply(&mut compaction_period);
         patch.compaction_threshold.apply(&mut compaction_threshold);
         patch
             .compaction_upper_limit
@@ -821,38 +858,29 @@ pub fn apply_patch(
             .l0_flush_stall_threshold
             .apply(&mut l0_flush_stall_threshold);
         patch.gc_horizon.apply(&mut gc_horizon);
-        patch
-            .gc_period
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut gc_period);
+        parse_duration("gc_period", patch.gc_period).apply(&mut gc_period);
         patch
             .image_creation_threshold
             .apply(&mut image_creation_threshold);
-        patch
-            .pitr_interval
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut pitr_interval);
-        patch
-            .walreceiver_connect_timeout
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut walreceiver_connect_timeout);
-        patch
-            .lagging_wal_timeout
-            .map(|v| humantime::parse_duration(&v))?
+        parse_duration("pitr_interval", patch.pitr_interval).apply(&mut pitr_interval);
+        parse_duration(
+            "walreceiver_connect_timeout",
+            patch.walreceiver_connect_timeout,
+        )
+        .apply(&mut walreceiver_connect_timeout);
+        parse_duration("lagging_wal_timeout", patch.lagging_wal_timeout)
             .apply(&mut lagging_wal_timeout);
         patch.max_lsn_wal_lag.apply(&mut max_lsn_wal_lag);
         patch.eviction_policy.apply(&mut eviction_policy);
         patch
             .min_resident_size_override
             .apply(&mut min_resident_size_override);
-        patch
-            .evictions_low_residence_duration_metric_threshold
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut evictions_low_residence_duration_metric_threshold);
-        patch
-            .heatmap_period
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut heatmap_period);
+        parse_duration(
+            "evictions_low_residence_duration_metric_threshold",
+            patch.evictions_low_residence_duration_metric_threshold,
+        )
+        .apply(&mut evictions_low_residence_duration_metric_threshold);
+        parse_duration("heatmap_period", patch.heatmap_period).apply(&mut heatmap_period);
         patch.lazy_slru_download.apply(&mut lazy_slru_download);
         patch
             .timeline_get_throttle
@@ -863,13 +891,8 @@ pub fn apply_patch(
         patch
             .image_creation_preempt_threshold
             .apply(&mut image_creation_preempt_threshold);
-        patch
-            .lsn_lease_length
-            .map(|v| humantime::parse_duration(&v))?
-            .apply(&mut lsn_lease_length);
-        patch
-            .lsn_lease_length_for_ts
-            .map(|v| humantime::parse_duration(&v))?
+        parse_duration("lsn_lease_length", patch.lsn_lease_length).apply(&mut lsn_lease_length);
+        parse_duration("lsn_lease_length_for_ts", patch.lsn_lease_length_for_ts)
             .apply(&mut lsn_lease_length_for_ts);
         patch.timeline_offloading.apply(&mut timeline_offloading);
         patch
@@ -889,6 +912,11 @@ pub fn apply_patch(
             .gc_compaction_ratio_percent
             .apply(&mut gc_compaction_ratio_percent);
         patch.sampling_ratio.apply(&mut sampling_ratio);
+        patch.trace_read_requests.apply(&mut trace_read_requests);
+
+        if !errors.is_empty() {
+            return Err(TenantConfigPatchError(errors));
+        }
 
         Ok(Self {
             checkpoint_distance,
@@ -928,6 +956,7 @@ pub fn apply_patch(
             gc_compaction_initial_threshold_kb,
             gc_compaction_ratio_percent,
             sampling_ratio,
+            trace_read_requests,
         })
     }
 
@@ -1036,6 +1065,9 @@ pub fn merge(
                 .gc_compaction_ratio_percent
                 .unwrap_or(global_conf.gc_compaction_ratio_percent),
             sampling_ratio: self.sampling_ratio.unwrap_or(global_conf.sampling_ratio),
+            trace_read_requests: self
+                .trace_read_requests
+                .unwrap_or(global_conf.trace_read_requests),
         }
     }
 }
@@ -1319,6 +1351,55 @@ pub struct StatusResponse {
     pub id: NodeId,
 }
 
+/// Response of `GET /v1/ready`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadinessResponse {
+    pub id: NodeId,
+    pub ready: bool,
+    /// Number of tenant shards in each [`TenantState`], by variant name. Empty while the tenant
+    /// map is still initializing.
+    pub tenants_by_state: HashMap<String, usize>,
+}
+
+/// Redacted view of the pageserver's effective configuration, as returned by `GET /v1/config`.
+///
+/// Only non-sensitive settings are included: tokens, keys and remote storage credentials are
+/// deliberately left out, and URLs are stripped of their userinfo and query.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PageserverConfigResponse {
+    pub id: NodeId,
+    pub listen_pg_addr: String,
+    pub listen_http_addr: String,
+    pub listen_https_addr: Option<String>,
+    pub availability_zone: Option<String>,
+    pub workdir: Utf8PathBuf,
+    pub pg_distrib_dir: Utf8PathBuf,
+    pub default_pg_version: u32,
+    pub http_auth_type: String,
+    pub pg_auth_type: String,
+    pub broker_endpoint: String,
+    pub control_plane_api: String,
+    pub control_plane_emergency_mode: bool,
+    #[serde(with = "humantime_serde")]
+    pub wait_lsn_timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub wal_redo_timeout: Duration,
+    pub page_cache_size: usize,
+    pub max_file_descriptors: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LogLevelRequest {
+    /// A filter directive in `RUST_LOG` syntax, e.g. `info,pageserver::tenant=debug`.
+    pub directive: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LogLevelResponse {
+    /// The directive that was active before the request was applied.
+    pub previous_directive: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct TenantLocationConfigRequest {
@@ -1378,6 +1459,13 @@ pub struct TenantConfigPatchRequest {
     pub config: TenantConfigPatch, // as we have a flattened field, we should reject all unknown fields in it
 }
 
+/// Response of `PUT /v1/tenant/:tenant_shard_id/trace`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TenantTraceResponse {
+    /// The tenant's `trace_read_requests` setting after the change.
+    pub enabled: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TenantWaitLsnRequest {
     #[serde(flatten)]
@@ -1412,6 +1500,15 @@ pub struct TenantInfo {
     pub gc_blocking: Option<String>,
 }
 
+/// A page of the tenant list, returned if a `limit` is passed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TenantList {
+    /// Ordered by `id`.
+    pub tenants: Vec<TenantInfo>,
+    /// Pass as `cursor` to get the next page; `None` on the last page.
+    pub next_cursor: Option<TenantShardId>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TenantDetails {
     #[serde(flatten)]
@@ -1439,6 +1536,28 @@ pub struct TimelineArchivalConfigRequest {
     pub state: TimelineArchivalState,
 }
 
+/// Request body of `DELETE /v1/tenant/:tenant_shard_id/timeline`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelinesDeleteRequest {
+    pub timeline_ids: Vec<TimelineId>,
+}
+
+/// Outcome of deleting one timeline of a [`TimelinesDeleteRequest`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TimelineDeleteResult {
+    /// Deletion was started, and completes in the background like a single timeline deletion.
+    Accepted,
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelinesDeleteResponse {
+    pub results: HashMap<TimelineId, TimelineDeleteResult>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct TimelinePatchIndexPartRequest {
     pub rel_size_migration: Option<RelSizeMigration>,
@@ -1448,10 +1567,55 @@ pub struct TimelinePatchIndexPartRequest {
     pub force_index_update: bool,
 }
 
+/// Request body of `POST /v1/tenant/:tenant_shard_id/timeline/:timeline_id/prefetch`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelinePrefetchRequest {
+    /// Layers by their file name.
+    Layers(Vec<String>),
+    /// All layers overlapping both ranges.
+    Range {
+        key_range: CompactKeyRange,
+        lsn_range: CompactLsnRange,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct TimelinePrefetchResponse {
+    /// Layers that were downloaded by this request.
+    pub downloaded: Vec<String>,
+    /// Layers that were already resident, and were left alone.
+    pub already_resident: Vec<String>,
+    /// Requested layer names that aren't valid or aren't part of the timeline.
+    pub not_found: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct TimelineReconcileIndexResponse {
+    /// Layers that were missing from the previous remote index.
+    pub added_layers: usize,
+    /// Layers of the previous remote index that the timeline doesn't have.
+    pub removed_layers: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TimelinesInfoAndOffloaded {
     pub timelines: Vec<TimelineInfo>,
     pub offloaded: Vec<OffloadedTimelineInfo>,
+    /// Per-timeline failures that left some optional fields of [`Self::timelines`] unset.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<TimelineListWarning>,
+}
+
+/// Header of `GET /v1/tenant/:tenant_shard_id/timeline?warnings=true` responses that holds the
+/// [`TimelineListWarning`]s as a JSON array, if there are any. The body stays a plain array.
+pub const TIMELINE_LIST_WARNINGS_HEADER: &str = "pageserver-timeline-list-warnings";
+
+/// A failure to fill in part of a timeline's info that didn't fail the whole listing.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct TimelineListWarning {
+    pub timeline_id: TimelineId,
+    pub message: String,
 }
 
 /// Analog of [`TimelineInfo`] for offloaded timelines.
@@ -1564,6 +1728,21 @@ pub struct TimelineInfo {
 
     /// Whether the timeline is invisible in synthetic size calculations.
     pub is_invisible: Option<bool>,
+
+    /// See [`WalLag::wal_lag_bytes`].
+    pub wal_lag_bytes: Option<u64>,
+    /// See [`WalLag::wal_lag_seconds`].
+    pub wal_lag_seconds: Option<f64>,
+}
+
+/// How far a timeline trails its WAL source. Both fields are `None` until the timeline has received
+/// WAL since it was loaded.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WalLag {
+    /// Bytes between the last LSN received from the WAL source and the last ingested record.
+    pub wal_lag_bytes: Option<u64>,
+    /// Seconds since the last message from the WAL source.
+    pub wal_lag_seconds: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1572,6 +1751,32 @@ pub struct LayerMapInfo {
     pub historic_layers: Vec<HistoricLayerInfo>,
 }
 
+/// The historic layers of a timeline as rectangles in key × LSN space, for plotting the layer map.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerMapExtents {
+    pub layers: Vec<LayerExtent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayerExtentKind {
+    Image,
+    Delta,
+}
+
+/// Both ranges are half-open. Image layers cover a single LSN, i.e. `lsn_end == lsn_start + 1`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerExtent {
+    pub layer_file_name: String,
+    pub kind: LayerExtentKind,
+    /// Hex-encoded, fixed width: keys order the same way as their string representation.
+    pub key_start: String,
+    pub key_end: String,
+    #[serde(with = "utils::lsn::serde_as_u64")]
+    pub lsn_start: Lsn,
+    #[serde(with = "utils::lsn::serde_as_u64")]
+    pub lsn_end: Lsn,
+}
+
 /// The residence status of a layer
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum LayerResidenceStatus {
@@ -1679,6 +1884,8 @@ pub struct ListAuxFilesRequest {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DownloadRemoteLayersTaskInfo {
     pub task_id: String,
+    /// The job to follow the task with, see [`JobInfo`].
+    pub job_id: u64,
     pub state: DownloadRemoteLayersTaskState,
     pub total_layer_count: u64,         // stable once `completed`
     pub successful_download_count: u64, // stable once `completed`
@@ -1692,9 +1899,67 @@ pub enum DownloadRemoteLayersTaskState {
     ShutDown,
 }
 
+/// A background job, as returned by `GET /v1/jobs/:job_id`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JobInfo {
+    pub job_id: u64,
+    /// What the job does, e.g. `download_remote_layers`.
+    pub kind: String,
+    /// The step the job is at. Which phases a job goes through depends on its kind.
+    pub phase: String,
+    pub layers_processed: u64,
+    pub state: JobState,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    /// Stopped before completing, e.g. because of a shutdown.
+    Aborted,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TimelineGcRequest {
     pub gc_horizon: Option<u64>,
+    /// Only report what GC would do: the cutoff is not moved and no layers are removed.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineEvictLayerRequest {
+    pub layer_file_name: String,
+}
+
+/// Response of `PUT /v1/tenant/:tenant_shard_id/checkpoint`: one entry per timeline of the tenant.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantCheckpointResponse {
+    pub timelines: Vec<TimelineCheckpointResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineCheckpointResult {
+    pub timeline_id: TimelineId,
+    /// `None` if the timeline was checkpointed, otherwise the reason it was not.
+    pub error: Option<String>,
+}
+
+/// Response of `PUT /v1/tenant/:tenant_shard_id/compact`: one entry per timeline of the tenant, in
+/// the order they were compacted.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantCompactResponse {
+    pub timelines: Vec<TimelineCompactResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineCompactResult {
+    pub timeline_id: TimelineId,
+    /// `None` if the timeline was compacted, otherwise the reason it was not.
+    pub error: Option<String>,
+    /// Time spent on this timeline, in milliseconds.
+    pub elapsed_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1802,6 +2067,49 @@ pub struct TopTenantShardsResponse {
     pub shards: Vec<TopTenantShardItem>,
 }
 
+/// Disk usage of a tenant shard's layers, summed across its timelines.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TenantDiskUsage {
+    /// Size of all layers, the sum of all the buckets below. Same as the `current_physical_size`
+    /// of the tenant's status.
+    pub total_physical_size: u64,
+    pub image: LayerDiskUsage,
+    pub delta: LayerDiskUsage,
+}
+
+/// Disk usage of one type of layers, see [`TenantDiskUsage`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LayerDiskUsage {
+    /// Size of the layers with a local file.
+    pub resident_size: u64,
+    /// Size of the layers that are only in remote storage.
+    pub remote_only_size: u64,
+}
+
+/// A background task running on a pageserver, as listed by `GET /v1/tasks`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackgroundTaskInfo {
+    pub task_id: u64,
+    /// The pageserver's `TaskKind`, e.g. `Compaction` or `RemoteUploadTask`.
+    pub kind: String,
+    pub name: String,
+    pub tenant_shard_id: TenantShardId,
+    pub timeline_id: Option<TimelineId>,
+    #[serde_as(as = "SystemTimeAsRfc3339Millis")]
+    pub started_at: SystemTime,
+    /// What the task is currently doing, as reported by the task itself.
+    pub phase: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BackgroundTaskList {
+    /// Ordered by `task_id`.
+    pub tasks: Vec<BackgroundTaskInfo>,
+    /// Pass as `cursor` to get the next page; `None` on the last page.
+    pub next_cursor: Option<u64>,
+}
+
 pub mod virtual_file {
     use std::sync::LazyLock;
 
@@ -2914,4 +3222,22 @@ fn test_tenant_config_patch_request_serde() {
 
         assert_eq!(patched, expected);
     }
+
+    #[test]
+    fn test_tenant_config_patch_reports_all_bad_durations() {
+        let patch = TenantConfigPatch {
+            gc_period: FieldPatch::Upsert("1 fortnight".to_string()),
+            pitr_interval: FieldPatch::Upsert("7 days".to_string()),
+            heatmap_period: FieldPatch::Upsert("60s".to_string()),
+            lsn_lease_length: FieldPatch::Upsert("10".to_string()),
+            ..Default::default()
+        };
+
+        let err = TenantConfig::default().apply_patch(patch).unwrap_err();
+        let fields: Vec<_> = err.0.iter().map(|(field, _)| *field).collect();
+        assert_eq!(fields, ["gc_period", "lsn_lease_length"]);
+        let message = err.to_string();
+        assert!(message.contains("gc_period"), "{message}");
+        assert!(message.contains("lsn_lease_length"), "{message}");
+    }
 }
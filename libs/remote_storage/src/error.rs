@@ -75,6 +75,36 @@ fn from(value: std::io::Error) -> Self {
     }
 }
 
+/// Reasons for [`crate::RemotePath::from_string`] to reject a path.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RemotePathError {
+    /// The path was empty.
+    Empty,
+    /// The path contained a `\`, which is not a separator in object keys.
+    Backslash(String),
+    /// The path was absolute, object keys are always relative to the bucket prefix.
+    Absolute(String),
+    /// The path contained a `..` component.
+    ParentTraversal(String),
+}
+
+impl std::fmt::Display for RemotePathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemotePathError::Empty => write!(f, "Remote path is empty"),
+            RemotePathError::Backslash(path) => {
+                write!(f, "Remote path {path:?} contains a '\\'")
+            }
+            RemotePathError::Absolute(path) => write!(f, "Remote path {path:?} is not relative"),
+            RemotePathError::ParentTraversal(path) => {
+                write!(f, "Remote path {path:?} contains a '..' component")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RemotePathError {}
+
 #[derive(Debug)]
 pub enum TimeTravelError {
     /// Validation or other error happened due to user input.
@@ -30,8 +30,8 @@
 /// Azure SDK's ETag type is a simple String wrapper: we use this internally instead of repeating it here.
 pub use azure_core::Etag;
 use bytes::Bytes;
-use camino::{Utf8Path, Utf8PathBuf};
-pub use error::{DownloadError, TimeTravelError, TimeoutOrCancel};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+pub use error::{DownloadError, RemotePathError, TimeTravelError, TimeoutOrCancel};
 use futures::StreamExt;
 use futures::stream::Stream;
 use itertools::Itertools as _;
@@ -103,7 +103,15 @@ fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         D: serde::Deserializer<'de>,
     {
         let str = String::deserialize(deserializer)?;
-        Ok(Self(Utf8PathBuf::from(&str)))
+        Self::from_string(&str).map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::str::FromStr for RemotePath {
+    type Err = RemotePathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_string(s)
     }
 }
 
@@ -122,8 +130,22 @@ pub fn new(relative_path: &Utf8Path) -> anyhow::Result<Self> {
         Ok(Self(relative_path.to_path_buf()))
     }
 
-    pub fn from_string(relative_path: &str) -> anyhow::Result<Self> {
-        Self::new(Utf8Path::new(relative_path))
+    /// Parses a path from external input. Unlike [`Self::new`], this rejects empty paths, `\`
+    /// and `..` components. Also used by the `FromStr` and `Deserialize` impls.
+    pub fn from_string(relative_path: &str) -> Result<Self, RemotePathError> {
+        if relative_path.is_empty() {
+            return Err(RemotePathError::Empty);
+        }
+        if relative_path.contains('\\') {
+            return Err(RemotePathError::Backslash(relative_path.to_string()));
+        }
+        if relative_path.starts_with('/') || Utf8Path::new(relative_path).is_absolute() {
+            return Err(RemotePathError::Absolute(relative_path.to_string()));
+        }
+        if relative_path.split('/').any(|component| component == "..") {
+            return Err(RemotePathError::ParentTraversal(relative_path.to_string()));
+        }
+        Ok(Self(Utf8PathBuf::from(relative_path)))
     }
 
     pub fn with_base(&self, base_path: &Utf8Path) -> Utf8PathBuf {
@@ -134,6 +156,19 @@ pub fn object_name(&self) -> Option<&str> {
         self.0.file_name()
     }
 
+    /// The full key of the object, relative to the storage's prefix: the components joined by
+    /// `/`, without leading or trailing separators, regardless of the platform's separator.
+    pub fn object_key(&self) -> String {
+        self.0
+            .components()
+            .filter_map(|component| match component {
+                Utf8Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
     pub fn join(&self, path: impl AsRef<Utf8Path>) -> Self {
         Self(self.0.join(path))
     }
@@ -150,6 +185,18 @@ pub fn add_trailing_slash(&self) -> Self {
         // Unwrap safety inputs are guararnteed to be valid UTF-8
         Self(format!("{}/", self.0).try_into().unwrap())
     }
+
+    /// Compares paths the way object stores that fold ASCII case do: there, `Layers/foo` and
+    /// `layers/foo` refer to the same object. The regular `Eq`/`Ord` stay case-sensitive.
+    pub fn eq_ignore_ascii_case(&self, other: &RemotePath) -> bool {
+        self.0.as_str().eq_ignore_ascii_case(other.0.as_str())
+    }
+
+    /// Returns the path with ASCII letters lowercased, for use as a key when deduplicating
+    /// paths destined for a case-folding object store.
+    pub fn normalized_for_case_insensitive(&self) -> RemotePath {
+        Self(Utf8PathBuf::from(self.0.as_str().to_ascii_lowercase()))
+    }
 }
 
 /// We don't need callers to be able to pass arbitrary delimiters: just control
@@ -883,6 +930,80 @@ fn download_opts_byte_range_negative() {
         .byte_range();
     }
 
+    #[test]
+    fn test_from_string_validation() {
+        assert_eq!(
+            RemotePath::from_string("/tenants/foo"),
+            Err(RemotePathError::Absolute("/tenants/foo".to_string()))
+        );
+        assert_eq!(
+            RemotePath::from_string("tenants/../foo"),
+            Err(RemotePathError::ParentTraversal(
+                "tenants/../foo".to_string()
+            ))
+        );
+        assert_eq!(RemotePath::from_string(""), Err(RemotePathError::Empty));
+        assert_eq!(
+            RemotePath::from_string("tenants\\foo/index_part.json"),
+            Err(RemotePathError::Backslash(
+                "tenants\\foo/index_part.json".to_string()
+            ))
+        );
+        // Dots inside a component are not traversal
+        assert!(RemotePath::from_string("tenants/foo..bar").is_ok());
+    }
+
+    #[test]
+    fn test_parse_and_deserialize_are_validated() {
+        let path: RemotePath = "tenants/foo/index_part.json".parse().unwrap();
+        assert_eq!(
+            path,
+            RemotePath::from_string("tenants/foo/index_part.json").unwrap()
+        );
+        assert_eq!(
+            "tenants/../foo".parse::<RemotePath>(),
+            Err(RemotePathError::ParentTraversal(
+                "tenants/../foo".to_string()
+            ))
+        );
+
+        let path: RemotePath = serde_json::from_str(r#""tenants/foo""#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&path).unwrap(),
+            r#""tenants/foo""#.to_string()
+        );
+        for invalid in [r#""""#, r#""/tenants""#, r#""tenants\\foo""#, r#""../foo""#] {
+            assert!(
+                serde_json::from_str::<RemotePath>(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_eq_ignore_ascii_case() {
+        let a = RemotePath::from_string("Layers/Foo").unwrap();
+        let b = RemotePath::from_string("layers/foo").unwrap();
+        assert_ne!(a, b);
+        assert!(a.eq_ignore_ascii_case(&b));
+        assert_eq!(
+            a.normalized_for_case_insensitive(),
+            b.normalized_for_case_insensitive()
+        );
+
+        let c = RemotePath::from_string("layers/bar").unwrap();
+        assert!(!a.eq_ignore_ascii_case(&c));
+        assert_ne!(
+            a.normalized_for_case_insensitive(),
+            c.normalized_for_case_insensitive()
+        );
+
+        // Non-ASCII characters are compared as-is.
+        let d = RemotePath::from_string("layers/É").unwrap();
+        let e = RemotePath::from_string("layers/é").unwrap();
+        assert!(!d.eq_ignore_ascii_case(&e));
+    }
+
     #[test]
     fn test_object_name() {
         let k = RemotePath::new(Utf8Path::new("a/b/c")).unwrap();
@@ -899,6 +1020,19 @@ fn test_object_name() {
         assert_eq!(k.object_name(), None);
     }
 
+    #[test]
+    fn test_object_key() {
+        let k = RemotePath::from_string("tenants/foo/timelines/bar/index_part.json").unwrap();
+        assert_eq!(k.object_key(), "tenants/foo/timelines/bar/index_part.json");
+
+        let k = RemotePath::new(Utf8Path::new("./a//b/")).unwrap();
+        assert_eq!(k.object_key(), "a/b");
+        assert!(!k.object_key().starts_with('/'));
+
+        let k = RemotePath::new(Utf8Path::new("")).unwrap();
+        assert_eq!(k.object_key(), "");
+    }
+
     #[test]
     fn rempte_path_cannot_be_created_from_absolute_ones() {
         let err = RemotePath::new(Utf8Path::new("/")).expect_err("Should fail on absolute paths");
@@ -254,7 +254,7 @@ async fn list_partial_prefix(
     // Prefix "" matches everything.
     let objects: HashSet<_> = test_client
         .list(
-            Some(&RemotePath::from_string("")?),
+            Some(&RemotePath::new(Utf8Path::new(""))?),
             ListingMode::NoDelimiter,
             None,
             &cancel,
@@ -269,7 +269,7 @@ async fn list_partial_prefix(
     // Prefix "" matches nothing with WithDelimiter.
     let objects: HashSet<_> = test_client
         .list(
-            Some(&RemotePath::from_string("")?),
+            Some(&RemotePath::new(Utf8Path::new(""))?),
             ListingMode::WithDelimiter,
             None,
             &cancel,
@@ -40,6 +40,7 @@
 use opentelemetry::trace::TracerProvider;
 use opentelemetry_otlp::WithExportConfig;
 pub use opentelemetry_otlp::{ExportConfig, Protocol};
+pub use opentelemetry_sdk::trace::Sampler;
 use tracing::level_filters::LevelFilter;
 use tracing::{Dispatch, Subscriber};
 use tracing_subscriber::Layer;
@@ -77,6 +78,19 @@ pub async fn init_tracing<S>(
     service_name: &str,
     export_config: ExportConfig,
 ) -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    init_tracing_with_sampler(service_name, export_config, None).await
+}
+
+/// Like `init_tracing`, but samples the exported traces with `sampler`. Without a sampler, the
+/// `opentelemetry` crate's default applies, which exports every trace.
+pub async fn init_tracing_with_sampler<S>(
+    service_name: &str,
+    export_config: ExportConfig,
+    sampler: Option<Sampler>,
+) -> Option<impl Layer<S>>
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
@@ -86,6 +100,7 @@ pub async fn init_tracing<S>(
     Some(init_tracing_internal(
         service_name.to_string(),
         export_config,
+        sampler,
     ))
 }
 
@@ -128,10 +143,15 @@ pub fn init_tracing_without_runtime<S>(
     Some(init_tracing_internal(
         service_name.to_string(),
         export_config,
+        None,
     ))
 }
 
-fn init_tracing_internal<S>(service_name: String, export_config: ExportConfig) -> impl Layer<S>
+fn init_tracing_internal<S>(
+    service_name: String,
+    export_config: ExportConfig,
+    sampler: Option<Sampler>,
+) -> impl Layer<S>
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
@@ -153,14 +173,16 @@ fn init_tracing_internal<S>(service_name: String, export_config: ExportConfig) -
         opentelemetry_sdk::propagation::TraceContextPropagator::new(),
     );
 
-    let tracer = opentelemetry_sdk::trace::TracerProvider::builder()
+    let mut provider = opentelemetry_sdk::trace::TracerProvider::builder()
         .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
         .with_resource(opentelemetry_sdk::Resource::new(vec![KeyValue::new(
             opentelemetry_semantic_conventions::resource::SERVICE_NAME,
             service_name,
-        )]))
-        .build()
-        .tracer("global");
+        )]));
+    if let Some(sampler) = sampler {
+        provider = provider.with_sampler(sampler);
+    }
+    let tracer = provider.build().tracer("global");
 
     tracing_opentelemetry::layer().with_tracer(tracer)
 }
@@ -171,6 +193,20 @@ pub fn shutdown_tracing() {
     opentelemetry::global::shutdown_tracer_provider();
 }
 
+/// Returns the OpenTelemetry trace ID of the current span, or `None` if it is not part of a
+/// trace. Useful to hand out to API clients, so they can find the trace of their request.
+pub fn current_trace_id() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let otel_context = tracing::Span::current().context();
+    let otel_spanref = otel_context.span();
+    let span_context = otel_spanref.span_context();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
 pub enum OtelEnablement {
     Disabled,
     Enabled {
@@ -218,3 +254,30 @@ pub fn init_performance_tracing(otel_enablement: OtelEnablement) -> Option<OtelG
 
     otel_subscriber.map(|dispatch| OtelGuard { dispatch })
 }
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::prelude::*;
+
+    use super::current_trace_id;
+
+    #[test]
+    fn trace_id_of_current_span() {
+        let tracer = opentelemetry_sdk::trace::TracerProvider::builder()
+            .build()
+            .tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(current_trace_id(), None);
+
+            tracing::info_span!("traced").in_scope(|| {
+                let trace_id = current_trace_id().expect("span is traced");
+                assert_eq!(trace_id.len(), 32);
+                assert_ne!(trace_id, "0".repeat(32));
+            });
+        });
+    }
+}
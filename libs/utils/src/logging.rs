@@ -1,5 +1,7 @@
+use std::collections::VecDeque;
 use std::future::Future;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
 use anyhow::Context;
@@ -8,6 +10,10 @@
 use strum_macros::{EnumString, VariantNames};
 use tokio::time::Instant;
 use tracing::info;
+use tracing::instrument::{Instrument, Instrumented};
+
+use crate::id::TimelineId;
+use crate::shard::TenantShardId;
 
 /// Logs a critical error, similarly to `tracing::error!`. This will:
 ///
@@ -132,6 +138,130 @@ pub enum Output {
     Stderr,
 }
 
+type LogFilterReloadHandle =
+    tracing_subscriber::reload::Handle<tracing_subscriber::EnvFilter, tracing_subscriber::Registry>;
+
+/// Handle to the filter of the log output layer, installed by [`init`].
+static LOG_FILTER_RELOAD_HANDLE: OnceLock<LogFilterReloadHandle> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum LogFilterReloadError {
+    #[error("invalid log filter directive: {0}")]
+    InvalidDirective(#[from] tracing_subscriber::filter::ParseError),
+    #[error("logging was not initialized with a reloadable filter")]
+    NotInitialized,
+    #[error("failed to reload log filter: {0}")]
+    Reload(#[from] tracing_subscriber::reload::Error),
+}
+
+/// Replaces the filter of the log output layer set up by [`init`] with one parsed from
+/// `directive` (same syntax as `RUST_LOG`), returning the previously active directive.
+///
+/// If `directive` fails to parse, the current filter is left untouched. Only the filter of the
+/// log output is affected: the event count metric keeps following the original `RUST_LOG`.
+pub fn reload_log_filter(directive: &str) -> Result<String, LogFilterReloadError> {
+    let new_filter = tracing_subscriber::EnvFilter::try_new(directive)?;
+    let handle = LOG_FILTER_RELOAD_HANDLE
+        .get()
+        .ok_or(LogFilterReloadError::NotInitialized)?;
+    let mut previous = None;
+    handle.modify(|filter| {
+        previous = Some(std::mem::replace(filter, new_filter).to_string());
+    })?;
+    Ok(previous.expect("modify closure is called on success"))
+}
+
+/// Keeps the most recent log lines in memory, so they can be served without access to the
+/// log output. Enabled with [`enable_log_ring`].
+pub struct LogRing {
+    capacity: usize,
+    lines: Mutex<VecDeque<Arc<str>>>,
+}
+
+impl LogRing {
+    fn new(capacity: usize) -> Self {
+        LogRing {
+            capacity,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn push(&self, output: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        // Format outside of the lock: holding it only for the O(1) queue updates keeps
+        // concurrent log writers and readers of the ring from stalling each other.
+        let output = String::from_utf8_lossy(output);
+        for line in output.lines().filter(|l| !l.is_empty()) {
+            let line: Arc<str> = Arc::from(line);
+            let mut lines = self.lines.lock().unwrap();
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    }
+
+    /// Returns up to `n` of the most recent lines, oldest first.
+    ///
+    /// Only the requested lines are copied, and under the lock only their reference counts are
+    /// bumped: the strings themselves are copied after it is released.
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let snapshot: Vec<Arc<str>> = {
+            let lines = self.lines.lock().unwrap();
+            lines
+                .range(lines.len().saturating_sub(n)..)
+                .cloned()
+                .collect()
+        };
+        snapshot.iter().map(|line| line.to_string()).collect()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+static LOG_RING: OnceLock<LogRing> = OnceLock::new();
+
+/// Makes the log output set up by [`init`] also retain its last `capacity` lines in memory.
+/// Must be called before [`init`]; later calls have no effect.
+pub fn enable_log_ring(capacity: usize) {
+    let _ = LOG_RING.set(LogRing::new(capacity));
+}
+
+/// The ring of recent log lines, if [`enable_log_ring`] was called.
+pub fn log_ring() -> Option<&'static LogRing> {
+    LOG_RING.get()
+}
+
+/// Passes writes through to the log output and, once the event is written, records it in the
+/// [`LogRing`]. The fmt layer creates one writer per event.
+struct LogRingWriter {
+    inner: Box<dyn std::io::Write>,
+    ring: &'static LogRing,
+    written: Vec<u8>,
+}
+
+impl std::io::Write for LogRingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Drop for LogRingWriter {
+    fn drop(&mut self) {
+        self.ring.push(&self.written);
+    }
+}
+
 pub fn init(
     log_format: LogFormat,
     tracing_error_layer_enablement: TracingErrorLayerEnablement,
@@ -153,9 +283,17 @@ pub fn init(
             .with_target(false)
             .with_ansi(false)
             .with_writer(move || -> Box<dyn std::io::Write> {
-                match output {
+                let inner: Box<dyn std::io::Write> = match output {
                     Output::Stdout => Box::new(std::io::stdout()),
                     Output::Stderr => Box::new(std::io::stderr()),
+                };
+                match LOG_RING.get() {
+                    Some(ring) => Box::new(LogRingWriter {
+                        inner,
+                        ring,
+                        written: Vec::new(),
+                    }),
+                    None => inner,
                 }
             });
         let log_layer = match log_format {
@@ -163,7 +301,11 @@ pub fn init(
             LogFormat::Plain => log_layer.boxed(),
             LogFormat::Test => log_layer.with_test_writer().boxed(),
         };
-        log_layer.with_filter(rust_log_env_filter())
+        let (filter, reload_handle) = tracing_subscriber::reload::Layer::new(rust_log_env_filter());
+        // Only the first initialization gets to install its handle; `init` can only succeed once
+        // anyway, since it sets the global default subscriber.
+        let _ = LOG_FILTER_RELOAD_HANDLE.set(reload_handle);
+        log_layer.with_filter(filter)
     });
 
     let r = r.with(
@@ -415,12 +557,31 @@ pub struct MonitorSlowFutureCallback {
     pub elapsed_since_last_callback: Duration,
 }
 
+/// Runs `fut` in a `tenant` span with the `tenant_id`, `shard_id` and, if given, `timeline_id`
+/// fields. Use this rather than spelling out the fields, so that they are named the same across
+/// the codebase, which extraction of span fields from logs relies on.
+pub fn with_tenant_span<F: Future>(
+    tenant_shard_id: TenantShardId,
+    timeline_id: Option<TimelineId>,
+    fut: F,
+) -> Instrumented<F> {
+    fut.instrument(tracing::info_span!(
+        "tenant",
+        tenant_id = %tenant_shard_id.tenant_id,
+        shard_id = %tenant_shard_id.shard_slug(),
+        timeline_id = timeline_id.map(tracing::field::display),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use metrics::IntCounterVec;
     use metrics::core::Opts;
 
-    use crate::logging::{TracingEventCountLayer, TracingEventCountMetric};
+    use crate::logging::{
+        LogFilterReloadError, LogRing, TracingEventCountLayer, TracingEventCountMetric,
+        reload_log_filter,
+    };
 
     #[test]
     fn tracing_event_count_metric() {
@@ -444,4 +605,28 @@ fn tracing_event_count_metric() {
         assert_eq!(counter_vec.with_label_values(&["warn"]).get(), 1);
         assert_eq!(counter_vec.with_label_values(&["error"]).get(), 1);
     }
+
+    #[test]
+    fn reload_log_filter_rejects_invalid_directive() {
+        // The directive is validated before the filter is touched, so this does not depend on
+        // whether `init` was called in this process.
+        let err = reload_log_filter("pageserver=loud").unwrap_err();
+        assert!(
+            matches!(err, LogFilterReloadError::InvalidDirective(_)),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn log_ring_keeps_most_recent_lines() {
+        let ring = LogRing::new(3);
+        ring.push(b"one\n");
+        ring.push(b"two\nthree\n");
+        assert_eq!(ring.tail(10), vec!["one", "two", "three"]);
+
+        ring.push(b"four\n");
+        assert_eq!(ring.tail(10), vec!["two", "three", "four"]);
+        assert_eq!(ring.tail(2), vec!["three", "four"]);
+        assert!(ring.tail(0).is_empty());
+    }
 }
@@ -58,7 +58,7 @@ async fn error_from_body(self) -> Result<Self> {
 
         let url = self.url().to_owned();
         Err(match self.json::<HttpErrorBody>().await {
-            Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+            Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
             Err(_) => {
                 Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), url))
             }
@@ -653,7 +653,7 @@ pub async fn layer_evict(
             StatusCode::NOT_MODIFIED => Ok(false),
             // TODO: dedupe this pattern / introduce separate error variant?
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -677,7 +677,7 @@ pub async fn layer_ondemand_download(
             StatusCode::NOT_MODIFIED => Ok(false),
             // TODO: dedupe this pattern / introduce separate error variant?
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -701,7 +701,7 @@ pub async fn ingest_aux_files(
         match resp.status() {
             StatusCode::OK => Ok(true),
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -730,7 +730,7 @@ pub async fn list_aux_files(
                 Ok(resp)
             }
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
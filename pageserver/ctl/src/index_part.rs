@@ -1,6 +1,7 @@
 use anyhow::Context;
 use camino::Utf8PathBuf;
 use pageserver::tenant::IndexPart;
+use pageserver::tenant::remote_timeline_client::index_codec::IndexCodecs;
 
 #[derive(clap::Subcommand)]
 pub(crate) enum IndexPartCmd {
@@ -11,7 +12,9 @@ pub(crate) async fn main(cmd: &IndexPartCmd) -> anyhow::Result<()> {
     match cmd {
         IndexPartCmd::Dump { path } => {
             let bytes = tokio::fs::read(path).await.context("read file")?;
-            let des: IndexPart = IndexPart::from_json_bytes(&bytes).context("deserialize")?;
+            let des: IndexPart = IndexCodecs::installed()
+                .decode(&bytes)
+                .context("deserialize")?;
             let output = serde_json::to_string_pretty(&des).context("serialize output")?;
             println!("{output}");
             Ok(())
@@ -56,6 +56,9 @@
 
 const PID_FILE_NAME: &str = "pageserver.pid";
 
+/// Number of recent log lines kept in memory for the `/v1/logs/tail` endpoint.
+const LOG_RING_CAPACITY: usize = 10_000;
+
 const FEATURES: &[&str] = &[
     #[cfg(feature = "testing")]
     "testing",
@@ -112,6 +115,8 @@ fn main() -> anyhow::Result<()> {
         TracingErrorLayerEnablement::Disabled
     };
 
+    // Recent log lines are served by the `/v1/logs/tail` management API endpoint.
+    logging::enable_log_ring(LOG_RING_CAPACITY);
     logging::init(
         conf.log_format,
         tracing_error_layer_enablement,
@@ -658,6 +663,9 @@ fn start_pageserver(
         background_jobs_barrier.clone(),
     );
 
+    // Cancelled by a termination signal, or by a shutdown request on the HTTP mgmt API.
+    let signal_token = CancellationToken::new();
+
     // Start up the service to handle HTTP mgmt API request. We created the
     // listener earlier already.
     let (http_endpoint_listener, https_endpoint_listener) = {
@@ -673,6 +681,7 @@ fn start_pageserver(
                 disk_usage_eviction_state,
                 deletion_queue.new_client(),
                 secondary_controller,
+                signal_token.clone(),
             )
             .context("Failed to initialize router state")?,
         );
@@ -767,7 +776,6 @@ fn start_pageserver(
 
     // All started up! Now just sit and wait for shutdown signal.
     BACKGROUND_RUNTIME.block_on(async move {
-        let signal_token = CancellationToken::new();
         let signal_cancel = signal_token.child_token();
 
         tokio::spawn(utils::signals::signal_handler(signal_token));
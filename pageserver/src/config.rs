@@ -13,8 +13,11 @@
 
 use anyhow::{Context, bail, ensure};
 use camino::{Utf8Path, Utf8PathBuf};
+use http_utils::endpoint::BasicAuthCredential;
 use once_cell::sync::OnceCell;
-use pageserver_api::config::{DiskUsageEvictionTaskConfig, MaxVectoredReadBytes};
+use pageserver_api::config::{
+    DiskUsageEvictionTaskConfig, HttpBasicAuthConfig, MaxVectoredReadBytes,
+};
 use pageserver_api::models::ImageCompressionAlgorithm;
 use pageserver_api::shard::TenantShardId;
 use pem::Pem;
@@ -22,6 +25,7 @@
 use remote_storage::{RemotePath, RemoteStorageConfig};
 use reqwest::Url;
 use storage_broker::Uri;
+use utils::auth::{Claims, Scope};
 use utils::id::{NodeId, TimelineId};
 use utils::logging::{LogFormat, SecretString};
 use utils::postgres_client::PostgresClientProtocol;
@@ -103,6 +107,11 @@ pub struct PageServerConf {
     /// Path to a file or directory containing public key(s) for verifying JWT tokens.
     /// Used for both mgmt and compute auth, if enabled.
     pub auth_validation_public_key_path: Option<Utf8PathBuf>,
+    /// Credential accepted by the mgmt API via HTTP Basic auth, next to JWTs.
+    pub http_basic_auth: Option<BasicAuthCredential>,
+    /// Deadline for mgmt API requests, after which they fail with 504. Some long-running
+    /// routes are exempt.
+    pub http_request_timeout: Duration,
 
     pub remote_storage_config: Option<RemoteStorageConfig>,
 
@@ -128,6 +137,10 @@ pub struct PageServerConf {
     /// [`TenantShard::gather_size_inputs`]: crate::tenant::TenantShard::gather_size_inputs
     pub eviction_task_immitated_concurrent_logical_size_queries: ConfigurableSemaphore,
 
+    /// Number of concurrent non-incremental logical size calculations, requested through
+    /// `include-non-incremental-logical-size` on the timeline APIs, allowed across all tenants.
+    pub concurrent_non_incremental_logical_size_queries: ConfigurableSemaphore,
+
     // How often to collect metrics and send them to the metrics endpoint.
     pub metric_collection_interval: Duration,
     // How often to send unchanged cached metrics to the metrics endpoint.
@@ -362,6 +375,8 @@ pub fn parse_and_validate(
             http_auth_type,
             pg_auth_type,
             auth_validation_public_key_path,
+            http_basic_auth,
+            http_request_timeout,
             remote_storage,
             broker_endpoint,
             broker_keepalive_interval,
@@ -391,6 +406,7 @@ pub fn parse_and_validate(
             virtual_file_io_mode,
             concurrent_tenant_warmup,
             concurrent_tenant_size_logical_size_queries,
+            concurrent_non_incremental_logical_size_queries,
             virtual_file_io_engine,
             tenant_config,
             no_sync,
@@ -426,6 +442,7 @@ pub fn parse_and_validate(
             http_auth_type,
             pg_auth_type,
             auth_validation_public_key_path,
+            http_request_timeout,
             remote_storage_config: remote_storage,
             broker_endpoint,
             broker_keepalive_interval,
@@ -469,6 +486,13 @@ pub fn parse_and_validate(
                     .expect("current_dir() is not a valid Utf8Path")
             }),
             control_plane_api_token: control_plane_api_token.map(SecretString::from),
+            http_basic_auth: http_basic_auth.map(|HttpBasicAuthConfig { username, password }| {
+                BasicAuthCredential::new(
+                    username,
+                    password,
+                    Claims::new(None, Scope::PageServerApi),
+                )
+            }),
             id,
             default_tenant_conf: tenant_config,
             concurrent_tenant_warmup: ConfigurableSemaphore::new(concurrent_tenant_warmup),
@@ -479,6 +503,9 @@ pub fn parse_and_validate(
                 // re-use `concurrent_tenant_size_logical_size_queries`
                 concurrent_tenant_size_logical_size_queries,
             ),
+            concurrent_non_incremental_logical_size_queries: ConfigurableSemaphore::new(
+                concurrent_non_incremental_logical_size_queries,
+            ),
             virtual_file_io_engine: match virtual_file_io_engine {
                 Some(v) => v,
                 None => match crate::virtual_file::io_engine_feature_test()
@@ -531,6 +558,11 @@ pub fn parse_and_validate(
             );
         }
 
+        ensure!(
+            conf.http_basic_auth.is_none() || conf.http_auth_type == AuthType::NeonJWT,
+            "`http_basic_auth` requires `http_auth_type = \"NeonJWT\"`"
+        );
+
         if let Some(tracing_config) = conf.tracing.as_ref() {
             let ratio = &tracing_config.sampling_ratio;
             ensure!(
@@ -0,0 +1,281 @@
+//! Background jobs whose progress can be followed over the management API.
+//!
+//! The code running a job registers it in [`JOBS`], and reports its progress through the
+//! returned [`Job`]: the phases it goes through, and the layers it processed. Clients get the
+//! current [`JobInfo`] with `GET /v1/jobs/:job_id`, or follow the job as server-sent events with
+//! `GET /v1/jobs/:job_id/events`, see [`JobSubscription::events`].
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::Stream;
+use once_cell::sync::Lazy;
+use pageserver_api::models::{JobInfo, JobState};
+use tokio::sync::watch;
+
+/// How many finished jobs are kept for lookup. Running jobs are always kept.
+const MAX_FINISHED_JOBS: usize = 128;
+
+/// The jobs of this process.
+pub static JOBS: Lazy<Jobs> = Lazy::new(Jobs::default);
+
+#[derive(Clone)]
+struct JobStatus {
+    info: JobInfo,
+    /// `info` as of entering each phase, in order: lets subscribers report every phase, even
+    /// those the job went through while they were not looking.
+    phases: Vec<JobInfo>,
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    last_id: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, watch::Receiver<JobStatus>>>,
+}
+
+impl Jobs {
+    /// Registers a new running job of the given kind, starting in `phase`.
+    pub fn register(&self, kind: &str, phase: &str) -> Job {
+        let job_id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let info = JobInfo {
+            job_id,
+            kind: kind.to_string(),
+            phase: phase.to_string(),
+            layers_processed: 0,
+            state: JobState::Running,
+        };
+        let (tx, rx) = watch::channel(JobStatus {
+            phases: vec![info.clone()],
+            info,
+        });
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.insert(job_id, rx);
+        // Forget the oldest finished jobs: ids are assigned in registration order.
+        let finished: Vec<u64> = jobs
+            .iter()
+            .filter(|(_, rx)| rx.borrow().info.state != JobState::Running)
+            .map(|(job_id, _)| *job_id)
+            .collect();
+        for job_id in &finished[..finished.len().saturating_sub(MAX_FINISHED_JOBS)] {
+            jobs.remove(job_id);
+        }
+
+        Job { tx }
+    }
+
+    pub fn get(&self, job_id: u64) -> Option<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.get(&job_id).map(|rx| rx.borrow().info.clone())
+    }
+
+    pub fn subscribe(&self, job_id: u64) -> Option<JobSubscription> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.get(&job_id)
+            .map(|rx| JobSubscription { rx: rx.clone() })
+    }
+}
+
+/// Reports the progress of a registered job. Dropping it before [`Job::complete`] marks the job
+/// as aborted.
+pub struct Job {
+    tx: watch::Sender<JobStatus>,
+}
+
+impl Job {
+    pub fn id(&self) -> u64 {
+        self.tx.borrow().info.job_id
+    }
+
+    pub fn enter_phase(&self, phase: &str) {
+        self.tx.send_modify(|status| {
+            status.info.phase = phase.to_string();
+            status.phases.push(status.info.clone());
+        });
+    }
+
+    pub fn add_layers_processed(&self, layers: u64) {
+        self.tx
+            .send_modify(|status| status.info.layers_processed += layers);
+    }
+
+    pub fn complete(self) {
+        self.finish(JobState::Completed);
+    }
+
+    fn finish(&self, state: JobState) {
+        self.tx.send_if_modified(|status| {
+            if status.info.state != JobState::Running {
+                return false;
+            }
+            status.info.state = state;
+            true
+        });
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.finish(JobState::Aborted);
+    }
+}
+
+/// Observes a job, without any influence on it.
+pub struct JobSubscription {
+    rx: watch::Receiver<JobStatus>,
+}
+
+impl JobSubscription {
+    /// The progress of the job as server-sent events: a `progress` event for every phase the job
+    /// entered and whenever its info changes, then a `completed` event with its final info once it
+    /// stops running, after which the stream ends. Intermediate layer counts may be skipped when
+    /// the job makes progress faster than the stream is consumed.
+    ///
+    /// Dropping the stream, e.g. because the client disconnected, only ends the subscription.
+    pub fn events(mut self) -> impl Stream<Item = String> {
+        async_stream::stream! {
+            let mut phases_sent = 0;
+            let mut last_sent: Option<JobInfo> = None;
+            loop {
+                let status = self.rx.borrow_and_update().clone();
+                for info in &status.phases[phases_sent..] {
+                    if last_sent.as_ref() != Some(info) {
+                        yield event_frame("progress", info);
+                        last_sent = Some(info.clone());
+                    }
+                }
+                phases_sent = status.phases.len();
+
+                if status.info.state != JobState::Running {
+                    yield event_frame("completed", &status.info);
+                    break;
+                }
+                if last_sent.as_ref() != Some(&status.info) {
+                    yield event_frame("progress", &status.info);
+                    last_sent = Some(status.info);
+                }
+
+                if self.rx.changed().await.is_err() {
+                    // The job was dropped, which marked it as aborted: that was seen above.
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn event_frame(event: &str, info: &JobInfo) -> String {
+    let data = serde_json::to_string(info).expect("job info is serializable");
+    format!("event: {event}\ndata: {data}\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn parse_frame(frame: &str) -> (String, JobInfo) {
+        let (event, data) = frame
+            .strip_suffix("\n\n")
+            .and_then(|frame| frame.split_once('\n'))
+            .unwrap();
+        (
+            event.strip_prefix("event: ").unwrap().to_string(),
+            serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap(),
+        )
+    }
+
+    async fn next_event(events: &mut (impl Stream<Item = String> + Unpin)) -> (String, JobInfo) {
+        parse_frame(&events.next().await.expect("stream ended early"))
+    }
+
+    #[tokio::test]
+    async fn events_report_every_phase_and_completion() {
+        let jobs = Jobs::default();
+        let job = jobs.register("test", "listing");
+        let events = jobs.subscribe(job.id()).unwrap().events();
+
+        job.enter_phase("downloading");
+        job.add_layers_processed(3);
+        job.enter_phase("uploading");
+        job.add_layers_processed(2);
+        job.complete();
+
+        let events: Vec<_> = events.map(|frame| parse_frame(&frame)).collect().await;
+        let phases: Vec<_> = events
+            .iter()
+            .filter(|(event, _)| event == "progress")
+            .map(|(_, info)| info.phase.as_str())
+            .collect();
+        assert_eq!(phases, ["listing", "downloading", "uploading"]);
+
+        let (event, info) = events.last().unwrap();
+        assert_eq!(event, "completed");
+        assert_eq!(info.phase, "uploading");
+        assert_eq!(info.layers_processed, 5);
+        assert_eq!(info.state, JobState::Completed);
+    }
+
+    #[tokio::test]
+    async fn events_follow_a_running_job() {
+        let jobs = Jobs::default();
+        let job = jobs.register("test", "listing");
+        let mut events = Box::pin(jobs.subscribe(job.id()).unwrap().events());
+
+        let (event, info) = next_event(&mut events).await;
+        assert_eq!(
+            (event.as_str(), info.phase.as_str()),
+            ("progress", "listing")
+        );
+
+        job.enter_phase("downloading");
+        let (event, info) = next_event(&mut events).await;
+        assert_eq!(
+            (event.as_str(), info.phase.as_str()),
+            ("progress", "downloading")
+        );
+
+        job.complete();
+        let (event, info) = next_event(&mut events).await;
+        assert_eq!(
+            (event.as_str(), info.state),
+            ("completed", JobState::Completed)
+        );
+        assert!(events.next().await.is_none());
+    }
+
+    #[test]
+    fn dropping_the_subscription_leaves_the_job_alone() {
+        let jobs = Jobs::default();
+        let job = jobs.register("test", "listing");
+        drop(jobs.subscribe(job.id()).unwrap().events());
+
+        job.enter_phase("downloading");
+        job.add_layers_processed(1);
+        assert_eq!(jobs.get(job.id()).unwrap().state, JobState::Running);
+        let job_id = job.id();
+        job.complete();
+        assert_eq!(jobs.get(job_id).unwrap().state, JobState::Completed);
+    }
+
+    #[test]
+    fn dropped_job_is_aborted() {
+        let jobs = Jobs::default();
+        let job_id = jobs.register("test", "listing").id();
+        assert_eq!(jobs.get(job_id).unwrap().state, JobState::Aborted);
+    }
+
+    #[test]
+    fn finished_jobs_are_forgotten() {
+        let jobs = Jobs::default();
+        let running = jobs.register("test", "listing");
+        let first = jobs.register("test", "listing").id();
+        for _ in 0..MAX_FINISHED_JOBS {
+            jobs.register("test", "listing").complete();
+        }
+        assert!(jobs.get(first).is_none());
+        assert!(jobs.get(running.id()).is_some());
+    }
+}
@@ -11,6 +11,7 @@
 pub mod disk_usage_eviction_task;
 pub mod http;
 pub mod import_datadir;
+pub mod jobs;
 pub mod l0_flush;
 
 extern crate hyper0 as hyper;
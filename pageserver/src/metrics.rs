@@ -182,6 +182,15 @@ pub(crate) enum StorageTimeOperation {
     .expect("failed to define a metric")
 });
 
+pub(crate) static CONCURRENT_NON_INCREMENTAL_LOGICAL_SIZE_QUERIES: Lazy<UIntGauge> =
+    Lazy::new(|| {
+        register_uint_gauge!(
+            "pageserver_concurrent_non_incremental_logical_size_queries",
+            "Number of non-incremental logical size calculations running for the HTTP API"
+        )
+        .expect("failed to define a metric")
+    });
+
 pub(crate) static INITDB_SEMAPHORE_ACQUISITION_TIME: Lazy<Histogram> = Lazy::new(|| {
     register_histogram!(
         "pageserver_initdb_semaphore_seconds_global",
@@ -1017,6 +1026,15 @@ fn drop(&mut self) {
     .expect("Failed to register pageserver_tenant_states_count metric")
 });
 
+/// Count of timelines loaded on this pageserver, across all tenant shards.
+pub(crate) static TIMELINE_COUNT: Lazy<UIntGauge> = Lazy::new(|| {
+    register_uint_gauge!(
+        "pageserver_timelines",
+        "Count of timelines loaded on this pageserver"
+    )
+    .expect("Failed to register pageserver_timelines metric")
+});
+
 /// A set of broken tenants.
 ///
 /// These are expected to be so rare that a set is fine. Set as in a new timeseries per each broken
@@ -2541,6 +2559,45 @@ pub fn as_str(&self) -> &'static str {
     .expect("failed to define a metric")
 });
 
+pub(crate) static INDEX_PART_PARSE_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "pageserver_index_part_parse_seconds",
+        "Time spent deserializing index_part.json objects, including failed attempts.",
+        &["tenant_id", "shard_id", "timeline_id"],
+        CRITICAL_OP_BUCKETS.into(),
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static INDEX_PART_PARSE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "pageserver_index_part_parse_bytes",
+        "Size of index_part.json objects passed to the deserializer.",
+        &["tenant_id", "shard_id", "timeline_id"],
+        // 1 KiB .. 256 MiB
+        vec![
+            1024.0,
+            16.0 * 1024.0,
+            256.0 * 1024.0,
+            1024.0 * 1024.0,
+            4.0 * 1024.0 * 1024.0,
+            16.0 * 1024.0 * 1024.0,
+            64.0 * 1024.0 * 1024.0,
+            256.0 * 1024.0 * 1024.0,
+        ],
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static INDEX_PART_PARSE_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pageserver_index_part_parse_errors_total",
+        "Number of index_part.json objects that failed to decode, by error category.",
+        &["category"],
+    )
+    .expect("failed to define a metric")
+});
+
 pub(crate) static TENANT_TASK_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
         "pageserver_tenant_task_events",
@@ -3099,6 +3156,9 @@ pub(crate) struct TimelineMetrics {
     pub wait_lsn_in_progress_micros: GlobalAndPerTenantIntCounter,
     pub wait_lsn_start_finish_counterpair: IntCounterPair,
     pub wait_ondemand_download_time: wait_ondemand_download_time::WaitOndemandDownloadTimeSum,
+    /// Running total of the sizes added to TIMELINE_LAYER_SIZE by [`Self::inc_layer`] and
+    /// [`Self::dec_layer`], so it can be read without touching the metric series.
+    persistent_layer_size: std::sync::atomic::AtomicU64,
     shutdown: std::sync::atomic::AtomicBool,
 }
 
@@ -3251,6 +3311,8 @@ pub fn new(
                 &timeline_id,
             );
 
+        TIMELINE_COUNT.inc();
+
         TimelineMetrics {
             tenant_id,
             shard_id,
@@ -3285,6 +3347,7 @@ pub fn new(
             wait_lsn_in_progress_micros,
             wait_lsn_start_finish_counterpair,
             wait_ondemand_download_time,
+            persistent_layer_size: std::sync::atomic::AtomicU64::default(),
             shutdown: std::sync::atomic::AtomicBool::default(),
         }
     }
@@ -3307,6 +3370,14 @@ pub(crate) fn resident_physical_size_get(&self) -> u64 {
         self.resident_physical_size_gauge.get()
     }
 
+    /// Sum of the persistent layer sizes, maintained alongside the TIMELINE_LAYER metrics as layers
+    /// are created and dropped. Unlike [`crate::tenant::timeline::Timeline::layer_size_sum`], this doesn't
+    /// lock the layer map, but may lag behind it briefly.
+    pub(crate) fn persistent_layer_size_get(&self) -> u64 {
+        self.persistent_layer_size
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
     /// Generates TIMELINE_LAYER labels for a persistent layer.
     fn make_layer_labels(&self, layer_desc: &PersistentLayerDesc) -> [&str; 5] {
         let level = match LayerMap::is_l0(&layer_desc.key_range, layer_desc.is_delta()) {
@@ -3378,6 +3449,8 @@ pub fn dec_layer(&self, layer_desc: &PersistentLayerDesc) {
             .get_metric_with_label_values(&labels)
             .unwrap()
             .sub(layer_desc.file_size);
+        self.persistent_layer_size
+            .fetch_sub(layer_desc.file_size, std::sync::atomic::Ordering::Relaxed);
     }
 
     /// Adds a persistent layer to TIMELINE_LAYER metrics.
@@ -3391,6 +3464,8 @@ pub fn inc_layer(&self, layer_desc: &PersistentLayerDesc) {
             .get_metric_with_label_values(&labels)
             .unwrap()
             .add(layer_desc.file_size);
+        self.persistent_layer_size
+            .fetch_add(layer_desc.file_size, std::sync::atomic::Ordering::Relaxed);
     }
 
     pub(crate) fn shutdown(&self) {
@@ -3405,6 +3480,8 @@ pub(crate) fn shutdown(&self) {
             return;
         }
 
+        TIMELINE_COUNT.dec();
+
         let tenant_id = &self.tenant_id;
         let timeline_id = &self.timeline_id;
         let shard_id = &self.shard_id;
@@ -3434,6 +3511,8 @@ pub(crate) fn shutdown(&self) {
         }
 
         let _ = LAYERS_PER_READ.remove_label_values(&[tenant_id, shard_id, timeline_id]);
+        let _ = INDEX_PART_PARSE_SECONDS.remove_label_values(&[tenant_id, shard_id, timeline_id]);
+        let _ = INDEX_PART_PARSE_BYTES.remove_label_values(&[tenant_id, shard_id, timeline_id]);
 
         let _ = EVICTIONS.remove_label_values(&[tenant_id, shard_id, timeline_id]);
         let _ = AUX_FILE_SIZE.remove_label_values(&[tenant_id, shard_id, timeline_id]);
@@ -3523,6 +3602,16 @@ pub(crate) fn shutdown(&self) {
     }
 }
 
+impl Drop for TimelineMetrics {
+    fn drop(&mut self) {
+        // Timelines that fail to initialize are dropped without being shut down, see
+        // `UninitializedTimeline`. They still counted towards TIMELINE_COUNT.
+        if !self.shutdown.load(std::sync::atomic::Ordering::Relaxed) {
+            TIMELINE_COUNT.dec();
+        }
+    }
+}
+
 pub(crate) fn remove_tenant_metrics(tenant_shard_id: &TenantShardId) {
     // Only shard zero deals in synthetic sizes
     if tenant_shard_id.is_shard_zero() {
@@ -4381,6 +4470,7 @@ pub fn preinitialize_metrics(
 
     // Tenant stats
     Lazy::force(&TENANT);
+    Lazy::force(&TIMELINE_COUNT);
 
     // Tenant manager stats
     Lazy::force(&TENANT_MANAGER);
@@ -38,7 +38,7 @@
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use futures::FutureExt;
 use once_cell::sync::Lazy;
@@ -224,6 +224,12 @@ macro_rules! pageserver_runtime {
 #[derive(Debug, Clone, Copy)]
 pub struct PageserverTaskId(u64);
 
+impl PageserverTaskId {
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
 impl fmt::Display for PageserverTaskId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -382,11 +388,13 @@ pub enum TaskKind {
     ImportPgdata,
 }
 
-#[derive(Default)]
 struct MutableTaskState {
     /// Handle for waiting for the task to exit. It can be None, if the
     /// the task has already exited.
     join_handle: Option<JoinHandle<()>>,
+
+    /// What the task is doing, see [`set_current_task_phase`].
+    phase: &'static str,
 }
 
 struct PageServerTask {
@@ -404,9 +412,26 @@ struct PageServerTask {
     tenant_shard_id: TenantShardId,
     timeline_id: Option<TimelineId>,
 
+    started_at: SystemTime,
+
     mutable: Mutex<MutableTaskState>,
 }
 
+/// Phase of a task that hasn't reported one with [`set_current_task_phase`].
+const DEFAULT_TASK_PHASE: &str = "running";
+
+/// A snapshot of a task in the registry, see [`list_tasks`].
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub task_id: PageserverTaskId,
+    pub kind: TaskKind,
+    pub name: String,
+    pub tenant_shard_id: TenantShardId,
+    pub timeline_id: Option<TimelineId>,
+    pub started_at: SystemTime,
+    pub phase: &'static str,
+}
+
 /// Launch a new task
 /// Note: if shutdown_process_on_error is set to true failure
 ///   of the task will lead to shutdown of entire process
@@ -430,7 +455,11 @@ pub fn spawn<F>(
         cancel: cancel.clone(),
         tenant_shard_id,
         timeline_id,
-        mutable: Mutex::new(MutableTaskState { join_handle: None }),
+        started_at: SystemTime::now(),
+        mutable: Mutex::new(MutableTaskState {
+            join_handle: None,
+            phase: DEFAULT_TASK_PHASE,
+        }),
     });
 
     TASKS.lock().unwrap().insert(task_id, Arc::clone(&task));
@@ -621,6 +650,33 @@ pub fn current_task_id() -> Option<PageserverTaskId> {
     CURRENT_TASK.try_with(|ct| ct.task_id).ok()
 }
 
+/// Reports what the current task is doing, e.g. whether a background loop is running an
+/// iteration or waiting for the next one. Shown in [`list_tasks`]; does nothing outside of tasks
+/// spawned with [`spawn`].
+pub fn set_current_task_phase(phase: &'static str) {
+    let _ = CURRENT_TASK.try_with(|ct| ct.mutable.lock().unwrap().phase = phase);
+}
+
+/// Lists the tasks currently in the registry, ordered by task id.
+pub fn list_tasks() -> Vec<TaskInfo> {
+    let mut tasks = TASKS
+        .lock()
+        .unwrap()
+        .values()
+        .map(|task| TaskInfo {
+            task_id: task.task_id,
+            kind: task.kind,
+            name: task.name.clone(),
+            tenant_shard_id: task.tenant_shard_id,
+            timeline_id: task.timeline_id,
+            started_at: task.started_at,
+            phase: task.mutable.lock().unwrap().phase,
+        })
+        .collect::<Vec<_>>();
+    tasks.sort_by_key(|task| task.task_id.as_u64());
+    tasks
+}
+
 /// A Future that can be used to check if the current task has been requested to
 /// shut down.
 pub async fn shutdown_watcher() {
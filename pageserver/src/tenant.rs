@@ -75,7 +75,8 @@
 use self::remote_timeline_client::{RemoteTimelineClient, WaitCompletionError};
 use self::timeline::uninit::{TimelineCreateGuard, TimelineExclusionError, UninitializedTimeline};
 use self::timeline::{
-    EvictionTaskTenantState, GcCutoffs, TimelineDeleteProgress, TimelineResources, WaitLsnError,
+    EvictionTaskTenantState, GcCutoffs, GcOptions, TimelineDeleteProgress, TimelineResources,
+    WaitLsnError,
 };
 use crate::config::PageServerConf;
 use crate::context;
@@ -2972,6 +2973,27 @@ pub(crate) async fn gc_iteration(
         pitr: Duration,
         cancel: &CancellationToken,
         ctx: &RequestContext,
+    ) -> Result<GcResult, GcError> {
+        self.gc_iteration_with_options(
+            target_timeline_id,
+            horizon,
+            pitr,
+            GcOptions::default(),
+            cancel,
+            ctx,
+        )
+        .await
+    }
+
+    /// Like [`Self::gc_iteration`], but with a dry run option for the `do_gc` API.
+    pub(crate) async fn gc_iteration_with_options(
+        &self,
+        target_timeline_id: Option<TimelineId>,
+        horizon: u64,
+        pitr: Duration,
+        options: GcOptions,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
     ) -> Result<GcResult, GcError> {
         // Don't start doing work during shutdown
         if let TenantState::Stopping { .. } = self.current_state() {
@@ -3009,7 +3031,7 @@ pub(crate) async fn gc_iteration(
             }
         };
 
-        self.gc_iteration_internal(target_timeline_id, horizon, pitr, cancel, ctx)
+        self.gc_iteration_internal(target_timeline_id, horizon, pitr, options, cancel, ctx)
             .await
     }
 
@@ -4461,15 +4483,31 @@ async fn gc_iteration_internal(
         target_timeline_id: Option<TimelineId>,
         horizon: u64,
         pitr: Duration,
+        options: GcOptions,
         cancel: &CancellationToken,
         ctx: &RequestContext,
     ) -> Result<GcResult, GcError> {
-        let mut totals: GcResult = Default::default();
+        let mut totals = GcResult {
+            dry_run: options.dry_run,
+            ..Default::default()
+        };
         let now = Instant::now();
 
-        let gc_timelines = self
-            .refresh_gc_info_internal(target_timeline_id, horizon, pitr, cancel, ctx)
-            .await?;
+        let gc_timelines: Vec<(Arc<Timeline>, Option<GcCutoffs>)> = if options.dry_run {
+            // Don't store the cutoffs of a dry run: branch creation and lease grants check against
+            // them, and must keep seeing the cutoffs of the last real GC.
+            self.dry_run_gc_cutoffs(target_timeline_id, horizon, pitr, cancel, ctx)
+                .await?
+                .into_iter()
+                .map(|(timeline, cutoffs)| (timeline, Some(cutoffs)))
+                .collect()
+        } else {
+            self.refresh_gc_info_internal(target_timeline_id, horizon, pitr, cancel, ctx)
+                .await?
+                .into_iter()
+                .map(|timeline| (timeline, None))
+                .collect()
+        };
 
         failpoint_support::sleep_millis_async!("gc_iteration_internal_after_getting_gc_timelines");
 
@@ -4488,13 +4526,13 @@ async fn gc_iteration_internal(
         //
         // See comments in [`TenantShard::branch_timeline`] for more information about why branch
         // creation task can run concurrently with timeline's GC iteration.
-        for timeline in gc_timelines {
+        for (timeline, cutoffs) in gc_timelines {
             if cancel.is_cancelled() {
                 // We were requested to shut down. Stop and return with the progress we
                 // made.
                 break;
             }
-            let result = match timeline.gc().await {
+            let result = match timeline.gc(options, cutoffs).await {
                 Err(GcError::TimelineCancelled) => {
                     if target_timeline_id.is_some() {
                         // If we were targetting this specific timeline, surface cancellation to caller
@@ -4612,16 +4650,15 @@ fn initialize_gc_info(
         }
     }
 
-    async fn refresh_gc_info_internal(
+    /// Finds the GC cutoffs of the given timeline, or of all timelines, without applying them.
+    async fn find_gc_cutoffs(
         &self,
         target_timeline_id: Option<TimelineId>,
         horizon: u64,
         pitr: Duration,
         cancel: &CancellationToken,
         ctx: &RequestContext,
-    ) -> Result<Vec<Arc<Timeline>>, GcError> {
-        // before taking the gc_cs lock, do the heavier weight finding of gc_cutoff points for
-        // currently visible timelines.
+    ) -> Result<(Vec<Arc<Timeline>>, HashMap<TimelineId, GcCutoffs>), GcError> {
         let timelines = self
             .timelines
             .lock()
@@ -4658,6 +4695,58 @@ async fn refresh_gc_info_internal(
             assert!(old.is_none());
         }
 
+        Ok((timelines, gc_cutoffs))
+    }
+
+    /// The timelines [`Self::refresh_gc_info_internal`] would return, each with the cutoffs it
+    /// would apply to it, leaving the timelines' `GcInfo` untouched.
+    async fn dry_run_gc_cutoffs(
+        &self,
+        target_timeline_id: Option<TimelineId>,
+        horizon: u64,
+        pitr: Duration,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> Result<Vec<(Arc<Timeline>, GcCutoffs)>, GcError> {
+        let (timelines, gc_cutoffs) = self
+            .find_gc_cutoffs(target_timeline_id, horizon, pitr, cancel, ctx)
+            .await?;
+
+        if !self.is_active() || self.cancel.is_cancelled() {
+            return Err(GcError::TenantCancelled);
+        }
+
+        Ok(timelines
+            .into_iter()
+            .map(|timeline| {
+                let current = timeline.gc_info.read().unwrap().cutoffs.clone();
+                // Like refresh_gc_info_internal: cutoffs never go back
+                let cutoffs = match gc_cutoffs.get(&timeline.timeline_id) {
+                    Some(cutoffs) => GcCutoffs {
+                        space: cutoffs.space.max(current.space),
+                        time: cutoffs.time.max(current.time),
+                    },
+                    None => current,
+                };
+                (timeline, cutoffs)
+            })
+            .collect())
+    }
+
+    async fn refresh_gc_info_internal(
+        &self,
+        target_timeline_id: Option<TimelineId>,
+        horizon: u64,
+        pitr: Duration,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> Result<Vec<Arc<Timeline>>, GcError> {
+        // before taking the gc_cs lock, do the heavier weight finding of gc_cutoff points for
+        // currently visible timelines.
+        let (timelines, gc_cutoffs) = self
+            .find_gc_cutoffs(target_timeline_id, horizon, pitr, cancel, ctx)
+            .await?;
+
         if !self.is_active() || self.cancel.is_cancelled() {
             return Err(GcError::TenantCancelled);
         }
@@ -5403,6 +5492,12 @@ pub async fn gather_size_inputs(
         cancel: &CancellationToken,
         ctx: &RequestContext,
     ) -> Result<size::ModelInputs, size::CalculateSyntheticSizeError> {
+        fail::fail_point!("gather-size-inputs-transient-error", |_| {
+            Err(size::CalculateSyntheticSizeError::GcInfo(GcError::Remote(
+                anyhow::anyhow!("failpoint: gather-size-inputs-transient-error"),
+            )))
+        });
+
         let logical_sizes_at_once = self
             .conf
             .concurrent_tenant_size_logical_size_queries
@@ -6369,6 +6464,32 @@ async fn no_duplicate_timelines() -> anyhow::Result<()> {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn no_duplicate_bootstrapped_timelines() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("no_duplicate_bootstrapped_timelines")
+            .await?
+            .load()
+            .await;
+        let _ = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        // The conflict is detected before running initdb
+        let err = tenant
+            .bootstrap_timeline_test(TIMELINE_ID, DEFAULT_PG_VERSION, None, &ctx)
+            .await
+            .expect_err("duplicate timeline creation should fail");
+        assert!(
+            matches!(
+                err.downcast_ref::<CreateTimelineError>(),
+                Some(CreateTimelineError::Conflict)
+            ),
+            "{err:?}"
+        );
+
+        Ok(())
+    }
+
     /// Convenience function to create a page image with given string as the only content
     pub fn test_value(s: &str) -> Value {
         let mut buf = BytesMut::new();
@@ -9182,6 +9303,144 @@ fn get_key(id: u32) -> Key {
         Ok(())
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_gc_dry_run() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_gc_dry_run")
+            .await
+            .unwrap()
+            .load()
+            .await;
+        // Advance to the lsn lease deadline so that GC is not blocked by
+        // initial transition into AttachedSingle.
+        tokio::time::advance(tenant.get_lsn_lease_length()).await;
+        tokio::time::resume();
+        let key = Key::from_hex("010000000033333333444444445500000000").unwrap();
+
+        let end_lsn = Lsn(0x100);
+        let image_layers = (0x20..=0x90)
+            .step_by(0x10)
+            .map(|n| {
+                (
+                    Lsn(n),
+                    vec![(key, test_img(&format!("data key at {:x}", n)))],
+                )
+            })
+            .collect();
+
+        let timeline = tenant
+            .create_test_timeline_with_layers(
+                TIMELINE_ID,
+                Lsn(0x10),
+                DEFAULT_PG_VERSION,
+                &ctx,
+                Vec::new(), // in-memory layers
+                Vec::new(),
+                image_layers,
+                end_lsn,
+            )
+            .await?;
+        timeline.force_set_disk_consistent_lsn(end_lsn);
+
+        let cutoff_before = *timeline.get_applied_gc_cutoff_lsn();
+        let layers_before = timeline.inspect_historic_layers().await?;
+
+        let dry_run = tenant
+            .gc_iteration_with_options(
+                Some(TIMELINE_ID),
+                0,
+                Duration::ZERO,
+                GcOptions { dry_run: true },
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        assert!(dry_run.dry_run);
+        assert!(dry_run.layers_removed > 0);
+        let new_cutoff = dry_run.new_gc_cutoff_lsn.expect("cutoff would advance");
+        assert!(new_cutoff > cutoff_before);
+
+        // Nothing changed
+        assert_eq!(*timeline.get_applied_gc_cutoff_lsn(), cutoff_before);
+        assert_eq!(timeline.inspect_historic_layers().await?, layers_before);
+
+        // ... and a real GC does what the dry run predicted
+        let res = tenant
+            .gc_iteration(
+                Some(TIMELINE_ID),
+                0,
+                Duration::ZERO,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        assert!(!res.dry_run);
+        assert_eq!(res.layers_removed, dry_run.layers_removed);
+        assert_eq!(res.new_gc_cutoff_lsn, Some(new_cutoff));
+        assert_eq!(*timeline.get_applied_gc_cutoff_lsn(), new_cutoff);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gc_dry_run_allows_branching() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_gc_dry_run_allows_branching")
+            .await
+            .unwrap()
+            .load()
+            .await;
+        tokio::time::advance(tenant.get_lsn_lease_length()).await;
+        tokio::time::resume();
+        let key = Key::from_hex("010000000033333333444444445500000000").unwrap();
+
+        let end_lsn = Lsn(0x100);
+        let image_layers = (0x20..=0x90)
+            .step_by(0x10)
+            .map(|n| {
+                (
+                    Lsn(n),
+                    vec![(key, test_img(&format!("data key at {:x}", n)))],
+                )
+            })
+            .collect();
+        let timeline = tenant
+            .create_test_timeline_with_layers(
+                TIMELINE_ID,
+                Lsn(0x10),
+                DEFAULT_PG_VERSION,
+                &ctx,
+                Vec::new(), // in-memory layers
+                Vec::new(),
+                image_layers,
+                end_lsn,
+            )
+            .await?;
+        timeline.force_set_disk_consistent_lsn(end_lsn);
+
+        let planned_cutoff_before = timeline.gc_info.read().unwrap().min_cutoff();
+        let dry_run = tenant
+            .gc_iteration_with_options(
+                Some(TIMELINE_ID),
+                0,
+                Duration::ZERO,
+                GcOptions { dry_run: true },
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        assert!(dry_run.new_gc_cutoff_lsn.expect("cutoff would advance") > Lsn(0x20));
+
+        // The dry run's cutoffs were not stored, so branching below them still works
+        assert_eq!(
+            timeline.gc_info.read().unwrap().min_cutoff(),
+            planned_cutoff_before
+        );
+        tenant
+            .branch_timeline_test(&timeline, NEW_TIMELINE_ID, Some(Lsn(0x20)), &ctx)
+            .await?;
+
+        Ok(())
+    }
+
     #[tokio::test(start_paused = true)]
     async fn test_lsn_lease() -> anyhow::Result<()> {
         let (tenant, ctx) = TenantHarness::create("test_lsn_lease")
@@ -3,6 +3,7 @@
 
 use anyhow::Result;
 use serde::Serialize;
+use utils::lsn::Lsn;
 
 ///
 /// Result of performing GC
@@ -17,6 +18,14 @@ pub struct GcResult {
     pub layers_not_updated: u64,
     pub layers_removed: u64, // # of layer files removed because they have been made obsolete by newer ondisk files.
 
+    /// The GC cutoff the timeline advanced to, or `None` if it stayed put. If several timelines
+    /// were collected, the highest of their cutoffs.
+    pub new_gc_cutoff_lsn: Option<Lsn>,
+
+    /// If set, nothing was actually collected: the counts and the cutoff are what GC would have
+    /// done.
+    pub dry_run: bool,
+
     #[serde(serialize_with = "serialize_duration_as_millis")]
     pub elapsed: Duration,
 
@@ -46,6 +55,8 @@ fn add_assign(&mut self, other: Self) {
         self.layers_needed_by_leases += other.layers_needed_by_leases;
         self.layers_not_updated += other.layers_not_updated;
         self.layers_removed += other.layers_removed;
+        self.new_gc_cutoff_lsn = self.new_gc_cutoff_lsn.max(other.new_gc_cutoff_lsn);
+        self.dry_run |= other.dry_run;
 
         self.elapsed += other.elapsed;
 
@@ -51,7 +51,7 @@
 };
 use crate::tenant::span::debug_assert_current_span_has_tenant_id;
 use crate::tenant::storage_layer::inmemory_layer;
-use crate::tenant::timeline::ShutdownMode;
+use crate::tenant::timeline::{GcOptions, ShutdownMode};
 use crate::tenant::{
     AttachedTenantConf, GcError, LoadConfigError, SpawnMode, TenantShard, TenantState,
 };
@@ -2220,9 +2220,12 @@ pub(crate) async fn immediate_gc(
 
         fail::fail_point!("immediate_gc_task_pre");
 
+        let options = GcOptions {
+            dry_run: gc_req.dry_run,
+        };
         #[allow(unused_mut)]
         let mut result = tenant
-            .gc_iteration(Some(timeline_id), gc_horizon, pitr, &cancel, &ctx)
+            .gc_iteration_with_options(Some(timeline_id), gc_horizon, pitr, options, &cancel, &ctx)
             .await;
         // FIXME: `gc_iteration` can return an error for multiple reasons; we should handle it
         // better once the types support it.
@@ -176,6 +176,7 @@
 
 pub(crate) mod download;
 pub mod index;
+pub mod index_codec;
 pub mod manifest;
 pub(crate) mod upload;
 
@@ -183,11 +184,11 @@
 use std::ops::DerefMut;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use anyhow::Context;
 use camino::Utf8Path;
-use chrono::{NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 pub(crate) use download::{
     download_index_part, download_initdb_tar_zst, download_tenant_manifest, is_temp_download_file,
     list_remote_tenant_shards, list_remote_timelines,
@@ -583,6 +584,17 @@ pub(crate) fn is_invisible(&self) -> Option<bool> {
             .ok()
     }
 
+    /// Returns whether the layer is referenced by the last uploaded index_part.
+    /// Return None if the remote index_part hasn't been downloaded yet.
+    pub(crate) fn is_layer_in_remote_index(&self, layer_name: &LayerName) -> Option<bool> {
+        self.upload_queue
+            .lock()
+            .unwrap()
+            .initialized_mut()
+            .map(|q| q.clean.0.layer_metadata.contains_key(layer_name))
+            .ok()
+    }
+
     /// Returns `Ok(Some(timestamp))` if the timeline has been archived, `Ok(None)` if the timeline hasn't been archived.
     ///
     /// Return Err(_) if the remote index_part hasn't been downloaded yet, or the timeline hasn't been stopped yet.
@@ -976,6 +988,25 @@ pub(crate) fn schedule_index_upload_for_rel_size_v2_status_update(
         Ok(())
     }
 
+    /// Record layer access times in the in-memory index. Like the rel size migration status, this
+    /// does not schedule an upload of its own: the next index upload will include the times.
+    pub(crate) fn record_layer_accesses(
+        &self,
+        accesses: impl IntoIterator<Item = (LayerName, SystemTime)>,
+    ) -> Result<(), NotInitialized> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+        for (name, accessed_at) in accesses {
+            let Some(metadata) = upload_queue.dirty.layer_metadata.get_mut(&name) else {
+                continue;
+            };
+            let mut observed = metadata.clone();
+            observed.last_accessed = Some(DateTime::<Utc>::from(accessed_at));
+            metadata.merge(&observed);
+        }
+        Ok(())
+    }
+
     ///
     /// Launch an index-file upload operation in the background, if necessary.
     ///
@@ -1005,18 +1036,32 @@ pub fn force_schedule_index_upload(self: &Arc<Self>) -> Result<(), NotInitialize
         Ok(())
     }
 
+    /// Only used in the `reconcile_index` HTTP API: replace the layers of the index with `layers`
+    /// and force an index upload. Layers dropped from the index are not deleted remotely.
+    pub(crate) fn schedule_index_upload_for_reconcile(
+        self: &Arc<Self>,
+        layers: HashMap<LayerName, LayerFileMetadata>,
+    ) -> Result<(), NotInitialized> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+        upload_queue.dirty.layer_metadata = layers;
+        self.schedule_index_upload(upload_queue);
+        Ok(())
+    }
+
     /// Launch an index-file upload operation in the background (internal function)
     fn schedule_index_upload(self: &Arc<Self>, upload_queue: &mut UploadQueueInitialized) {
         let disk_consistent_lsn = upload_queue.dirty.metadata.disk_consistent_lsn();
-        // fix up the duplicated field
+        // fix up the duplicated fields
         upload_queue.dirty.disk_consistent_lsn = disk_consistent_lsn;
+        upload_queue.dirty.pg_version = Some(upload_queue.dirty.metadata.pg_version());
 
         // make sure it serializes before doing it in perform_upload_task so that it doesn't
         // look like a retryable error
         let void = std::io::sink();
         serde_json::to_writer(void, &upload_queue.dirty).expect("serialize index_part.json");
 
-        let index_part = &upload_queue.dirty;
+        let index_part = upload_queue.dirty.clone();
 
         info!(
             "scheduling metadata upload up to consistent LSN {disk_consistent_lsn} with {} files ({} changed)",
@@ -1024,8 +1069,9 @@ fn schedule_index_upload(self: &Arc<Self>, upload_queue: &mut UploadQueueInitial
             upload_queue.latest_files_changes_since_metadata_upload_scheduled,
         );
 
+        // The epoch is bumped when the upload starts, see `launch_queued_tasks`.
         let op = UploadOp::UploadMetadata {
-            uploaded: Box::new(index_part.clone()),
+            uploaded: Box::new(index_part),
         };
         self.metric_begin(&op);
         upload_queue.queued_operations.push_back(op);
@@ -1237,21 +1283,33 @@ pub(crate) fn schedule_layer_file_upload(
         self: &Arc<Self>,
         layer: ResidentLayer,
     ) -> Result<(), NotInitialized> {
+        let metadata = Self::layer_upload_metadata(&layer);
+
         let mut guard = self.upload_queue.lock().unwrap();
         let upload_queue = guard.initialized_mut()?;
 
-        self.schedule_layer_file_upload0(upload_queue, layer);
+        self.schedule_layer_file_upload0(upload_queue, layer, metadata);
         self.launch_queued_tasks(upload_queue);
         Ok(())
     }
 
+    /// The metadata to upload `layer` with. Stats the local file, so call it before taking the
+    /// upload queue lock.
+    fn layer_upload_metadata(layer: &ResidentLayer) -> LayerFileMetadata {
+        let mut metadata = layer.metadata();
+        // Lets a restart tell the uploaded file from a rewritten one of the same size.
+        metadata.local_mtime = std::fs::metadata(layer.local_path())
+            .and_then(|m| m.modified())
+            .ok();
+        metadata
+    }
+
     fn schedule_layer_file_upload0(
         self: &Arc<Self>,
         upload_queue: &mut UploadQueueInitialized,
         layer: ResidentLayer,
+        metadata: LayerFileMetadata,
     ) {
-        let metadata = layer.metadata();
-
         upload_queue
             .dirty
             .layer_metadata
@@ -1454,11 +1512,16 @@ pub(crate) fn schedule_compaction_update(
         compacted_from: &[Layer],
         compacted_to: &[ResidentLayer],
     ) -> Result<(), NotInitialized> {
+        let uploads = compacted_to
+            .iter()
+            .map(|layer| (layer.clone(), Self::layer_upload_metadata(layer)))
+            .collect::<Vec<_>>();
+
         let mut guard = self.upload_queue.lock().unwrap();
         let upload_queue = guard.initialized_mut()?;
 
-        for layer in compacted_to {
-            self.schedule_layer_file_upload0(upload_queue, layer.clone());
+        for (layer, metadata) in uploads {
+            self.schedule_layer_file_upload0(upload_queue, layer, metadata);
         }
 
         let names = compacted_from.iter().map(|x| x.layer_desc().layer_name());
@@ -1987,7 +2050,11 @@ fn launch_queued_tasks(self: &Arc<Self>, upload_queue: &mut UploadQueueInitializ
                         *mode = Some(OpType::MayReorder)
                     }
                 }
-                UploadOp::UploadMetadata { .. } => {}
+                UploadOp::UploadMetadata { uploaded } => {
+                    // Index uploads run one at a time, so the clean index is the last one that
+                    // was uploaded: number this one after it.
+                    uploaded.epoch = upload_queue.clean.0.epoch() + 1;
+                }
                 UploadOp::Delete(Delete { layers }) => {
                     for (name, meta) in layers {
                         upload_queue
@@ -2341,6 +2408,7 @@ async fn perform_upload_task(self: &Arc<Self>, task: Arc<UploadTask>) {
                     // not taking ownership is wasteful
                     upload_queue.clean.0.clone_from(uploaded);
                     upload_queue.clean.1 = Some(task.task_id);
+                    upload_queue.dirty.epoch = uploaded.epoch();
 
                     let lsn = upload_queue.clean.0.metadata.disk_consistent_lsn();
                     self.metrics
@@ -2976,6 +3044,8 @@ async fn upload_scheduling() {
             let upload_queue = guard.initialized_mut().unwrap();
             assert!(upload_queue.queued_operations.len() == 1);
             assert!(upload_queue.latest_files_changes_since_metadata_upload_scheduled == 0);
+            // Only bumped once the upload completes
+            assert_eq!(upload_queue.dirty.epoch(), initial_index_part.epoch());
         }
 
         // Wait for the uploads to finish
@@ -3011,6 +3081,12 @@ async fn upload_scheduling() {
             ],
         );
         assert_eq!(index_part.metadata, metadata);
+        assert_eq!(index_part.epoch(), initial_index_part.epoch() + 1);
+        {
+            let mut guard = client.upload_queue.lock().unwrap();
+            let upload_queue = guard.initialized_mut().unwrap();
+            assert_eq!(upload_queue.dirty.epoch(), index_part.epoch());
+        }
 
         // Schedule upload and then a deletion. Check that the deletion is queued
         client
@@ -118,6 +118,23 @@ pub async fn download_layer_file<'a>(
         )));
     }
 
+    // Restore the modification time of the file the layer was uploaded from, so that a restart
+    // doesn't take the downloaded file for a rewritten one. See
+    // [`LayerFileMetadata::matches_local_file`].
+    if let Some(mtime) = layer_metadata.local_mtime {
+        let set_mtime = async {
+            let file = OpenOptions::new()
+                .write(true)
+                .open(temp_file.path())
+                .await?;
+            file.into_std().await.set_modified(mtime)
+        };
+        set_mtime
+            .await
+            .with_context(|| format!("set modification time of {:?}", temp_file.path()))
+            .map_err(DownloadError::Other)?;
+    }
+
     fail::fail_point!("remote-storage-download-pre-rename", |_| {
         Err(DownloadError::Other(anyhow!(
             "remote-storage-download-pre-rename failpoint triggered"
@@ -386,9 +403,10 @@ async fn do_download_index_part(
     let (index_part_bytes, index_part_mtime) =
         do_download_remote_path_retry_forever(storage, &remote_path, download_opts, cancel).await?;
 
-    let index_part: IndexPart = serde_json::from_slice(&index_part_bytes)
-        .with_context(|| format!("deserialize index part file at {remote_path:?}"))
-        .map_err(DownloadError::Other)?;
+    let index_part =
+        IndexPart::from_bytes_observed(&index_part_bytes, tenant_shard_id, timeline_id)
+            .with_context(|| format!("deserialize index part file at {remote_path:?}"))
+            .map_err(DownloadError::Other)?;
 
     Ok((index_part, index_generation, index_part_mtime))
 }
@@ -4,16 +4,21 @@
 //! remote timeline layers and its metadata.
 
 use std::collections::HashMap;
+use std::time::SystemTime;
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use pageserver_api::models::AuxFilePolicy;
 use pageserver_api::models::RelSizeMigration;
-use pageserver_api::shard::ShardIndex;
+use pageserver_api::shard::{ShardIndex, TenantShardId};
+use remote_storage::RemotePath;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 use utils::id::TimelineId;
 use utils::lsn::Lsn;
 
-use super::is_same_remote_layer_path;
+use super::index_codec::{IndexCodecs, IndexPartError};
+use super::{is_same_remote_layer_path, parse_remote_index_path};
+use crate::metrics;
 use crate::tenant::Generation;
 use crate::tenant::metadata::TimelineMetadata;
 use crate::tenant::storage_layer::LayerName;
@@ -27,9 +32,19 @@
 /// remember to add a test case for the changed version.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct IndexPart {
-    /// Debugging aid describing the version of this type.
-    #[serde(default)]
-    version: usize,
+    /// Version of the serialization schema of this type. Written as `version` for compatibility
+    /// with readers that predate the split from [`Self::content_version`].
+    #[serde(
+        rename = "version",
+        alias = "format_version",
+        default = "IndexPart::default_format_version"
+    )]
+    format_version: usize,
+
+    /// Version of the semantics of the contents, independent of how they are serialized. Bumped
+    /// when the meaning of existing fields changes in a way older readers must not ignore.
+    #[serde(default = "IndexPart::default_content_version")]
+    content_version: usize,
 
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -114,6 +129,74 @@ pub struct IndexPart {
     /// The timestamp when the timeline was marked invisible in synthetic size calculations.
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub(crate) marked_invisible_at: Option<NaiveDateTime>,
+
+    /// Postgres version of the timeline, duplicated from [`Self::metadata`] for tools inspecting
+    /// remote storage. Informative only: reads go through `metadata`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) pg_version: Option<u32>,
+
+    /// Incremented for every completed upload of this index, see [`Self::prepare_next`]: an
+    /// upload is numbered after the last uploaded index when it starts. Lets an uploader detect
+    /// that the remote index was overwritten since it read it, by comparing epochs.
+    #[serde(default)]
+    pub(crate) epoch: u64,
+
+    /// Top-level fields unknown to this version, written by a newer one. Written back as they
+    /// were read by [`IndexPart::to_json_bytes`], so that rewriting the index after a downgrade
+    /// doesn't lose them. Only collected by [`IndexPart::from_json_bytes`], and only for indices of
+    /// a newer format version: older ones can't contain any.
+    #[serde(skip)]
+    extra: serde_json::Map<String, serde_json::Value>,
+
+    /// The checksum the index was stored with, absent before format version 20. Only set while
+    /// parsing: [`IndexPart::from_json_bytes`] takes it to verify it, as it is stale as soon as the
+    /// index is modified.
+    #[serde(rename = "checksum", default, skip_serializing)]
+    stored_checksum: Option<u32>,
+}
+
+/// Serialized names of the top-level fields of [`IndexPart`], including aliases and the
+/// checksum written by [`IndexPart::to_json_bytes`]. Anything else is kept in [`IndexPart::extra`].
+const KNOWN_FIELDS: &[&str] = &[
+    "version",
+    "format_version",
+    "content_version",
+    "deleted_at",
+    "archived_at",
+    "import_pgdata",
+    "layer_metadata",
+    "disk_consistent_lsn",
+    "metadata_bytes",
+    "metadata",
+    "lineage",
+    "gc_blocking",
+    "last_aux_file_policy",
+    "rel_size_migration",
+    "l2_lsn",
+    "gc_compaction",
+    "marked_invisible_at",
+    "pg_version",
+    "epoch",
+    "checksum",
+];
+
+/// Fields that older versions wrote, but that are not read anymore. They are not kept in
+/// [`IndexPart::extra`], so that they are dropped on the next write.
+///
+/// - `timeline_layers`: not read since format version 3, not written since 4.
+/// - `missing_layers`: never used, only found in indices of early format versions. Indices that
+///   still contain it lose it when they are next uploaded.
+const RETIRED_FIELDS: &[&str] = &["timeline_layers", "missing_layers"];
+
+/// How an [`IndexPart`] is serialized: the checksum is not kept in memory, as it is stale as soon
+/// as the index is modified.
+#[derive(Serialize)]
+struct ChecksummedIndexPartRef<'a> {
+    #[serde(flatten)]
+    index_part: &'a IndexPart,
+    #[serde(flatten)]
+    extra: &'a serde_json::Map<String, serde_json::Value>,
+    checksum: u32,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -126,7 +209,9 @@ impl IndexPart {
     /// When adding or modifying any parts of `IndexPart`, increment the version so that it can be
     /// used to understand later versions.
     ///
-    /// Version is currently informative only.
+    /// The format version describes the serialization schema only; semantic changes are tracked
+    /// by [`Self::LATEST_CONTENT_VERSION`].
+    ///
     /// Version history
     /// - 2: added `deleted_at`
     /// - 3: no longer deserialize `timeline_layers` (serialized format is the same, but timeline_layers
@@ -142,16 +227,32 @@ impl IndexPart {
     /// - 12: +l2_lsn
     /// - 13: +gc_compaction
     /// - 14: +marked_invisible_at
-    const LATEST_VERSION: usize = 14;
-
-    // Versions we may see when reading from a bucket.
-    pub const KNOWN_VERSIONS: &'static [usize] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
+    /// - 15: +content_version
+    /// - 16: +last_accessed in layer metadata
+    /// - 17: +pg_version
+    /// - 18: +epoch
+    /// - 19: +local_mtime in layer metadata
+    /// - 20: +checksum, only in the serialized form (see [`Self::checksum`])
+    const LATEST_FORMAT_VERSION: usize = 20;
+
+    /// The first format version that is written with a checksum.
+    const CHECKSUM_FORMAT_VERSION: usize = 20;
+
+    /// Content version history
+    /// - 1: initial content semantics, also assumed for indices written before this field existed
+    const LATEST_CONTENT_VERSION: usize = 1;
+
+    // Format versions we may see when reading from a bucket.
+    pub const KNOWN_VERSIONS: &'static [usize] = &[
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
+    ];
 
     pub const FILE_NAME: &'static str = "index_part.json";
 
     pub fn empty(metadata: TimelineMetadata) -> Self {
         IndexPart {
-            version: Self::LATEST_VERSION,
+            format_version: Self::LATEST_FORMAT_VERSION,
+            content_version: Self::LATEST_CONTENT_VERSION,
             layer_metadata: Default::default(),
             disk_consistent_lsn: metadata.disk_consistent_lsn(),
             metadata,
@@ -165,11 +266,40 @@ pub fn empty(metadata: TimelineMetadata) -> Self {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         }
     }
 
-    pub fn version(&self) -> usize {
-        self.version
+    /// Indices written before the version field was introduced are the first format.
+    fn default_format_version() -> usize {
+        1
+    }
+
+    fn default_content_version() -> usize {
+        1
+    }
+
+    pub fn format_version(&self) -> usize {
+        self.format_version
+    }
+
+    pub fn content_version(&self) -> usize {
+        self.content_version
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the index to upload next, with the epoch of this one bumped.
+    pub fn prepare_next(&self) -> IndexPart {
+        IndexPart {
+            epoch: self.epoch + 1,
+            ..self.clone()
+        }
     }
 
     /// If you want this under normal operations, read it from self.metadata:
@@ -178,12 +308,111 @@ pub fn duplicated_disk_consistent_lsn(&self) -> Lsn {
         self.disk_consistent_lsn
     }
 
+    /// Parses an index part, verifying its checksum if it has one.
     pub fn from_json_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
-        serde_json::from_slice::<IndexPart>(bytes)
+        let mut index_part: IndexPart = serde_json::from_slice(bytes)?;
+        if index_part.format_version > Self::LATEST_FORMAT_VERSION {
+            // Written by a newer version: parse again to keep what this one doesn't know.
+            let mut fields: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_slice(bytes)?;
+            fields.retain(|name, _| {
+                !KNOWN_FIELDS.contains(&name.as_str()) && !RETIRED_FIELDS.contains(&name.as_str())
+            });
+            index_part.extra = fields;
+        }
+        let Some(checksum) = index_part.stored_checksum.take() else {
+            // Older versions didn't write one, only complain if this version should have.
+            if index_part.format_version >= Self::CHECKSUM_FORMAT_VERSION {
+                warn!(
+                    format_version = index_part.format_version,
+                    "index part has no checksum, not verifying it"
+                );
+            }
+            return Ok(index_part);
+        };
+        let computed = index_part.checksum()?;
+        if computed != checksum {
+            return Err(serde::de::Error::custom(format!(
+                "index part checksum mismatch: stored {checksum:08x}, computed {computed:08x}"
+            )));
+        }
+        Ok(index_part)
+    }
+
+    /// Decodes an index part as stored in remote storage, with whichever of the installed
+    /// [`IndexCodecs`] it was written with. Records parse duration, input size and failures in
+    /// the `pageserver_index_part_parse_*` metrics for the given timeline.
+    pub(crate) fn from_bytes_observed(
+        bytes: &[u8],
+        tenant_shard_id: &TenantShardId,
+        timeline_id: &TimelineId,
+    ) -> Result<Self, IndexPartError> {
+        let tenant_id = tenant_shard_id.tenant_id.to_string();
+        let shard_id = tenant_shard_id.shard_slug().to_string();
+        let timeline_id = timeline_id.to_string();
+        let labels = [tenant_id.as_str(), shard_id.as_str(), timeline_id.as_str()];
+
+        let started_at = std::time::Instant::now();
+        let res = IndexCodecs::installed().decode(bytes);
+        metrics::INDEX_PART_PARSE_SECONDS
+            .with_label_values(&labels)
+            .observe(started_at.elapsed().as_secs_f64());
+        metrics::INDEX_PART_PARSE_BYTES
+            .with_label_values(&labels)
+            .observe(bytes.len() as f64);
+
+        if let Err(e) = &res {
+            let category = match e {
+                IndexPartError::Json(e) => match e.classify() {
+                    serde_json::error::Category::Io => "io",
+                    serde_json::error::Category::Syntax => "syntax",
+                    serde_json::error::Category::Data => "data",
+                    serde_json::error::Category::Eof => "eof",
+                },
+                IndexPartError::UnknownCodec(_)
+                | IndexPartError::Truncated
+                | IndexPartError::Codec { .. } => "codec",
+            };
+            metrics::INDEX_PART_PARSE_ERRORS
+                .with_label_values(&[category])
+                .inc();
+        }
+        res
     }
 
     pub fn to_json_bytes(&self) -> serde_json::Result<Vec<u8>> {
-        serde_json::to_vec(self)
+        serde_json::to_vec(&ChecksummedIndexPartRef {
+            index_part: self,
+            extra: &self.extra,
+            checksum: self.checksum()?,
+        })
+    }
+
+    /// CRC32C over the layers and the timeline metadata, written next to the index part to detect
+    /// truncated or corrupted uploads.
+    ///
+    /// Only covers fields that all versions since 20 read, so that re-serializing an index
+    /// written by a newer version gives the same checksum. The layers are hashed in name order,
+    /// as the order of `layer_metadata` in the json is arbitrary.
+    fn checksum(&self) -> serde_json::Result<u32> {
+        let mut layers = self.layer_metadata.iter().collect::<Vec<_>>();
+        layers.sort_by_cached_key(|(name, _)| name.to_string());
+
+        let mut crc = 0;
+        for (name, metadata) in layers {
+            let line = format!(
+                "{name} {} {}{}\n",
+                metadata.file_size,
+                metadata.shard,
+                metadata.generation.get_suffix()
+            );
+            crc = crc32c::crc32c_append(crc, line.as_bytes());
+        }
+        let metadata_bytes = self
+            .metadata
+            .to_bytes()
+            .map_err(serde::ser::Error::custom)?;
+        Ok(crc32c::crc32c_append(crc, &metadata_bytes))
     }
 
     #[cfg(test)]
@@ -202,6 +431,67 @@ pub fn references(&self, name: &LayerName, metadata: &LayerFileMetadata) -> bool
         is_same_remote_layer_path(name, metadata, name, index_metadata)
     }
 
+    /// Compares the layers of this index with `layers`: `added` are the ones only in `layers`,
+    /// `removed` the ones only in this index. A layer whose remote path differs, e.g. because of
+    /// its generation, counts as both.
+    pub(crate) fn layer_diff(
+        &self,
+        layers: &HashMap<LayerName, LayerFileMetadata>,
+    ) -> IndexLayerDiff {
+        let mut added = layers
+            .iter()
+            .filter(|(name, metadata)| !self.references(name, metadata))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let mut removed = self
+            .layer_metadata
+            .iter()
+            .filter(|(name, metadata)| {
+                layers
+                    .get(name)
+                    .is_none_or(|other| !is_same_remote_layer_path(name, metadata, name, other))
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        added.sort_by_key(LayerName::to_string);
+        removed.sort_by_key(LayerName::to_string);
+        IndexLayerDiff { added, removed }
+    }
+
+    /// Compares the layers of this index with the layer files found on local disk, e.g. when
+    /// loading a timeline after restart. Local files not in the index are `orphans`, which are
+    /// safe to delete.
+    pub(crate) fn reconcile_residency(
+        &self,
+        local_layers: &HashMap<LayerName, LocalLayerFile>,
+    ) -> ResidencyReport {
+        let mut resident = Vec::new();
+        let mut remote_only = Vec::new();
+        let mut stale = Vec::new();
+        for (name, metadata) in &self.layer_metadata {
+            match local_layers.get(name) {
+                Some(local) if metadata.matches_local_file(local) => resident.push(name.clone()),
+                Some(_) => stale.push(name.clone()),
+                None => remote_only.push(name.clone()),
+            }
+        }
+        let mut orphans = local_layers
+            .keys()
+            .filter(|name| !self.layer_metadata.contains_key(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        resident.sort_by_key(LayerName::to_string);
+        remote_only.sort_by_key(LayerName::to_string);
+        stale.sort_by_key(LayerName::to_string);
+        orphans.sort_by_key(LayerName::to_string);
+        ResidencyReport {
+            resident,
+            remote_only,
+            stale,
+            orphans,
+        }
+    }
+
     /// Check for invariants in the index: this is useful when uploading an index to ensure that if
     /// we encounter a bug, we do not persist buggy metadata.
     pub(crate) fn validate(&self) -> Result<(), String> {
@@ -233,6 +523,18 @@ pub struct LayerFileMetadata {
     #[serde(default = "ShardIndex::unsharded")]
     #[serde(skip_serializing_if = "ShardIndex::is_unsharded")]
     pub shard: ShardIndex,
+
+    /// When the layer was last read, as last observed by a pageserver attached to the timeline.
+    /// Lets eviction tooling tell layers that are actually cold from those that are merely old.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_accessed: Option<DateTime<Utc>>,
+
+    /// Modification time of the local file when the layer was uploaded. Tells the uploaded file
+    /// apart from a different local file of the same name and size after a crash.
+    #[serde(default, with = "humantime_serde")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_mtime: Option<SystemTime>,
 }
 
 impl LayerFileMetadata {
@@ -241,12 +543,40 @@ pub fn new(file_size: u64, generation: Generation, shard: ShardIndex) -> Self {
             file_size,
             generation,
             shard,
+            last_accessed: None,
+            local_mtime: None,
         }
     }
     /// Helper to get both generation and file size in a tuple
     pub fn generation_file_size(&self) -> (Generation, u64) {
         (self.generation, self.file_size)
     }
+
+    /// Folds another observation of the same layer into this one, keeping the most recent
+    /// access and modification times.
+    pub fn merge(&mut self, other: &LayerFileMetadata) {
+        self.last_accessed = self.last_accessed.max(other.last_accessed);
+        self.local_mtime = self.local_mtime.max(other.local_mtime);
+    }
+
+    /// Whether both describe the same layer file, regardless of when it was last read or of its
+    /// local modification time.
+    pub fn is_same_file(&self, other: &LayerFileMetadata) -> bool {
+        self.file_size == other.file_size
+            && self.generation == other.generation
+            && self.shard == other.shard
+    }
+
+    /// Whether `local` is the file this layer was uploaded from. The modification time is only
+    /// compared if it is known on both sides. Downloads restore the recorded time, so that a file
+    /// that was evicted and downloaded again still matches.
+    pub(crate) fn matches_local_file(&self, local: &LocalLayerFile) -> bool {
+        self.file_size == local.file_size
+            && match (self.local_mtime, local.mtime) {
+                (Some(uploaded), Some(found)) => uploaded == found,
+                _ => true,
+            }
+    }
 }
 
 /// Limited history of earlier ancestors.
@@ -425,9 +755,102 @@ pub(super) fn without_reason(&self, reason: GcBlockingReason) -> Option<Self> {
     }
 }
 
+/// A historical `index_part.json` object of a timeline, as found in a listing.
+#[derive(Debug, Clone)]
+pub struct IndexHistoryEntry {
+    pub key: RemotePath,
+    /// [`IndexPart::deleted_at`] of this index, if it was downloaded and marks the timeline as
+    /// deleted. Such tombstones are retained for a grace period.
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+/// Difference between the layer sets of two indices, see [`IndexPart::layer_diff`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct IndexLayerDiff {
+    pub(crate) added: Vec<LayerName>,
+    pub(crate) removed: Vec<LayerName>,
+}
+
+/// A layer file found on local disk, see [`IndexPart::reconcile_residency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LocalLayerFile {
+    pub(crate) file_size: u64,
+    pub(crate) mtime: Option<SystemTime>,
+}
+
+/// Residency of a timeline's layers, see [`IndexPart::reconcile_residency`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ResidencyReport {
+    /// In the index and on local disk.
+    pub(crate) resident: Vec<LayerName>,
+    /// In the index, but not on local disk.
+    pub(crate) remote_only: Vec<LayerName>,
+    /// In the index, but the local file of that name is not the uploaded one: its size or
+    /// modification time differs.
+    pub(crate) stale: Vec<LayerName>,
+    /// On local disk, but not in the index.
+    pub(crate) orphans: Vec<LayerName>,
+}
+
+/// Partition of a timeline's historical indices, see [`select_index_history_retention`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexHistoryRetention {
+    pub retain: Vec<RemotePath>,
+    pub delete: Vec<RemotePath>,
+}
+
+/// Selects which historical indices of a timeline to keep: the newest `retain_latest` generations
+/// and any tombstone deleted less than `tombstone_grace_period` before `now`. The rest are safe
+/// to delete.
+///
+/// The current (newest) index is always retained, as are keys that do not parse as index keys.
+pub fn select_index_history_retention(
+    entries: impl IntoIterator<Item = IndexHistoryEntry>,
+    retain_latest: usize,
+    tombstone_grace_period: std::time::Duration,
+    now: NaiveDateTime,
+) -> IndexHistoryRetention {
+    let mut retention = IndexHistoryRetention::default();
+
+    let mut generations = Vec::new();
+    for entry in entries {
+        let generation = match parse_remote_index_path(entry.key.clone()) {
+            Some(generation) => generation,
+            // A legacy pre-generation index
+            None if entry.key.object_name() == Some(IndexPart::FILE_NAME) => Generation::none(),
+            // We will not delete what we don't understand.
+            None => {
+                retention.retain.push(entry.key);
+                continue;
+            }
+        };
+        generations.push((generation, entry));
+    }
+
+    // Newest first
+    generations.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let grace_period =
+        chrono::Duration::from_std(tombstone_grace_period).unwrap_or(chrono::Duration::MAX);
+    for (i, (_, entry)) in generations.into_iter().enumerate() {
+        let is_current = i == 0;
+        let within_grace_period = entry
+            .deleted_at
+            .is_some_and(|deleted_at| now.signed_duration_since(deleted_at) < grace_period);
+        if is_current || i < retain_latest || within_grace_period {
+            retention.retain.push(entry.key);
+        } else {
+            retention.delete.push(entry.key);
+        }
+    }
+
+    retention
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
+    use std::time::Duration;
 
     use utils::id::TimelineId;
 
@@ -448,19 +871,24 @@ fn v1_indexpart_is_parsed() {
 
         let expected = IndexPart {
             // note this is not verified, could be anything, but exists for humans debugging.. could be the git version instead?
-            version: 1,
+            format_version: 1,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -475,6 +903,10 @@ fn v1_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -497,19 +929,24 @@ fn v1_indexpart_is_parsed_with_optional_missing_layers() {
 
         let expected = IndexPart {
             // note this is not verified, could be anything, but exists for humans debugging.. could be the git version instead?
-            version: 1,
+            format_version: 1,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -524,10 +961,18 @@ fn v1_indexpart_is_parsed_with_optional_missing_layers() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
         assert_eq!(part, expected);
+
+        // Accepted on read, but never written back
+        let rewritten = String::from_utf8(part.to_json_bytes().unwrap()).unwrap();
+        assert!(!rewritten.contains("missing_layers"), "{rewritten}");
     }
 
     #[test]
@@ -547,19 +992,24 @@ fn v2_indexpart_is_parsed_with_deleted_at() {
 
         let expected = IndexPart {
             // note this is not verified, could be anything, but exists for humans debugging.. could be the git version instead?
-            version: 2,
+            format_version: 2,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -574,6 +1024,10 @@ fn v2_indexpart_is_parsed_with_deleted_at() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -591,7 +1045,8 @@ fn empty_layers_are_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 1,
+            format_version: 1,
+            content_version: 1,
             layer_metadata: HashMap::new(),
             disk_consistent_lsn: "0/2532648".parse::<Lsn>().unwrap(),
             metadata: TimelineMetadata::from_bytes(&[
@@ -627,6 +1082,10 @@ fn empty_layers_are_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let empty_layers_parsed = IndexPart::from_json_bytes(empty_layers_json.as_bytes()).unwrap();
@@ -648,19 +1107,24 @@ fn v4_indexpart_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 4,
+            format_version: 4,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -675,6 +1139,10 @@ fn v4_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -697,17 +1165,20 @@ fn v5_indexpart_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 5,
+            format_version: 5,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000014EF420-00000000014EF499".parse().unwrap(), LayerFileMetadata {
                     file_size: 23289856,
                     generation: Generation::new(1),
                     shard: ShardIndex::unsharded(),
+                    last_accessed: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000014EF499-00000000015A7619".parse().unwrap(), LayerFileMetadata {
                     file_size: 1015808,
                     generation: Generation::new(1),
                     shard: ShardIndex::unsharded(),
+                    last_accessed: None,
                 })
             ]),
             disk_consistent_lsn: Lsn::from_str("0/15A7618").unwrap(),
@@ -726,6 +1197,10 @@ fn v5_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -751,19 +1226,24 @@ fn v6_indexpart_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 6,
+            format_version: 6,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -782,6 +1262,10 @@ fn v6_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -810,17 +1294,22 @@ fn v7_indexpart_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 7,
+            format_version: 7,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -843,6 +1332,10 @@ fn v7_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -872,17 +1365,22 @@ fn v8_indexpart_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 8,
+            format_version: 8,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -905,6 +1403,10 @@ fn v8_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -936,17 +1438,22 @@ fn v9_indexpart_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 9,
+            format_version: 9,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -972,6 +1479,10 @@ fn v9_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1012,17 +1523,22 @@ fn v10_importpgdata_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 10,
+            format_version: 10,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1052,6 +1568,10 @@ fn v10_importpgdata_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1093,17 +1613,22 @@ fn v11_rel_size_migration_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 11,
+            format_version: 11,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1133,6 +1658,10 @@ fn v11_rel_size_migration_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1178,17 +1707,22 @@ fn v12_v13_l2_gc_ompaction_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 13,
+            format_version: 13,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1220,6 +1754,10 @@ fn v12_v13_l2_gc_ompaction_is_parsed() {
                 last_completed_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
             }),
             marked_invisible_at: None,
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1266,17 +1804,22 @@ fn v14_marked_invisible_at_is_parsed() {
         }"#;
 
         let expected = IndexPart {
-            version: 14,
+            format_version: 14,
+            content_version: 1,
             layer_metadata: HashMap::from([
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    last_accessed: None,
+                    local_mtime: None,
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1308,13 +1851,428 @@ fn v14_marked_invisible_at_is_parsed() {
                 last_completed_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
             }),
             marked_invisible_at: Some(parse_naive_datetime("2023-07-31T09:00:00.123000000")),
+            pg_version: None,
+            epoch: 0,
+            extra: Default::default(),
+            stored_checksum: None,
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
         assert_eq!(part, expected);
     }
 
+    #[test]
+    fn format_and_content_versions_are_parsed() {
+        let parse = |versions: &str| {
+            let example = format!(
+                r#"{{
+                    {versions}
+                    "layer_metadata":{{}},
+                    "disk_consistent_lsn":"0/16960E8",
+                    "metadata": {{
+                        "disk_consistent_lsn": "0/16960E8",
+                        "prev_record_lsn": "0/1696070",
+                        "ancestor_timeline": null,
+                        "ancestor_lsn": "0/0",
+                        "latest_gc_cutoff_lsn": "0/1696070",
+                        "initdb_lsn": "0/1696070",
+                        "pg_version": 14
+                    }}
+                }}"#
+            );
+            let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
+            (part.format_version(), part.content_version())
+        };
+
+        // Legacy index without any version field
+        assert_eq!(parse(""), (1, 1));
+        // Format version only, under both the legacy and the new name
+        assert_eq!(parse(r#""version": 14,"#), (14, 1));
+        assert_eq!(parse(r#""format_version": 14,"#), (14, 1));
+        // Both versions present
+        assert_eq!(parse(r#""version": 15, "content_version": 2,"#), (15, 2));
+
+        // Newly written indices carry the latest versions and round-trip them
+        let part = IndexPart::example();
+        assert_eq!(part.format_version(), IndexPart::LATEST_FORMAT_VERSION);
+        assert_eq!(part.content_version(), IndexPart::LATEST_CONTENT_VERSION);
+        let bytes = part.to_json_bytes().unwrap();
+        assert_eq!(IndexPart::from_json_bytes(&bytes).unwrap(), part);
+    }
+
+    #[test]
+    fn pg_version_round_trips() {
+        let mut part = IndexPart::example();
+        // Only filled in when scheduling an upload, omitted from the json otherwise
+        assert_eq!(part.pg_version, None);
+        let bytes = part.to_json_bytes().unwrap();
+        assert!(!String::from_utf8(bytes).unwrap().contains("pg_version\":"));
+
+        part.pg_version = Some(part.metadata.pg_version());
+        let bytes = part.to_json_bytes().unwrap();
+        let parsed = IndexPart::from_json_bytes(&bytes).unwrap();
+        assert_eq!(parsed.pg_version, Some(part.metadata.pg_version()));
+        assert_eq!(parsed, part);
+    }
+
+    #[test]
+    fn unknown_fields_round_trip() {
+        let mut json: serde_json::Value =
+            serde_json::from_slice(&IndexPart::example().to_json_bytes().unwrap()).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        fields.insert(
+            "version".to_string(),
+            (IndexPart::LATEST_FORMAT_VERSION + 1).into(),
+        );
+        fields.insert(
+            "from_the_future".to_string(),
+            serde_json::json!({ "nested": [1, 2, 3] }),
+        );
+        fields.insert("timeline_layers".to_string(), serde_json::json!([]));
+        let bytes = serde_json::to_vec(&json).unwrap();
+
+        let part = IndexPart::from_json_bytes(&bytes).unwrap();
+        // Neither known fields like the checksum nor retired ones are kept
+        assert_eq!(
+            part.extra.keys().collect::<Vec<_>>(),
+            vec!["from_the_future"]
+        );
+
+        // Still there after a modification and a rewrite, unlike the retired field
+        let part = part.prepare_next();
+        let rewritten: serde_json::Value =
+            serde_json::from_slice(&part.to_json_bytes().unwrap()).unwrap();
+        assert_eq!(
+            rewritten["from_the_future"],
+            serde_json::json!({ "nested": [1, 2, 3] })
+        );
+        assert!(rewritten.get("timeline_layers").is_none());
+        assert_eq!(
+            IndexPart::from_json_bytes(&serde_json::to_vec(&rewritten).unwrap()).unwrap(),
+            part
+        );
+    }
+
+    #[test]
+    fn known_fields_are_complete() {
+        // All fields set, so that none is skipped when serializing
+        let mut part = IndexPart::example();
+        part.deleted_at = Some(parse_naive_datetime("2023-07-31T09:00:00.123000000"));
+        part.archived_at = Some(parse_naive_datetime("2023-07-31T09:00:00.123000000"));
+        part.gc_blocking = Some(GcBlocking::started_now_for(GcBlockingReason::Manual));
+        part.last_aux_file_policy = Some(AuxFilePolicy::V2);
+        part.rel_size_migration = Some(RelSizeMigration::Legacy);
+        part.l2_lsn = Some(Lsn(1));
+        part.gc_compaction = Some(GcCompactionState {
+            last_completed_lsn: Lsn(1),
+        });
+        part.marked_invisible_at = Some(parse_naive_datetime("2023-07-31T09:00:00.123000000"));
+        part.pg_version = Some(part.metadata.pg_version());
+        let json: serde_json::Value =
+            serde_json::from_slice(&part.to_json_bytes().unwrap()).unwrap();
+        for name in json.as_object().unwrap().keys() {
+            assert!(KNOWN_FIELDS.contains(&name.as_str()), "{name}");
+        }
+    }
+
+    #[test]
+    fn checksum_catches_corruption() {
+        let mut part = IndexPart::example();
+        part.layer_metadata.insert(
+            "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9"
+                .parse()
+                .unwrap(),
+            LayerFileMetadata::new(25600000, Generation::new(3), ShardIndex::unsharded()),
+        );
+        let bytes = part.to_json_bytes().unwrap();
+        assert_eq!(IndexPart::from_json_bytes(&bytes).unwrap(), part);
+
+        // Flip a digit of the file size: still valid json, but no longer the uploaded index
+        let json = String::from_utf8(bytes.clone()).unwrap();
+        assert_eq!(json.matches("25600000").count(), 1);
+        let corrupted = json.replace("25600000", "25600001");
+        let err = IndexPart::from_json_bytes(corrupted.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+
+        // Indices written before the checksum are loaded without verification
+        let mut legacy: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let legacy = legacy.as_object_mut().unwrap();
+        assert!(legacy.remove("checksum").is_some());
+        legacy.insert("version".to_string(), 19.into());
+        let legacy = serde_json::to_vec(&legacy).unwrap();
+        let parsed = IndexPart::from_json_bytes(&legacy).unwrap();
+        assert_eq!(parsed.layer_metadata, part.layer_metadata);
+        assert_eq!(parsed.format_version(), 19);
+    }
+
+    #[test]
+    fn reconcile_residency() {
+        let layer = |lsn: &str| -> LayerName {
+            format!(
+                "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__{lsn}"
+            )
+            .parse()
+            .unwrap()
+        };
+        let resident = layer("0000000001696070-00000000016960E9");
+        let remote_only = layer("00000000016B59D8-00000000016B5A51");
+        let rewritten = layer("00000000016B5A51-00000000016B5A59");
+        let orphan = layer("00000000016B5A61-00000000016B5A71");
+
+        let uploaded_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_721_379_600);
+        let rewritten_at = uploaded_at + Duration::from_nanos(1);
+
+        let mut part = IndexPart::example();
+        for name in [&resident, &remote_only, &rewritten] {
+            part.layer_metadata.insert(
+                name.clone(),
+                LayerFileMetadata {
+                    local_mtime: Some(uploaded_at),
+                    ..LayerFileMetadata::new(8192, Generation::new(1), ShardIndex::unsharded())
+                },
+            );
+        }
+
+        // All local files have the same size: only the modification time tells that `rewritten`
+        // is not the file that was uploaded.
+        let local = |mtime| LocalLayerFile {
+            file_size: 8192,
+            mtime: Some(mtime),
+        };
+        let report = part.reconcile_residency(&HashMap::from([
+            (resident.clone(), local(uploaded_at)),
+            (rewritten.clone(), local(rewritten_at)),
+            (orphan.clone(), local(uploaded_at)),
+        ]));
+        assert_eq!(
+            report,
+            ResidencyReport {
+                resident: vec![resident.clone()],
+                remote_only: vec![remote_only],
+                stale: vec![rewritten.clone()],
+                orphans: vec![orphan],
+            }
+        );
+
+        // Without a recorded modification time, the size has to do
+        part.layer_metadata.get_mut(&rewritten).unwrap().local_mtime = None;
+        let report = part.reconcile_residency(&HashMap::from([
+            (resident.clone(), local(uploaded_at)),
+            (rewritten.clone(), local(rewritten_at)),
+        ]));
+        assert_eq!(report.resident, vec![resident, rewritten]);
+        assert!(report.stale.is_empty());
+    }
+
+    #[test]
+    fn prepare_next_bumps_epoch() {
+        let part = IndexPart::example();
+        assert_eq!(part.epoch(), 0);
+
+        let next = part.prepare_next();
+        assert_eq!(next.epoch(), 1);
+        assert_eq!(next.prepare_next().epoch(), 2);
+        assert_eq!(IndexPart { epoch: 0, ..next }, part);
+    }
+
+    #[test]
+    fn legacy_epoch_defaults_to_zero() {
+        let legacy = r#"{
+            "version": 17,
+            "layer_metadata": {},
+            "disk_consistent_lsn": "0/16960E8",
+            "metadata": {
+                "disk_consistent_lsn": "0/16960E8",
+                "prev_record_lsn": "0/1696070",
+                "ancestor_timeline": null,
+                "ancestor_lsn": "0/0",
+                "latest_gc_cutoff_lsn": "0/1696070",
+                "initdb_lsn": "0/1696070",
+                "pg_version": 14
+            }
+        }"#;
+        let part = IndexPart::from_json_bytes(legacy.as_bytes()).unwrap();
+        assert_eq!(part.epoch(), 0);
+
+        let part = part.prepare_next();
+        let bytes = part.to_json_bytes().unwrap();
+        assert_eq!(IndexPart::from_json_bytes(&bytes).unwrap().epoch(), 1);
+    }
+
+    #[test]
+    fn layer_last_accessed_is_optional() {
+        let legacy: LayerFileMetadata =
+            serde_json::from_str(r#"{ "file_size": 8192, "generation": 3 }"#).unwrap();
+        assert_eq!(legacy.last_accessed, None);
+        assert!(
+            !serde_json::to_string(&legacy)
+                .unwrap()
+                .contains("last_accessed")
+        );
+
+        let parsed: LayerFileMetadata = serde_json::from_str(
+            r#"{ "file_size": 8192, "last_accessed": "2024-07-19T09:00:00.123Z" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.last_accessed,
+            Some("2024-07-19T09:00:00.123Z".parse::<DateTime<Utc>>().unwrap())
+        );
+    }
+
+    #[test]
+    fn layer_local_mtime_round_trips() {
+        let legacy: LayerFileMetadata =
+            serde_json::from_str(r#"{ "file_size": 8192, "generation": 3 }"#).unwrap();
+        assert_eq!(legacy.local_mtime, None);
+        assert!(
+            !serde_json::to_string(&legacy)
+                .unwrap()
+                .contains("local_mtime")
+        );
+
+        // Kept at full precision, so that it compares equal to the local file's
+        let metadata = LayerFileMetadata {
+            local_mtime: Some(SystemTime::UNIX_EPOCH + Duration::new(1_721_379_600, 123_456_789)),
+            ..legacy
+        };
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert!(json.contains(r#""local_mtime":"2024-07-19T09:00:00.123456789Z""#));
+        assert_eq!(
+            serde_json::from_str::<LayerFileMetadata>(&json).unwrap(),
+            metadata
+        );
+    }
+
+    #[test]
+    fn layer_metadata_merge_keeps_newer_access() {
+        let older = "2024-07-19T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let newer = "2024-07-20T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let with_access = |at| LayerFileMetadata {
+            last_accessed: at,
+            ..LayerFileMetadata::new(8192, Generation::new(3), ShardIndex::unsharded())
+        };
+
+        let mut metadata = with_access(Some(newer));
+        metadata.merge(&with_access(Some(older)));
+        assert_eq!(metadata.last_accessed, Some(newer));
+
+        let mut metadata = with_access(Some(older));
+        metadata.merge(&with_access(Some(newer)));
+        assert_eq!(metadata.last_accessed, Some(newer));
+
+        let mut metadata = with_access(None);
+        metadata.merge(&with_access(Some(older)));
+        assert_eq!(metadata.last_accessed, Some(older));
+
+        let mut metadata = with_access(Some(older));
+        metadata.merge(&with_access(None));
+        assert_eq!(metadata.last_accessed, Some(older));
+
+        let modified_at = |secs| LayerFileMetadata {
+            local_mtime: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            ..LayerFileMetadata::new(8192, Generation::new(3), ShardIndex::unsharded())
+        };
+        let mut metadata = modified_at(2);
+        metadata.merge(&modified_at(1));
+        assert_eq!(metadata, modified_at(2));
+        metadata.merge(&modified_at(3));
+        assert_eq!(metadata, modified_at(3));
+    }
+
+    #[test]
+    fn parse_is_observed_in_metrics() {
+        let tenant_shard_id = TenantShardId::unsharded(
+            utils::id::TenantId::from_str("5b3e4fd4b54ab3e9a38c2cbe1d4a18fa").unwrap(),
+        );
+        let timeline_id = TimelineId::from_str("a8a8b4d3e05cc5f6e3f5b37b3b5d2f8e").unwrap();
+        let labels = [
+            tenant_shard_id.tenant_id.to_string(),
+            tenant_shard_id.shard_slug().to_string(),
+            timeline_id.to_string(),
+        ];
+        let labels = labels.each_ref().map(String::as_str);
+
+        let bytes = IndexPart::example().to_json_bytes().unwrap();
+        IndexPart::from_bytes_observed(&bytes, &tenant_shard_id, &timeline_id).unwrap();
+
+        let seconds = metrics::INDEX_PART_PARSE_SECONDS.with_label_values(&labels);
+        assert_eq!(seconds.get_sample_count(), 1);
+        let size = metrics::INDEX_PART_PARSE_BYTES.with_label_values(&labels);
+        assert_eq!(size.get_sample_count(), 1);
+        assert_eq!(size.get_sample_sum(), bytes.len() as f64);
+
+        let syntax_errors = metrics::INDEX_PART_PARSE_ERRORS.with_label_values(&["syntax"]);
+        let before = syntax_errors.get();
+        IndexPart::from_bytes_observed(b"{not json", &tenant_shard_id, &timeline_id).unwrap_err();
+        assert_eq!(syntax_errors.get(), before + 1);
+        assert_eq!(seconds.get_sample_count(), 2);
+    }
+
     fn parse_naive_datetime(s: &str) -> NaiveDateTime {
         chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S.%f").unwrap()
     }
+
+    #[test]
+    fn index_history_retention() {
+        let timeline_path =
+            "tenants/3aa8fcc61f6d357410b7de754b1d9001/timelines/e10a6b2e2ad6b1f6e6b33bc6b2cb9c1a";
+        let key = |name: &str| RemotePath::from_string(&format!("{timeline_path}/{name}")).unwrap();
+        let entry = |name: &str, deleted_at: Option<&str>| IndexHistoryEntry {
+            key: key(name),
+            deleted_at: deleted_at.map(parse_naive_datetime),
+        };
+        let now = parse_naive_datetime("2024-02-01T00:00:00.000000");
+        let grace_period = std::time::Duration::from_secs(7 * 24 * 3600);
+
+        let entries = vec![
+            entry("index_part.json", None),
+            entry("index_part.json-00000001", None),
+            // A tombstone within the grace period
+            entry(
+                "index_part.json-00000002",
+                Some("2024-01-30T00:00:00.000000"),
+            ),
+            // A tombstone past the grace period
+            entry(
+                "index_part.json-00000003",
+                Some("2024-01-01T00:00:00.000000"),
+            ),
+            entry("index_part.json-00000004", None),
+            entry("index_part.json-00000005", None),
+            entry("index_part.json-00000006", None),
+            entry("index_part.json-garbage", None),
+        ];
+
+        let mut retention = select_index_history_retention(entries.clone(), 2, grace_period, now);
+        retention.retain.sort();
+        retention.delete.sort();
+        assert_eq!(
+            retention,
+            IndexHistoryRetention {
+                retain: vec![
+                    key("index_part.json-00000002"),
+                    key("index_part.json-00000005"),
+                    key("index_part.json-00000006"),
+                    key("index_part.json-garbage"),
+                ],
+                delete: vec![
+                    key("index_part.json"),
+                    key("index_part.json-00000001"),
+                    key("index_part.json-00000003"),
+                    key("index_part.json-00000004"),
+                ],
+            }
+        );
+
+        // The current index is retained even when asked to keep nothing.
+        let retention = select_index_history_retention(
+            entries.into_iter().filter(|e| e.deleted_at.is_none()),
+            0,
+            grace_period,
+            now,
+        );
+        assert!(retention.retain.contains(&key("index_part.json-00000006")));
+        assert!(!retention.delete.contains(&key("index_part.json-00000006")));
+    }
 }
@@ -0,0 +1,284 @@
+//! Encoding of [`IndexPart`] objects in remote storage.
+//!
+//! By default, index parts are stored as bare JSON. Deployments that need e.g. encryption at rest
+//! can install another [`IndexCodec`] at startup. Objects written by such a codec start with the
+//! codec's magic, so that readers pick the right codec for each object: objects written before
+//! and after switching codecs can then be read side by side during a rollout.
+
+use std::sync::{Arc, OnceLock};
+
+use super::index::IndexPart;
+
+/// Length of [`IndexCodec::magic`].
+pub const MAGIC_LEN: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IndexPartError {
+    #[error("index part is not valid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("index part is written with an unknown codec {0:?}")]
+    UnknownCodec([u8; MAGIC_LEN]),
+    #[error("index part is truncated")]
+    Truncated,
+    #[error("index part codec {codec}: {source:#}")]
+    Codec {
+        codec: &'static str,
+        source: anyhow::Error,
+    },
+}
+
+/// Converts an [`IndexPart`] from and to the bytes stored in remote storage.
+pub trait IndexCodec: Send + Sync {
+    /// Human-readable name, for errors and logs.
+    fn name(&self) -> &'static str;
+
+    /// Written in front of every object encoded by this codec. `None` only for the JSON codec,
+    /// whose objects are unprefixed.
+    fn magic(&self) -> Option<[u8; MAGIC_LEN]>;
+
+    /// Encodes the index part, without the magic.
+    fn encode(&self, index_part: &IndexPart) -> Result<Vec<u8>, IndexPartError>;
+
+    /// Decodes what [`Self::encode`] returned.
+    fn decode(&self, bytes: &[u8]) -> Result<IndexPart, IndexPartError>;
+}
+
+/// The default codec: bare JSON, which is what all index parts were written as historically.
+pub struct JsonIndexCodec;
+
+impl IndexCodec for JsonIndexCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn magic(&self) -> Option<[u8; MAGIC_LEN]> {
+        None
+    }
+
+    fn encode(&self, index_part: &IndexPart) -> Result<Vec<u8>, IndexPartError> {
+        Ok(index_part.to_json_bytes()?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<IndexPart, IndexPartError> {
+        Ok(IndexPart::from_json_bytes(bytes)?)
+    }
+}
+
+/// The codec new index parts are written with, and the codecs existing ones may be read with.
+pub struct IndexCodecs {
+    write: Arc<dyn IndexCodec>,
+    read: Vec<Arc<dyn IndexCodec>>,
+}
+
+impl Default for IndexCodecs {
+    fn default() -> Self {
+        Self::new(Arc::new(JsonIndexCodec), Vec::new())
+    }
+}
+
+impl IndexCodecs {
+    /// Reading JSON is always supported, as is reading with the `write` codec.
+    ///
+    /// Panics if a magic could be mistaken for the start of a JSON object.
+    pub fn new(write: Arc<dyn IndexCodec>, read: Vec<Arc<dyn IndexCodec>>) -> Self {
+        let mut all: Vec<Arc<dyn IndexCodec>> = vec![write.clone()];
+        all.extend(read);
+        for codec in &all {
+            if let Some(magic) = codec.magic() {
+                assert!(
+                    magic[0] != b'{' && !magic[0].is_ascii_whitespace(),
+                    "magic of index codec {} is ambiguous with json",
+                    codec.name()
+                );
+            }
+        }
+        Self { write, read: all }
+    }
+
+    /// The codecs used by this process: the ones passed to [`install`], or JSON only.
+    pub fn installed() -> &'static IndexCodecs {
+        INSTALLED.get_or_init(IndexCodecs::default)
+    }
+
+    pub fn encode(&self, index_part: &IndexPart) -> Result<Vec<u8>, IndexPartError> {
+        let encoded = self.write.encode(index_part)?;
+        let Some(magic) = self.write.magic() else {
+            return Ok(encoded);
+        };
+        let mut bytes = Vec::with_capacity(MAGIC_LEN + encoded.len());
+        bytes.extend_from_slice(&magic);
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<IndexPart, IndexPartError> {
+        let is_json = bytes
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_none_or(|b| *b == b'{');
+        if is_json {
+            return JsonIndexCodec.decode(bytes);
+        }
+
+        let (magic, payload) = bytes
+            .split_first_chunk::<MAGIC_LEN>()
+            .ok_or(IndexPartError::Truncated)?;
+        let codec = self
+            .read
+            .iter()
+            .find(|codec| codec.magic().as_ref() == Some(magic))
+            .ok_or(IndexPartError::UnknownCodec(*magic))?;
+        codec.decode(payload)
+    }
+}
+
+static INSTALLED: OnceLock<IndexCodecs> = OnceLock::new();
+
+/// Sets the codecs of this process. Must be called before any index part is read or written,
+/// and at most once.
+pub fn install(codecs: IndexCodecs) -> anyhow::Result<()> {
+    INSTALLED
+        .set(codecs)
+        .map_err(|_| anyhow::anyhow!("index codecs are already installed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for an AEAD: "encrypts" with a key-dependent XOR keystream seeded by a nonce
+    /// that is prepended to the ciphertext, and appends a tag that covers nonce and ciphertext.
+    struct StubAeadCodec {
+        key: u8,
+    }
+
+    impl StubAeadCodec {
+        const NONCE_LEN: usize = 8;
+
+        fn keystream(&self, nonce: &[u8]) -> impl Iterator<Item = u8> {
+            let seed = nonce.iter().fold(self.key, |acc, b| acc.rotate_left(3) ^ b);
+            (0u8..=255).cycle().map(move |i| i.wrapping_mul(31) ^ seed)
+        }
+
+        fn tag(&self, data: &[u8]) -> [u8; 4] {
+            let tag = data
+                .iter()
+                .fold(0x811c_9dc5_u32 ^ u32::from(self.key), |acc, b| {
+                    (acc ^ u32::from(*b)).wrapping_mul(0x0100_0193)
+                });
+            tag.to_be_bytes()
+        }
+    }
+
+    impl IndexCodec for StubAeadCodec {
+        fn name(&self) -> &'static str {
+            "stub-aead"
+        }
+
+        fn magic(&self) -> Option<[u8; MAGIC_LEN]> {
+            Some(*b"NIX1")
+        }
+
+        fn encode(&self, index_part: &IndexPart) -> Result<Vec<u8>, IndexPartError> {
+            let plaintext = index_part.to_json_bytes()?;
+            let nonce: [u8; Self::NONCE_LEN] = rand::random();
+            let mut sealed = nonce.to_vec();
+            sealed.extend(
+                plaintext
+                    .iter()
+                    .zip(self.keystream(&nonce))
+                    .map(|(p, k)| p ^ k),
+            );
+            let tag = self.tag(&sealed);
+            sealed.extend_from_slice(&tag);
+            Ok(sealed)
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<IndexPart, IndexPartError> {
+            let codec = self.name();
+            let (sealed, tag) = bytes
+                .split_last_chunk::<4>()
+                .ok_or(IndexPartError::Truncated)?;
+            if self.tag(sealed) != *tag {
+                return Err(IndexPartError::Codec {
+                    codec,
+                    source: anyhow::anyhow!("authentication failed"),
+                });
+            }
+            let (nonce, ciphertext) = sealed
+                .split_at_checked(Self::NONCE_LEN)
+                .ok_or(IndexPartError::Truncated)?;
+            let plaintext: Vec<u8> = ciphertext
+                .iter()
+                .zip(self.keystream(nonce))
+                .map(|(c, k)| c ^ k)
+                .collect();
+            Ok(IndexPart::from_json_bytes(&plaintext)?)
+        }
+    }
+
+    fn stub_aead_codecs() -> IndexCodecs {
+        IndexCodecs::new(Arc::new(StubAeadCodec { key: 42 }), Vec::new())
+    }
+
+    #[test]
+    fn json_is_unprefixed() {
+        let index_part = IndexPart::example();
+        let codecs = IndexCodecs::default();
+        let bytes = codecs.encode(&index_part).unwrap();
+        assert_eq!(bytes, index_part.to_json_bytes().unwrap());
+        assert_eq!(codecs.decode(&bytes).unwrap(), index_part);
+    }
+
+    #[test]
+    fn stub_aead_round_trip() {
+        let index_part = IndexPart::example();
+        let codecs = stub_aead_codecs();
+
+        let bytes = codecs.encode(&index_part).unwrap();
+        assert!(bytes.starts_with(b"NIX1"));
+        assert!(
+            !bytes
+                .windows(b"layer_metadata".len())
+                .any(|w| w == b"layer_metadata")
+        );
+        assert_eq!(codecs.decode(&bytes).unwrap(), index_part);
+
+        // Objects written before the switch to the new codec can still be read
+        let json = index_part.to_json_bytes().unwrap();
+        assert_eq!(codecs.decode(&json).unwrap(), index_part);
+
+        // ... but a reader without the codec can't read the new objects
+        assert!(matches!(
+            IndexCodecs::default().decode(&bytes),
+            Err(IndexPartError::UnknownCodec(magic)) if &magic == b"NIX1"
+        ));
+    }
+
+    #[test]
+    fn stub_aead_tampering_fails_decode() {
+        let codecs = stub_aead_codecs();
+        let bytes = codecs.encode(&IndexPart::example()).unwrap();
+
+        for i in [
+            MAGIC_LEN,
+            MAGIC_LEN + StubAeadCodec::NONCE_LEN,
+            bytes.len() - 1,
+        ] {
+            let mut tampered = bytes.clone();
+            tampered[i] ^= 0x01;
+            assert!(matches!(
+                codecs.decode(&tampered),
+                Err(IndexPartError::Codec {
+                    codec: "stub-aead",
+                    ..
+                })
+            ));
+        }
+
+        assert!(matches!(
+            codecs.decode(&bytes[..MAGIC_LEN + 2]),
+            Err(IndexPartError::Truncated)
+        ));
+    }
+}
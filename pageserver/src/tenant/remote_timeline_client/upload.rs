@@ -18,6 +18,7 @@
 
 use super::Generation;
 use super::index::IndexPart;
+use super::index_codec::IndexCodecs;
 use super::manifest::TenantManifest;
 use crate::tenant::remote_timeline_client::{
     remote_index_path, remote_initdb_archive_path, remote_initdb_preserved_archive_path,
@@ -45,7 +46,7 @@ pub(crate) async fn upload_index_part(
     index_part.validate().map_err(|e| anyhow::anyhow!(e))?;
 
     // FIXME: this error comes too late
-    let serialized = index_part.to_json_bytes()?;
+    let serialized = IndexCodecs::installed().encode(index_part)?;
     let serialized = Bytes::from(serialized);
 
     let index_part_size = serialized.len();
@@ -1182,7 +1182,11 @@ async fn layer_action(
                 );
                 return LayerAction::Download;
             }
-            if on_disk.metadata != layer.metadata || on_disk.access_time != layer.access_time {
+            // The last_accessed time in the metadata is tracked by the attached location, and
+            // only the heatmap's access time matters here.
+            if !on_disk.metadata.is_same_file(&layer.metadata)
+                || on_disk.access_time != layer.access_time
+            {
                 // We already have this layer on disk.  Update its access time.
                 tracing::debug!(
                     "Access time updated for layer {}: {} -> {}",
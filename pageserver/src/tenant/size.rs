@@ -74,6 +74,22 @@ fn from(value: GcError) -> Self {
     }
 }
 
+impl CalculateSyntheticSizeError {
+    /// Errors that stem from reading data, e.g. a remote storage hiccup, which may succeed if the
+    /// calculation is simply retried. Logic errors and cancellation are not transient.
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            CalculateSyntheticSizeError::LogicalSize { error, .. } => {
+                matches!(error, CalculateLogicalSizeError::PageRead(_))
+            }
+            CalculateSyntheticSizeError::GcInfo(e) => {
+                matches!(e, GcError::Remote(_) | GcError::GcCutoffs(_))
+            }
+            CalculateSyntheticSizeError::Fatal(_) | CalculateSyntheticSizeError::Cancelled => false,
+        }
+    }
+}
+
 impl SegmentMeta {
     fn size_needed(&self) -> bool {
         match self.kind {
@@ -1098,6 +1098,12 @@ pub(crate) fn latest_activity(&self) -> SystemTime {
         }
     }
 
+    /// Get the latest access timestamp, if the layer was read since it was constructed. Unlike
+    /// [`Self::latest_activity`], changes in residence such as evictions and downloads don't count.
+    pub(crate) fn latest_access(&self) -> Option<SystemTime> {
+        self.read_low_res_timestamp(Self::ATIME_SHIFT)
+    }
+
     /// Whether this layer has been accessed (excluding in [`AccessStatsBehavior::Skip`]).
     ///
     /// This indicates whether the layer has been used for some purpose that would motivate
@@ -438,6 +438,10 @@ pub(crate) fn latest_activity(&self) -> SystemTime {
         self.0.access_stats.latest_activity()
     }
 
+    pub(crate) fn latest_access(&self) -> Option<SystemTime> {
+        self.0.access_stats.latest_access()
+    }
+
     pub(crate) fn visibility(&self) -> LayerVisibilityHint {
         self.0.access_stats.visibility()
     }
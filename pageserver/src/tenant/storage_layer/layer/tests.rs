@@ -1077,10 +1077,17 @@ fn access_stats() {
     let rtime = UNIX_EPOCH + Duration::from_secs(2000000000);
     access_stats.record_residence_event_at(rtime);
     assert_eq!(access_stats.latest_activity(), lowres_time(rtime));
+    // A residence change is not an access
+    assert_eq!(access_stats.latest_access(), None);
 
     let atime = UNIX_EPOCH + Duration::from_secs(2100000000);
     access_stats.record_access_at(atime);
     assert_eq!(access_stats.latest_activity(), lowres_time(atime));
+    assert_eq!(access_stats.latest_access(), Some(lowres_time(atime)));
+
+    // Later residence changes, e.g. an eviction, don't move the access time
+    access_stats.record_residence_event_at(atime + Duration::from_secs(100));
+    assert_eq!(access_stats.latest_access(), Some(lowres_time(atime)));
 
     // Setting visibility doesn't clobber access time
     access_stats.set_visibility(LayerVisibilityHint::Covered);
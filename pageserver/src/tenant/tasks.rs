@@ -560,6 +560,7 @@ impl Iteration {
     pub(crate) async fn run<F: Future<Output = O>, O>(self, fut: F) -> IterationResult<O> {
         let mut fut = pin!(fut);
 
+        task_mgr::set_current_task_phase("iterating");
         // Wrap `fut` into a future that logs a message every `period` so that we get a
         // very obvious breadcrumb in the logs _while_ a slow iteration is happening.
         let output = loop {
@@ -568,6 +569,7 @@ pub(crate) async fn run<F: Future<Output = O>, O>(self, fut: F) -> IterationResu
                 Err(_) => info!("still running"),
             }
         };
+        task_mgr::set_current_task_phase("waiting");
         let elapsed = self.started_at.elapsed();
         warn_when_period_overrun(elapsed, self.period, self.kind);
 
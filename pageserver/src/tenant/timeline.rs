@@ -102,6 +102,7 @@
     DownloadBehavior, PerfInstrumentFutureExt, RequestContext, RequestContextBuilder,
 };
 use crate::disk_usage_eviction_task::{DiskUsageEvictionInfo, EvictionCandidate, finite_f32};
+use crate::jobs::{JOBS, Job};
 use crate::keyspace::{KeyPartitioning, KeySpace};
 use crate::l0_flush::{self, L0FlushGlobalState};
 use crate::metrics::{
@@ -916,6 +917,13 @@ pub(crate) struct MarkInvisibleRequest {
     pub is_visible: Option<bool>,
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct GcOptions {
+    /// Count the layers GC would remove and compute the cutoff it would move to, without doing
+    /// either.
+    pub dry_run: bool,
+}
+
 #[derive(Debug, Clone, Default)]
 pub(crate) struct CompactOptions {
     pub flags: EnumSet<CompactFlags>,
@@ -2504,10 +2512,19 @@ pub(crate) fn get_lazy_slru_download(&self) -> bool {
 
     /// Checks if a get page request should get perf tracing
     ///
-    /// The configuration priority is: tenant config override, default tenant config,
+    /// `trace_read_requests` samples every request. Otherwise, the sampling ratio's
+    /// configuration priority is: tenant config override, default tenant config,
     /// pageserver config.
     pub(crate) fn is_get_page_request_sampled(&self) -> bool {
         let tenant_conf = self.tenant_conf.load();
+        if tenant_conf
+            .tenant_conf
+            .trace_read_requests
+            .unwrap_or(self.conf.default_tenant_conf.trace_read_requests)
+        {
+            return true;
+        }
+
         let ratio = tenant_conf
             .tenant_conf
             .sampling_ratio
@@ -3236,6 +3253,20 @@ pub(super) async fn load_layer_map(
                     );
                 }
 
+                let residency = index_part.reconcile_residency(
+                    &discovered_layers
+                        .iter()
+                        .map(|(name, local)| (name.clone(), local.as_local_layer_file()))
+                        .collect(),
+                );
+                tracing::info!(
+                    resident = residency.resident.len(),
+                    remote_only = residency.remote_only.len(),
+                    stale = residency.stale.len(),
+                    orphans = residency.orphans.len(),
+                    "reconciled local layers with index"
+                );
+
                 let decided = init::reconcile(discovered_layers, &index_part, disk_consistent_lsn);
 
                 let mut loaded_layers = Vec::new();
@@ -3790,7 +3821,7 @@ pub(crate) fn update_directory_entries_count(&self, kind: DirectoryKind, count:
         }
     }
 
-    async fn find_layer(
+    pub(crate) async fn find_layer(
         &self,
         layer_name: &LayerName,
     ) -> Result<Option<Layer>, layer_manager::Shutdown> {
@@ -3803,6 +3834,26 @@ async fn find_layer(
         Ok(layer)
     }
 
+    /// Historic layers overlapping both the given key and LSN ranges.
+    pub(crate) async fn find_layers_overlapping(
+        &self,
+        key_range: &Range<Key>,
+        lsn_range: &Range<Lsn>,
+    ) -> Result<Vec<Layer>, layer_manager::Shutdown> {
+        fn overlaps<T: Ord>(a: &Range<T>, b: &Range<T>) -> bool {
+            a.start < b.end && b.start < a.end
+        }
+
+        let guard = self.layers.read().await;
+        let layers = guard
+            .layer_map()?
+            .iter_historic_layers()
+            .filter(|l| overlaps(&l.key_range, key_range) && overlaps(&l.lsn_range, lsn_range))
+            .map(|found| guard.get_from_desc(&found))
+            .collect();
+        Ok(layers)
+    }
+
     pub(super) fn should_keep_previous_heatmap(&self, new_heatmap_end_lsn: Lsn) -> bool {
         let crnt = self.previous_heatmap.load();
         match crnt.as_deref() {
@@ -6274,7 +6325,13 @@ pub(super) async fn find_gc_cutoffs(
     /// Currently, we don't make any attempt at removing unneeded page versions
     /// within a layer file. We can only remove the whole file if it's fully
     /// obsolete.
-    pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
+    ///
+    /// `cutoffs` replaces the cutoffs of [`Self::gc_info`], for dry runs that must not store theirs.
+    pub(super) async fn gc(
+        &self,
+        options: GcOptions,
+        cutoffs: Option<GcCutoffs>,
+    ) -> Result<GcResult, GcError> {
         // this is most likely the background tasks, but it might be the spawned task from
         // immediate_gc
         let _g = tokio::select! {
@@ -6292,9 +6349,10 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
 
         let (space_cutoff, time_cutoff, retain_lsns, max_lsn_with_valid_lease) = {
             let gc_info = self.gc_info.read().unwrap();
+            let cutoffs = cutoffs.unwrap_or_else(|| gc_info.cutoffs.clone());
 
-            let space_cutoff = min(gc_info.cutoffs.space, self.get_disk_consistent_lsn());
-            let time_cutoff = gc_info.cutoffs.time;
+            let space_cutoff = min(cutoffs.space, self.get_disk_consistent_lsn());
+            let time_cutoff = cutoffs.time;
             let retain_lsns = gc_info
                 .retain_lsns
                 .iter()
@@ -6303,9 +6361,15 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
 
             // Gets the maximum LSN that holds the valid lease.
             //
-            // Caveat: `refresh_gc_info` is in charged of updating the lease map.
-            // Here, we do not check for stale leases again.
-            let max_lsn_with_valid_lease = gc_info.leases.last_key_value().map(|(lsn, _)| *lsn);
+            // Caveat: `refresh_gc_info` is in charged of updating the lease map, which a dry run
+            // skips: only leases that are still valid count.
+            let now = SystemTime::now();
+            let max_lsn_with_valid_lease = gc_info
+                .leases
+                .iter()
+                .rev()
+                .find(|(_, lease)| !lease.is_expired(&now))
+                .map(|(lsn, _)| *lsn);
 
             (
                 space_cutoff,
@@ -6337,10 +6401,12 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
         // Reset standby horizon to ignore it if it is not updated till next GC.
         // It is an easy way to unset it when standby disappears without adding
         // more conf options.
-        self.standby_horizon.store(Lsn::INVALID);
-        self.metrics
-            .standby_horizon_gauge
-            .set(Lsn::INVALID.0 as i64);
+        if !options.dry_run {
+            self.standby_horizon.store(Lsn::INVALID);
+            self.metrics
+                .standby_horizon_gauge
+                .set(Lsn::INVALID.0 as i64);
+        }
 
         let res = self
             .gc_timeline(
@@ -6349,14 +6415,17 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
                 retain_lsns,
                 max_lsn_with_valid_lease,
                 new_gc_cutoff,
+                options,
             )
             .instrument(
-                info_span!("gc_timeline", timeline_id = %self.timeline_id, cutoff = %new_gc_cutoff),
+                info_span!("gc_timeline", timeline_id = %self.timeline_id, cutoff = %new_gc_cutoff, dry_run = options.dry_run),
             )
             .await?;
 
-        // only record successes
-        timer.stop_and_record();
+        // only record successes, and only of real GC
+        if !options.dry_run {
+            timer.stop_and_record();
+        }
 
         Ok(res)
     }
@@ -6368,11 +6437,15 @@ async fn gc_timeline(
         retain_lsns: Vec<Lsn>,
         max_lsn_with_valid_lease: Option<Lsn>,
         new_gc_cutoff: Lsn,
+        options: GcOptions,
     ) -> Result<GcResult, GcError> {
         // FIXME: if there is an ongoing detach_from_ancestor, we should just skip gc
 
         let now = SystemTime::now();
-        let mut result: GcResult = GcResult::default();
+        let mut result = GcResult {
+            dry_run: options.dry_run,
+            ..Default::default()
+        };
 
         // Nothing to GC. Return early.
         let latest_gc_cutoff = *self.get_applied_gc_cutoff_lsn();
@@ -6383,25 +6456,29 @@ async fn gc_timeline(
             return Ok(result);
         }
 
+        result.new_gc_cutoff_lsn = Some(new_gc_cutoff);
+
         // We need to ensure that no one tries to read page versions or create
         // branches at a point before latest_gc_cutoff_lsn. See branch_timeline()
         // for details. This will block until the old value is no longer in use.
         //
         // The GC cutoff should only ever move forwards.
-        let waitlist = {
-            let write_guard = self.applied_gc_cutoff_lsn.lock_for_write();
-            if *write_guard > new_gc_cutoff {
-                return Err(GcError::BadLsn {
-                    why: format!(
-                        "Cannot move GC cutoff LSN backwards (was {}, new {})",
-                        *write_guard, new_gc_cutoff
-                    ),
-                });
-            }
+        if !options.dry_run {
+            let waitlist = {
+                let write_guard = self.applied_gc_cutoff_lsn.lock_for_write();
+                if *write_guard > new_gc_cutoff {
+                    return Err(GcError::BadLsn {
+                        why: format!(
+                            "Cannot move GC cutoff LSN backwards (was {}, new {})",
+                            *write_guard, new_gc_cutoff
+                        ),
+                    });
+                }
 
-            write_guard.store_and_unlock(new_gc_cutoff)
-        };
-        waitlist.wait().await;
+                write_guard.store_and_unlock(new_gc_cutoff)
+            };
+            waitlist.wait().await;
+        }
 
         info!("GC starting");
 
@@ -6517,6 +6594,16 @@ async fn gc_timeline(
             layers_to_remove.push(l);
         }
 
+        if options.dry_run {
+            result.layers_removed = layers_to_remove.len() as u64;
+            info!(
+                "GC dry run would remove {} layers, cutoff {}",
+                result.layers_removed, new_gc_cutoff
+            );
+            result.elapsed = now.elapsed().unwrap_or(Duration::ZERO);
+            return Ok(result);
+        }
+
         if !layers_to_remove.is_empty() {
             // Persist the new GC cutoff value before we actually remove anything.
             // This unconditionally schedules also an index_part.json update, even though, we will
@@ -6669,6 +6756,9 @@ pub(crate) async fn spawn_download_all_remote_layers(
             }
         }
 
+        let job = JOBS.register("download_remote_layers", "scheduled");
+        let job_id = job.id();
+
         let self_clone = Arc::clone(&self);
         let task_ctx = ctx.detached_child(
             TaskKind::DownloadAllRemoteLayers,
@@ -6681,7 +6771,7 @@ pub(crate) async fn spawn_download_all_remote_layers(
             Some(self.timeline_id),
             "download all remote layers task",
             async move {
-                self_clone.download_all_remote_layers(request, &task_ctx).await;
+                self_clone.download_all_remote_layers(request, job, &task_ctx).await;
                 let mut status_guard = self_clone.download_all_remote_layers_task_info.write().unwrap();
                  match &mut *status_guard {
                     None => {
@@ -6703,6 +6793,7 @@ pub(crate) async fn spawn_download_all_remote_layers(
 
         let initial_info = DownloadRemoteLayersTaskInfo {
             task_id: format!("{task_id}"),
+            job_id,
             state: DownloadRemoteLayersTaskState::Running,
             total_layer_count: 0,
             successful_download_count: 0,
@@ -6716,10 +6807,12 @@ pub(crate) async fn spawn_download_all_remote_layers(
     async fn download_all_remote_layers(
         self: &Arc<Self>,
         request: DownloadRemoteLayersTaskSpawnRequest,
+        job: Job,
         ctx: &RequestContext,
     ) {
         use pageserver_api::models::DownloadRemoteLayersTaskState;
 
+        job.enter_phase("listing");
         let remaining = {
             let guard = self.layers.read().await;
             let Ok(lm) = guard.layer_map() else {
@@ -6756,6 +6849,7 @@ macro_rules! lock_status {
             st.total_layer_count = total_layer_count as u64;
         }
 
+        job.enter_phase("downloading");
         let mut remaining = remaining.into_iter();
         let mut have_remaining = true;
         let mut js = tokio::task::JoinSet::new();
@@ -6784,6 +6878,7 @@ macro_rules! lock_status {
             }
 
             while let Some(res) = js.join_next().await {
+                job.add_layers_processed(1);
                 match res {
                     Ok((_, Ok(_))) => {
                         lock_status!(st);
@@ -6812,6 +6907,7 @@ macro_rules! lock_status {
             lock_status!(st);
             st.state = DownloadRemoteLayersTaskState::Completed;
         }
+        job.complete();
     }
 
     pub(crate) fn get_download_all_remote_layers_task_info(
@@ -426,6 +426,10 @@ async fn background(
     pub(crate) fn is_not_started(&self) -> bool {
         matches!(self, Self::NotStarted)
     }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(self, Self::Finished)
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
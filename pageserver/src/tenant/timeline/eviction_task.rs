@@ -30,7 +30,7 @@
 use crate::pgdatadir_mapping::CollectKeySpaceError;
 use crate::task_mgr::{self, BACKGROUND_RUNTIME, TaskKind};
 use crate::tenant::size::CalculateSyntheticSizeError;
-use crate::tenant::storage_layer::LayerVisibilityHint;
+use crate::tenant::storage_layer::{AsLayerDesc, LayerVisibilityHint};
 use crate::tenant::tasks::{BackgroundLoopKind, BackgroundLoopSemaphorePermit, sleep_random};
 use crate::tenant::timeline::EvictionError;
 use crate::tenant::{LogicalSizeCalculationCause, TenantShard};
@@ -210,6 +210,16 @@ struct EvictionStats {
         {
             let guard = self.layers.read().await;
 
+            // Carry the observed access times into the next index upload, so that tooling working
+            // from the remote index can tell cold layers from hot ones. Only reads count: residence
+            // changes, like the evictions below, are not accesses.
+            let accesses = guard
+                .likely_resident_layers()
+                .filter_map(|layer| Some((layer.layer_desc().layer_name(), layer.latest_access()?)))
+                .collect::<Vec<_>>();
+            // Only fails if the upload queue is shutting down, in which case there's no next upload.
+            let _ = self.remote_client.record_layer_accesses(accesses);
+
             guard
                 .likely_resident_layers()
                 .filter(|layer| {
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, hash_map};
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use anyhow::Context;
 use camino::{Utf8Path, Utf8PathBuf};
@@ -7,7 +8,7 @@
 
 use crate::is_temporary;
 use crate::tenant::ephemeral_file::is_ephemeral_file;
-use crate::tenant::remote_timeline_client::index::{IndexPart, LayerFileMetadata};
+use crate::tenant::remote_timeline_client::index::{IndexPart, LayerFileMetadata, LocalLayerFile};
 use crate::tenant::remote_timeline_client::{self};
 use crate::tenant::storage_layer::LayerName;
 
@@ -37,10 +38,13 @@ pub(super) fn scan_timeline_dir(path: &Utf8Path) -> anyhow::Result<Vec<Discovere
 
         let discovered = match LayerName::from_str(&file_name) {
             Ok(file_name) => {
-                let file_size = direntry.metadata()?.len();
+                let metadata = direntry.metadata()?;
                 Discovered::Layer(
                     file_name,
-                    LocalLayerFileMetadata::new(direntry.path().to_owned(), file_size),
+                    LocalLayerFileMetadata {
+                        mtime: metadata.modified().ok(),
+                        ..LocalLayerFileMetadata::new(direntry.path().to_owned(), metadata.len())
+                    },
                 )
             }
             Err(_) => {
@@ -71,6 +75,8 @@ pub(super) fn scan_timeline_dir(path: &Utf8Path) -> anyhow::Result<Vec<Discovere
 pub(super) struct LocalLayerFileMetadata {
     pub(super) file_size: u64,
     pub(super) local_path: Utf8PathBuf,
+    /// `None` if the platform or filesystem doesn't report it.
+    pub(super) mtime: Option<SystemTime>,
 }
 
 impl LocalLayerFileMetadata {
@@ -78,6 +84,14 @@ pub fn new(local_path: Utf8PathBuf, file_size: u64) -> Self {
         Self {
             local_path,
             file_size,
+            mtime: None,
+        }
+    }
+
+    pub(super) fn as_local_layer_file(&self) -> LocalLayerFile {
+        LocalLayerFile {
+            file_size: self.file_size,
+            mtime: self.mtime,
         }
     }
 }
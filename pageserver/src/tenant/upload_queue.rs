@@ -641,6 +641,8 @@ fn make_layer_with_size(timeline: &Arc<Timeline>, name: &str, size: usize) -> Re
             generation: timeline.generation,
             shard: timeline.get_shard_index(),
             file_size: size as u64,
+            last_accessed: None,
+            local_mtime: None,
         };
         make_layer_with_metadata(timeline, name, metadata)
     }
@@ -1378,6 +1380,8 @@ fn make_ops(layer: ResidentLayer) -> Vec<UploadOp> {
                 shard,
                 generation: Generation::Valid(generation),
                 file_size: 0,
+                last_accessed: None,
+                local_mtime: None,
             };
             make_layer_with_metadata(&tli, name, metadata)
         };
@@ -1,11 +1,14 @@
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::BuildHasher;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use std::{array, env, fmt, io};
 
-use chrono::{DateTime, Utc};
-use indexmap::IndexSet;
+use chrono::{DateTime, FixedOffset, Utc};
+use indexmap::{IndexMap, IndexSet};
 use opentelemetry::trace::TraceContextExt;
 use scopeguard::defer;
 use serde::ser::{SerializeMap, Serializer};
@@ -18,47 +21,157 @@
 use tracing_subscriber::fmt::{FormatEvent, FormatFields};
 use tracing_subscriber::layer::{Context, Layer};
 use tracing_subscriber::prelude::*;
-use tracing_subscriber::registry::{LookupSpan, SpanRef};
+use tracing_subscriber::registry::{LookupSpan, Registry, SpanRef};
+use tracing_subscriber::reload;
 use try_lock::TryLock;
 
+use crate::metrics::{LogLevel, Metrics};
+
 /// Initialize logging and OpenTelemetry tracing and exporter.
 ///
-/// Logging can be configured using `RUST_LOG` environment variable.
+/// Logging can be configured using `RUST_LOG` environment variable, and reconfigured at runtime
+/// with [`LoggingGuard::reload_filter`]. The output format is selected with `LOGFMT`: `json`
+/// (default), `msgpack`, `logfmt` or `text`. All formats but `text` are configured with:
+///
+/// - `LOG_EXTRACT_FIELDS`: span fields extracted into top-level fields like `request_id`, e.g.
+///   `LOG_EXTRACT_FIELDS=tenant_id,endpoint_id`.
+/// - `LOGFMT_REDACT_FIELDS`: fields whose values are replaced with `[redacted]`, e.g.
+///   `LOGFMT_REDACT_FIELDS=password,token`.
+/// - `LOGFMT_REDACT_KEEP_CHARS`: number of chars of redacted values kept at both ends.
+/// - `LOGFMT_FLOAT_DECIMALS`: decimals floating point field values are rounded to, e.g.
+///   `LOGFMT_FLOAT_DECIMALS=3`.
+/// - `LOGFMT_SAMPLE`: log only 1 in N events of a target, e.g.
+///   `LOGFMT_SAMPLE=proxy::serverless=10`. Errors are always logged.
+/// - `LOGFMT_LABELS`: constant labels for log routing added to every line, e.g.
+///   `LOGFMT_LABELS=region=eu-central-1,deployment=prod`.
+/// - `LOGFMT_FALLBACK_FILE`: file logs are appended to if writing to stderr keeps failing.
+///
+/// The JSON and MessagePack formats additionally with:
+///
+/// - `LOGFMT_OMIT_FIELDS`: optional fields left out, e.g. `LOGFMT_OMIT_FIELDS=src,thread_id`.
+/// - `LOGFMT_MAX_LINE_LEN`: lines longer than this, 64KiB by default, have their field values
+///   truncated.
+/// - `LOGFMT_SPAN_CLOSE`: with `true`, closing spans are logged with the time they were open.
+/// - `LOGFMT_ERROR_CHAINS`: with `true`, error fields are logged with their sources, as array.
+/// - `LOGFMT_SPANS`: `array` logs spans as an array, and `flat` as top-level
+///   `span.<name>.<field>` fields.
+/// - `LOGFMT_TIMESTAMP`: timestamps are in UTC by default, and can be switched to a fixed offset,
+///   e.g. `LOGFMT_TIMESTAMP=+02:00`, or to milliseconds since the epoch with
+///   `LOGFMT_TIMESTAMP=epoch_millis`.
 ///
 /// OpenTelemetry is configured with OTLP/HTTP exporter. It picks up
 /// configuration from environment variables. For example, to change the
-/// destination, set `OTEL_EXPORTER_OTLP_ENDPOINT=http://jaeger:4318`.
+/// destination, set `OTEL_EXPORTER_OTLP_ENDPOINT=http://jaeger:4318`, or to export only a tenth
+/// of the traces, `OTEL_TRACES_SAMPLER=parentbased_traceidratio` and `OTEL_TRACES_SAMPLER_ARG=0.1`.
 /// See <https://opentelemetry.io/docs/reference/specification/sdk-environment-variables>
+///
+/// Dropping the returned guard waits for pending traces to be exported for up to
+/// `LOG_SHUTDOWN_TIMEOUT`, e.g. `LOG_SHUTDOWN_TIMEOUT=10s`, 5s by default.
+///
+/// Without an exporter, JSON log lines use the `request_id` span field as `trace_id` instead.
+///
+/// See [`init_with_config`] to configure logging programmatically instead.
 pub async fn init() -> anyhow::Result<LoggingGuard> {
-    let logfmt = LogFormat::from_env()?;
-
-    let env_filter = EnvFilter::builder()
-        .with_default_directive(LevelFilter::INFO.into())
-        .from_env_lossy()
-        .add_directive(
-            "aws_config=info"
-                .parse()
-                .expect("this should be a valid filter directive"),
-        )
-        .add_directive(
-            "azure_core::policies::transport=off"
-                .parse()
-                .expect("this should be a valid filter directive"),
-        );
+    init_with_config(LoggingConfig::from_env()?).await
+}
 
-    let otlp_layer =
-        tracing_utils::init_tracing("proxy", tracing_utils::ExportConfig::default()).await;
+/// Like [`init`], but with the log options of `config` rather than those from the environment.
+/// `RUST_LOG` and the OpenTelemetry env vars are still read.
+pub async fn init_with_config(config: LoggingConfig) -> anyhow::Result<LoggingGuard> {
+    let LoggingConfig {
+        format: logfmt,
+        extract_fields,
+        writer,
+        log_fields,
+        float_decimals,
+        spans_format,
+        max_line_len,
+        redact_fields,
+        sample_rates,
+        span_close_events,
+        error_chains,
+        timestamp_format,
+        labels,
+        shutdown_timeout,
+    } = config;
+    let fallback_file = match writer {
+        LogWriter::Stderr => None,
+        LogWriter::StderrWithFallbackFile(path) => Some(FileWriter::open(&path)?),
+    };
 
-    let json_log_layer = if logfmt == LogFormat::Json {
-        Some(JsonLoggingLayer::new(
-            RealClock,
-            StderrWriter {
-                stderr: std::io::stderr(),
-            },
-            ["request_id", "session_id", "conn_id"],
-        ))
-    } else {
-        None
+    let env_filter = add_proxy_directives(
+        EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy(),
+    );
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
+
+    let sampler = trace_sampler(
+        env::var("OTEL_TRACES_SAMPLER").ok().as_deref(),
+        env::var("OTEL_TRACES_SAMPLER_ARG").ok().as_deref(),
+    )?;
+    let otlp_layer = tracing_utils::init_tracing_with_sampler(
+        "proxy",
+        tracing_utils::ExportConfig::default(),
+        sampler,
+    )
+    .await;
+
+    // Without OTel there are no trace IDs, so correlate lines by request ID instead.
+    let fallback_trace_id = otlp_layer.is_none().then_some("request_id");
+
+    let options = FormatOptions {
+        extract_fields: extract_fields.into_iter().map(Cow::Owned).collect(),
+        redact_fields,
+        log_fields,
+        encoding: if logfmt == LogFormat::MsgPack {
+            RecordEncoding::MsgPack
+        } else {
+            RecordEncoding::Json
+        },
+        float_decimals,
+        fallback_trace_id,
+        spans_format,
+        max_line_len,
+        error_chains,
+        timestamp_format,
+        labels: labels.into_iter().collect(),
+    };
+    let (json_log_layer, logfmt_log_layer) = match logfmt {
+        LogFormat::Json | LogFormat::MsgPack => {
+            let layer = JsonLoggingLayer::new(
+                RealClock,
+                FallbackWriter::new(
+                    StderrWriter {
+                        stderr: std::io::stderr(),
+                    },
+                    fallback_file,
+                    FALLBACK_WRITER_MAX_FAILURES,
+                ),
+                ["request_id", "session_id", "conn_id"],
+                options,
+            )
+            .with_sample_rates(sample_rates)
+            .with_span_close_events(span_close_events);
+            (Some(layer), None)
+        }
+        LogFormat::Logfmt => {
+            let layer = LogfmtLoggingLayer::new(
+                RealClock,
+                FallbackWriter::new(
+                    StderrWriter {
+                        stderr: std::io::stderr(),
+                    },
+                    fallback_file,
+                    FALLBACK_WRITER_MAX_FAILURES,
+                ),
+                ["request_id", "session_id", "conn_id"],
+                options,
+            )
+            .with_sample_rates(sample_rates);
+            (None, Some(layer))
+        }
+        LogFormat::Text => (None, None),
     };
 
     let text_log_layer = if logfmt == LogFormat::Text {
@@ -76,10 +189,138 @@ pub async fn init() -> anyhow::Result<LoggingGuard> {
         .with(env_filter)
         .with(otlp_layer)
         .with(json_log_layer)
+        .with(logfmt_log_layer)
         .with(text_log_layer)
         .try_init()?;
 
-    Ok(LoggingGuard)
+    Ok(LoggingGuard::new(FilterReloadHandle {
+        handle: filter_handle,
+        add_directives: add_proxy_directives,
+    })
+    .with_shutdown_timeout(shutdown_timeout))
+}
+
+/// Log options of [`init_with_config`]. The default doesn't depend on the environment, unlike
+/// [`Self::from_env`], which reads the env vars documented on [`init`]. The builder methods
+/// override env vars: [`Self::with_format`] overrides `LOGFMT`, [`Self::with_extract_fields`]
+/// `LOG_EXTRACT_FIELDS`, [`Self::with_writer`] `LOGFMT_FALLBACK_FILE`, and
+/// [`Self::with_shutdown_timeout`] `LOG_SHUTDOWN_TIMEOUT`. The other options can only be set with
+/// env vars.
+#[derive(Debug)]
+pub struct LoggingConfig {
+    format: LogFormat,
+    extract_fields: Vec<String>,
+    writer: LogWriter,
+    log_fields: LogFields,
+    float_decimals: Option<u8>,
+    spans_format: SpansFormat,
+    max_line_len: usize,
+    redact_fields: RedactFields,
+    sample_rates: SampleRates,
+    span_close_events: bool,
+    error_chains: bool,
+    timestamp_format: TimestampFormat,
+    labels: HashMap<&'static str, String>,
+    shutdown_timeout: Duration,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            format: LogFormat::default(),
+            extract_fields: Vec::new(),
+            writer: LogWriter::Stderr,
+            log_fields: LogFields::default(),
+            float_decimals: None,
+            spans_format: SpansFormat::Map,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            redact_fields: RedactFields::default(),
+            sample_rates: SampleRates::default(),
+            span_close_events: false,
+            error_chains: false,
+            timestamp_format: TimestampFormat::Utc,
+            labels: HashMap::new(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let config = LoggingConfig {
+            log_fields: LogFields::from_env()?,
+            float_decimals: float_decimals_from_env()?,
+            spans_format: spans_format_from_env()?,
+            max_line_len: max_line_len_from_env()?,
+            redact_fields: RedactFields::from_env()?,
+            sample_rates: SampleRates::from_env()?,
+            span_close_events: span_close_events_from_env()?,
+            error_chains: error_chains_from_env()?,
+            timestamp_format: TimestampFormat::from_env()?,
+            labels: labels_from_env()?,
+            ..LoggingConfig::default()
+        };
+        Ok(config
+            .with_format(LogFormat::from_env()?)
+            .with_extract_fields(extract_fields_from_env())
+            .with_writer(LogWriter::from_env())
+            .with_shutdown_timeout(shutdown_timeout_from_env()?))
+    }
+
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Span fields extracted into the `extract` object of JSON lines, in addition to
+    /// `request_id`, `session_id` and `conn_id`.
+    pub fn with_extract_fields(mut self, fields: impl IntoIterator<Item = String>) -> Self {
+        self.extract_fields = fields.into_iter().collect();
+        self
+    }
+
+    pub fn with_writer(mut self, writer: LogWriter) -> Self {
+        self.writer = writer;
+        self
+    }
+
+    /// See [`LoggingGuard::with_shutdown_timeout`].
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+}
+
+/// Where logs are written to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogWriter {
+    Stderr,
+    /// Stderr, or the file if writes to stderr keep failing. Not used by the text format.
+    StderrWithFallbackFile(PathBuf),
+}
+
+impl LogWriter {
+    fn from_env() -> Self {
+        match env::var_os("LOGFMT_FALLBACK_FILE") {
+            None => LogWriter::Stderr,
+            Some(path) => LogWriter::StderrWithFallbackFile(path.into()),
+        }
+    }
+}
+
+/// Directives added to the `RUST_LOG` directives of the proxy.
+fn add_proxy_directives(env_filter: EnvFilter) -> EnvFilter {
+    env_filter
+        .add_directive(
+            "aws_config=info"
+                .parse()
+                .expect("this should be a valid filter directive"),
+        )
+        .add_directive(
+            "azure_core::policies::transport=off"
+                .parse()
+                .expect("this should be a valid filter directive"),
+        )
 }
 
 /// Initialize logging for local_proxy with log prefix and no opentelemetry.
@@ -89,6 +330,7 @@ pub fn init_local_proxy() -> anyhow::Result<LoggingGuard> {
     let env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .from_env_lossy();
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
 
     let fmt_layer = tracing_subscriber::fmt::layer()
         .with_ansi(false)
@@ -100,7 +342,10 @@ pub fn init_local_proxy() -> anyhow::Result<LoggingGuard> {
         .with(fmt_layer)
         .try_init()?;
 
-    Ok(LoggingGuard)
+    Ok(LoggingGuard::new(FilterReloadHandle {
+        handle: filter_handle,
+        add_directives: std::convert::identity,
+    }))
 }
 
 pub struct LocalProxyFormatter(Format<Full, SystemTime>);
@@ -121,22 +366,414 @@ fn format_event(
     }
 }
 
-pub struct LoggingGuard;
+/// How long [`LoggingGuard`] waits for the trace pipeline to flush on drop by default.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Replaces the env filter of the installed subscriber.
+struct FilterReloadHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Applied to every new filter, like to the one parsed from `RUST_LOG` on startup.
+    add_directives: fn(EnvFilter) -> EnvFilter,
+}
+
+pub struct LoggingGuard {
+    shutdown_timeout: Duration,
+    shutdown: fn(),
+    filter: Option<FilterReloadHandle>,
+}
+
+impl LoggingGuard {
+    fn new(filter: FilterReloadHandle) -> Self {
+        LoggingGuard {
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            shutdown: tracing_utils::shutdown_tracing,
+            filter: Some(filter),
+        }
+    }
+
+    /// Replaces the log filter with `directives`, in `RUST_LOG` syntax, e.g. `info,proxy=debug`.
+    /// On error, e.g. for invalid directives, the current filter stays in place.
+    pub fn reload_filter(&self, directives: &str) -> anyhow::Result<()> {
+        let Some(filter) = &self.filter else {
+            anyhow::bail!("log filter is not reloadable");
+        };
+        let env_filter = EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .parse(directives)
+            .map_err(|e| anyhow::anyhow!("invalid log filter {directives:?}: {e}"))?;
+        filter.handle.reload((filter.add_directives)(env_filter))?;
+        tracing::info!(directives, "reloaded log filter");
+        Ok(())
+    }
+
+    /// Bound how long dropping the guard waits for pending traces to be exported. A collector
+    /// that doesn't respond within the timeout is given up on, so it can't hang process exit.
+    #[must_use]
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+}
 
 impl Drop for LoggingGuard {
     fn drop(&mut self) {
         // Shutdown trace pipeline gracefully, so that it has a chance to send any
         // pending traces before we exit.
         tracing::info!("shutting down the tracing machinery");
-        tracing_utils::shutdown_tracing();
+
+        // The shutdown blocks on the exporter, so run it on a thread we can stop waiting for.
+        let (tx, rx) = std::sync::mpsc::channel();
+        let shutdown = self.shutdown;
+        let spawned = std::thread::Builder::new()
+            .name("tracing-shutdown".to_owned())
+            .spawn(move || {
+                shutdown();
+                let _ = tx.send(());
+            });
+        if let Err(e) = spawned {
+            tracing::warn!("failed to spawn tracing shutdown thread, shutting down inline: {e}");
+            shutdown();
+            return;
+        }
+
+        if rx.recv_timeout(self.shutdown_timeout).is_err() {
+            tracing::warn!(
+                timeout = ?self.shutdown_timeout,
+                "tracing shutdown did not complete in time, pending traces may be lost"
+            );
+        }
     }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
-enum LogFormat {
+pub enum LogFormat {
     Text,
     #[default]
     Json,
+    MsgPack,
+    Logfmt,
+}
+
+impl LogFields {
+    fn from_env() -> anyhow::Result<Self> {
+        let mut log_fields = LogFields::default();
+        let Ok(omit) = env::var("LOGFMT_OMIT_FIELDS") else {
+            return Ok(log_fields);
+        };
+        for field in omit.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match field {
+                "src" => log_fields.src = false,
+                "module" => log_fields.module = false,
+                "process_id" => log_fields.process_id = false,
+                "thread_id" => log_fields.thread_id = false,
+                "thread_name" => log_fields.thread_name = false,
+                "target" => log_fields.target = false,
+                field => anyhow::bail!("unknown log field: {field}"),
+            }
+        }
+        Ok(log_fields)
+    }
+}
+
+fn float_decimals_from_env() -> anyhow::Result<Option<u8>> {
+    let Ok(decimals) = env::var("LOGFMT_FLOAT_DECIMALS") else {
+        return Ok(None);
+    };
+    let decimals = decimals
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid LOGFMT_FLOAT_DECIMALS {decimals:?}: {e}"))?;
+    Ok(Some(decimals))
+}
+
+fn spans_format_from_env() -> anyhow::Result<SpansFormat> {
+    match env::var("LOGFMT_SPANS").as_deref() {
+        Err(_) | Ok("map") => Ok(SpansFormat::Map),
+        Ok("array") => Ok(SpansFormat::Array),
+        Ok("flat") => Ok(SpansFormat::Flat),
+        Ok(spans) => anyhow::bail!("unknown LOGFMT_SPANS {spans:?}, expected map, array or flat"),
+    }
+}
+
+/// Fields whose values are replaced in logs, matched by exact field name.
+#[derive(Clone, Debug, Default)]
+struct RedactFields {
+    names: HashSet<String>,
+    /// Number of chars kept at both ends of redacted values, so that e.g. tokens can still be
+    /// told apart. Values too short to hide anything are redacted completely.
+    keep_chars: usize,
+}
+
+impl RedactFields {
+    fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        RedactFields {
+            names: names.into_iter().map(str::to_owned).collect(),
+            keep_chars: 0,
+        }
+    }
+
+    fn with_keep_chars(mut self, keep_chars: usize) -> Self {
+        self.keep_chars = keep_chars;
+        self
+    }
+
+    fn from_env() -> anyhow::Result<Self> {
+        let names = env::var("LOGFMT_REDACT_FIELDS").unwrap_or_default();
+        let redact_fields = RedactFields::new(
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|field| !field.is_empty()),
+        );
+        let Ok(keep_chars) = env::var("LOGFMT_REDACT_KEEP_CHARS") else {
+            return Ok(redact_fields);
+        };
+        let keep_chars = keep_chars
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid LOGFMT_REDACT_KEEP_CHARS {keep_chars:?}: {e}"))?;
+        Ok(redact_fields.with_keep_chars(keep_chars))
+    }
+
+    #[inline]
+    fn contains(&self, name: &str) -> bool {
+        !self.names.is_empty() && self.names.contains(name)
+    }
+
+    fn redact(&self, value: &str) -> String {
+        let len = value.chars().count();
+        if self.keep_chars == 0 || len <= 2 * self.keep_chars {
+            return REDACTED.to_owned();
+        }
+        let head: String = value.chars().take(self.keep_chars).collect();
+        let tail: String = value.chars().skip(len - self.keep_chars).collect();
+        format!("{head}{REDACTED}{tail}")
+    }
+
+    /// Replaces `value` if it's the value of the field `name`.
+    #[inline]
+    fn redact_value(&self, name: &str, value: serde_json::Value) -> serde_json::Value {
+        if !self.contains(name) {
+            return value;
+        }
+        match value {
+            serde_json::Value::String(value) => serde_json::Value::from(self.redact(&value)),
+            value => serde_json::Value::from(self.redact(&value.to_string())),
+        }
+    }
+}
+
+/// Rates of 1-in-N sampling of events, by target prefix. The longest matching prefix applies.
+/// ERROR events are never sampled.
+#[derive(Clone, Debug, Default)]
+struct SampleRates {
+    rates: Vec<(String, u64)>,
+}
+
+impl SampleRates {
+    fn new<'a>(rates: impl IntoIterator<Item = (&'a str, u64)>) -> Self {
+        SampleRates {
+            rates: rates
+                .into_iter()
+                .map(|(target, rate)| (target.to_owned(), rate))
+                .collect(),
+        }
+    }
+
+    fn from_env() -> anyhow::Result<Self> {
+        let Ok(sample) = env::var("LOGFMT_SAMPLE") else {
+            return Ok(SampleRates::default());
+        };
+        let mut rates = Vec::new();
+        for rate in sample.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let Some((target, n)) = rate.rsplit_once('=') else {
+                anyhow::bail!("invalid LOGFMT_SAMPLE {rate:?}, expected target=N");
+            };
+            let n: u64 = n
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid LOGFMT_SAMPLE {rate:?}: {e}"))?;
+            anyhow::ensure!(
+                n > 0,
+                "invalid LOGFMT_SAMPLE {rate:?}: rate must be positive"
+            );
+            rates.push((target, n));
+        }
+        Ok(SampleRates::new(rates))
+    }
+
+    /// The sampler for events of a callsite, if they are sampled at all.
+    fn sampler(&self, metadata: &Metadata<'_>) -> Option<CallsiteSampler> {
+        if *metadata.level() == tracing::Level::ERROR {
+            return None;
+        }
+        let (_, rate) = self
+            .rates
+            .iter()
+            .filter(|(target, _)| metadata.target().starts_with(target.as_str()))
+            .max_by_key(|(target, _)| target.len())?;
+        (*rate > 1).then(|| CallsiteSampler {
+            rate: *rate,
+            events: AtomicU64::new(0),
+        })
+    }
+}
+
+/// Counts the events of a sampled callsite.
+struct CallsiteSampler {
+    rate: u64,
+    events: AtomicU64,
+}
+
+impl CallsiteSampler {
+    /// Whether to log the next event. If so, returns the number of events sampled out since the
+    /// previous logged one.
+    #[inline]
+    fn sample(&self) -> Option<u64> {
+        let n = self.events.fetch_add(1, Ordering::Relaxed);
+        (n % self.rate == 0).then(|| if n == 0 { 0 } else { self.rate - 1 })
+    }
+}
+
+/// The OTel sampler configured with `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG`, or
+/// `None` to export all traces. Unlike the `opentelemetry` crate, which falls back to the default
+/// sampler, rejects invalid configuration.
+fn trace_sampler(
+    sampler: Option<&str>,
+    arg: Option<&str>,
+) -> anyhow::Result<Option<tracing_utils::Sampler>> {
+    use tracing_utils::Sampler;
+
+    let ratio = || -> anyhow::Result<f64> {
+        let Some(arg) = arg else {
+            return Ok(1.0);
+        };
+        let ratio: f64 = arg
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid OTEL_TRACES_SAMPLER_ARG {arg:?}: {e}"))?;
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&ratio),
+            "invalid OTEL_TRACES_SAMPLER_ARG {arg:?}: ratio must be between 0 and 1"
+        );
+        Ok(ratio)
+    };
+
+    let Some(sampler) = sampler else {
+        return Ok(None);
+    };
+    Ok(Some(match sampler {
+        "always_on" => Sampler::AlwaysOn,
+        "always_off" => Sampler::AlwaysOff,
+        "traceidratio" => Sampler::TraceIdRatioBased(ratio()?),
+        "parentbased_always_on" => Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        "parentbased_always_off" => Sampler::ParentBased(Box::new(Sampler::AlwaysOff)),
+        "parentbased_traceidratio" => {
+            Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio()?)))
+        }
+        sampler => anyhow::bail!("unknown OTEL_TRACES_SAMPLER {sampler:?}"),
+    }))
+}
+
+/// How [`JsonLoggingLayer`] formats the time of events.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum TimestampFormat {
+    /// RFC 3339 in UTC, with microseconds, e.g. `2024-01-01T12:00:00.000000Z`.
+    #[default]
+    Utc,
+    /// RFC 3339 in the given offset, with microseconds, e.g. `2024-01-01T14:00:00.000000+02:00`.
+    FixedOffset(FixedOffset),
+    /// Milliseconds since the Unix epoch, as a number.
+    EpochMillis,
+}
+
+impl TimestampFormat {
+    fn from_env() -> anyhow::Result<Self> {
+        match env::var("LOGFMT_TIMESTAMP").as_deref() {
+            Err(_) | Ok("utc") => Ok(TimestampFormat::Utc),
+            Ok("epoch_millis") => Ok(TimestampFormat::EpochMillis),
+            Ok(offset) => offset
+                .parse()
+                .map(TimestampFormat::FixedOffset)
+                .map_err(|e| anyhow::anyhow!("invalid LOGFMT_TIMESTAMP {offset:?}: {e}")),
+        }
+    }
+
+    #[inline]
+    fn format(self, now: DateTime<Utc>) -> Timestamp {
+        match self {
+            TimestampFormat::Utc => {
+                Timestamp::Text(now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true))
+            }
+            TimestampFormat::FixedOffset(offset) => Timestamp::Text(
+                now.with_timezone(&offset)
+                    .to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            ),
+            TimestampFormat::EpochMillis => Timestamp::Number(now.timestamp_millis()),
+        }
+    }
+}
+
+/// A timestamp formatted with a [`TimestampFormat`].
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Text(String),
+    Number(i64),
+}
+
+fn extract_fields_from_env() -> Vec<String> {
+    env::var("LOG_EXTRACT_FIELDS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parses `LOGFMT_LABELS`. Label names are leaked, which is fine as this is done once.
+fn labels_from_env() -> anyhow::Result<HashMap<&'static str, String>> {
+    let Ok(labels) = env::var("LOGFMT_LABELS") else {
+        return Ok(HashMap::new());
+    };
+    let mut parsed = HashMap::new();
+    for label in labels.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+        let Some((name, value)) = label.split_once('=') else {
+            anyhow::bail!("invalid LOGFMT_LABELS {label:?}, expected name=value");
+        };
+        parsed.insert(&*name.trim().to_owned().leak(), value.trim().to_owned());
+    }
+    Ok(parsed)
+}
+
+fn error_chains_from_env() -> anyhow::Result<bool> {
+    match env::var("LOGFMT_ERROR_CHAINS").as_deref() {
+        Err(_) | Ok("false") => Ok(false),
+        Ok("true") => Ok(true),
+        Ok(value) => anyhow::bail!("invalid LOGFMT_ERROR_CHAINS {value:?}, expected true or false"),
+    }
+}
+
+fn span_close_events_from_env() -> anyhow::Result<bool> {
+    match env::var("LOGFMT_SPAN_CLOSE").as_deref() {
+        Err(_) | Ok("false") => Ok(false),
+        Ok("true") => Ok(true),
+        Ok(value) => anyhow::bail!("invalid LOGFMT_SPAN_CLOSE {value:?}, expected true or false"),
+    }
+}
+
+fn max_line_len_from_env() -> anyhow::Result<usize> {
+    let Ok(max_line_len) = env::var("LOGFMT_MAX_LINE_LEN") else {
+        return Ok(DEFAULT_MAX_LINE_LEN);
+    };
+    max_line_len
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid LOGFMT_MAX_LINE_LEN {max_line_len:?}: {e}"))
+}
+
+fn shutdown_timeout_from_env() -> anyhow::Result<Duration> {
+    let Ok(timeout) = env::var("LOG_SHUTDOWN_TIMEOUT") else {
+        return Ok(DEFAULT_SHUTDOWN_TIMEOUT);
+    };
+    humantime::parse_duration(&timeout)
+        .map_err(|e| anyhow::anyhow!("invalid LOG_SHUTDOWN_TIMEOUT {timeout:?}: {e}"))
 }
 
 impl LogFormat {
@@ -146,6 +783,8 @@ fn from_env() -> anyhow::Result<Self> {
             Err(_) => LogFormat::default(),
             Ok("text") => LogFormat::Text,
             Ok("json") => LogFormat::Json,
+            Ok("msgpack") => LogFormat::MsgPack,
+            Ok("logfmt") => LogFormat::Logfmt,
             Ok(logfmt) => anyhow::bail!("unknown log format: {logfmt}"),
         })
     }
@@ -166,9 +805,103 @@ fn make_writer(&self) -> impl io::Write {
     }
 }
 
+/// Appends to a file, e.g. for [`FallbackWriter::secondary`].
+struct FileWriter {
+    file: std::fs::File,
+}
+
+impl MakeWriter for FileWriter {
+    #[inline]
+    fn make_writer(&self) -> impl io::Write {
+        &self.file
+    }
+}
+
+/// Writes to `primary`, and to `secondary` instead once writes to `primary` failed more than
+/// `max_failures` times in a row, so that logs aren't lost if e.g. stderr is a closed pipe.
+/// `primary` is still tried first for every line, and a successful write resets the count. If
+/// `primary` fails after writing part of a line, only the rest of it goes to `secondary`.
+struct FallbackWriter<W: MakeWriter, S: MakeWriter> {
+    primary: W,
+    secondary: Option<S>,
+    max_failures: u64,
+    /// Number of failed writes to `primary` since its last successful one.
+    failures: AtomicU64,
+}
+
+impl<W: MakeWriter, S: MakeWriter> FallbackWriter<W, S> {
+    fn new(primary: W, secondary: Option<S>, max_failures: u64) -> Self {
+        FallbackWriter {
+            primary,
+            secondary,
+            max_failures,
+            failures: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<W: MakeWriter, S: MakeWriter> MakeWriter for FallbackWriter<W, S> {
+    #[inline]
+    fn make_writer(&self) -> impl io::Write {
+        self
+    }
+}
+
+impl<W: MakeWriter, S: MakeWriter> io::Write for &FallbackWriter<W, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        use std::io::Write;
+
+        let mut primary = self.primary.make_writer();
+        let mut written = 0;
+        let err = loop {
+            if written == buf.len() {
+                self.failures.store(0, Ordering::Relaxed);
+                return Ok(written);
+            }
+            match primary.write(&buf[written..]) {
+                Ok(0) => break io::Error::from(io::ErrorKind::WriteZero),
+                Ok(n) => written += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break err,
+            }
+        };
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        match &self.secondary {
+            Some(secondary) if failures > self.max_failures => {
+                secondary.make_writer().write_all(&buf[written..])?;
+                Ok(buf.len())
+            }
+            // Report the partial write, the caller retries the rest.
+            _ if written > 0 => Ok(written),
+            _ => Err(err),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Failed writes to stderr after which logs go to [`LogWriter::StderrWithFallbackFile`].
+const FALLBACK_WRITER_MAX_FAILURES: u64 = 10;
+
+impl FileWriter {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow::anyhow!("cannot open log file {path:?}: {e}"))?;
+        Ok(FileWriter { file })
+    }
+}
+
 // TODO: move into separate module or even separate crate.
 trait Clock {
     fn now(&self) -> DateTime<Utc>;
+
+    /// Monotonic time, for durations. Unlike [`Self::now`], it never goes back.
+    fn instant(&self) -> Instant;
 }
 
 struct RealClock;
@@ -178,19 +911,51 @@ impl Clock for RealClock {
     fn now(&self) -> DateTime<Utc> {
         Utc::now()
     }
+
+    #[inline]
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
 }
 
 /// Name of the field used by tracing crate to store the event message.
 const MESSAGE_FIELD: &str = "message";
 
+/// How many dropped events [`JsonLoggingLayer`] reports at once.
+const DROPPED_EVENTS_REPORT_INTERVAL: u64 = 100;
+
+/// Counts an event that failed to be formatted or written in `dropped_events` and the
+/// `log_events_dropped_total` metric. Returns the new count.
+fn count_dropped_event(dropped_events: &AtomicU64) -> u64 {
+    if let Some(metrics) = Metrics::try_get() {
+        metrics.proxy.log_events_dropped_total.inc();
+    }
+    dropped_events.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Replaces the values of [`RedactFields`].
+const REDACTED: &str = "[redacted]";
+
+/// Events logged while this many events are already being logged on the same thread are dropped.
+/// Nested logging happens e.g. when the OTel exporter logs while exporting, which could otherwise
+/// recurse without bound.
+const MAX_REENTRANCY_DEPTH: u8 = 4;
+
 thread_local! {
-    /// Protects against deadlocks and double panics during log writing.
-    /// The current panic handler will use tracing to log panic information.
-    static REENTRANCY_GUARD: Cell<bool> = const { Cell::new(false) };
+    /// Number of events being logged on this thread. Protects against deadlocks and double
+    /// panics during log writing: nested events don't use [`EVENT_FORMATTER`], and are dropped
+    /// past [`MAX_REENTRANCY_DEPTH`]. The current panic handler will use tracing to log panic
+    /// information.
+    static REENTRANCY_DEPTH: Cell<u8> = const { Cell::new(0) };
     /// Thread-local instance with per-thread buffer for log writing.
     static EVENT_FORMATTER: RefCell<EventFormatter> = RefCell::new(EventFormatter::new());
     /// Cached OS thread ID.
     static THREAD_ID: u64 = gettid::gettid();
+    /// Cached thread name, if it's worth logging. Threads aren't renamed once running.
+    static THREAD_NAME: Option<String> = std::thread::current()
+        .name()
+        .filter(|name| !name.is_empty() && *name != "tokio-runtime-worker")
+        .map(str::to_owned);
 }
 
 /// Implements tracing layer to handle events specific to logging.
@@ -199,25 +964,164 @@ struct JsonLoggingLayer<C: Clock, W: MakeWriter, const F: usize> {
     skipped_field_indices: papaya::HashMap<callsite::Identifier, SkippedFieldIndices>,
     callsite_ids: papaya::HashMap<callsite::Identifier, CallsiteId>,
     writer: W,
-    // We use a const generic and arrays to bypass one heap allocation.
-    extract_fields: IndexSet<&'static str>,
+    options: FormatOptions,
+    /// Number of events that failed to be formatted or written, also counted in the
+    /// `log_events_dropped_total` metric.
+    dropped_events: AtomicU64,
+    sample_rates: SampleRates,
+    /// Samplers of the event callsites that [`Self::sample_rates`] apply to.
+    samplers: papaya::HashMap<callsite::Identifier, CallsiteSampler>,
+    /// Log a line with the duration of every span when it closes.
+    span_close_events: bool,
+    // We use a const generic and arrays to bypass one heap allocation. Fields beyond the first `F`
+    // are only known at runtime.
     _marker: std::marker::PhantomData<[&'static str; F]>,
 }
 
+/// How [`JsonLoggingLayer`] formats events.
+struct FormatOptions {
+    /// Span and event fields extracted into the `extract` object. [`JsonLoggingLayer::new`]
+    /// prepends its `F` fields, whose values don't need a heap allocation per event.
+    extract_fields: IndexSet<Cow<'static, str>>,
+    redact_fields: RedactFields,
+    log_fields: LogFields,
+    encoding: RecordEncoding,
+    /// Number of decimals `f64` field values are rounded to, or full precision if `None`.
+    float_decimals: Option<u8>,
+    /// Span field used as `trace_id` when the event has no valid OTel span. See
+    /// [`FallbackTraceId`].
+    fallback_trace_id: Option<&'static str>,
+    spans_format: SpansFormat,
+    /// Lines longer than this have their field values truncated. See [`EventFormatter::format`].
+    max_line_len: usize,
+    /// Serialize error fields as the array of messages of the error and its sources.
+    error_chains: bool,
+    timestamp_format: TimestampFormat,
+    /// Constant labels added to every line as the `labels` object, or as `labels.<name>` logfmt
+    /// keys, sorted by name.
+    labels: BTreeMap<&'static str, String>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            extract_fields: IndexSet::new(),
+            redact_fields: RedactFields::default(),
+            log_fields: LogFields::default(),
+            encoding: RecordEncoding::Json,
+            float_decimals: None,
+            fallback_trace_id: None,
+            spans_format: SpansFormat::Map,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            error_chains: false,
+            timestamp_format: TimestampFormat::Utc,
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+/// Counts a written event and its bytes in the `log_events_total` and `log_bytes_total` metrics.
+#[inline]
+fn record_log_volume(level: &tracing::Level, bytes: usize) {
+    if let Some(metrics) = Metrics::try_get() {
+        metrics.proxy.log_events_total.inc(LogLevel::from(level));
+        metrics
+            .proxy
+            .log_bytes_total
+            .inc_by(LogLevel::from(level), bytes as u64);
+    }
+}
+
+/// How [`JsonLoggingLayer`] serializes the spans of an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpansFormat {
+    /// `spans` is a map keyed by `name#cid`. See [`SerializableSpans`].
+    Map,
+    /// `spans` is an array ordered from root to leaf.
+    Array,
+    /// No `spans`, but a top-level `span.<name>.<field>` entry per span field, which is easier to
+    /// query e.g. in Loki. If spans of the same name have the same field, the innermost one wins.
+    Flat,
+}
+
+/// How [`JsonLoggingLayer`] encodes each event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RecordEncoding {
+    /// One JSON object per line.
+    Json,
+    /// One MessagePack map per event, prefixed with its length as a big-endian `u32`.
+    MsgPack,
+}
+
+/// Selects which of the optional fields are added to each log line.
+/// `timestamp`, `level` and `message` are always present.
+#[derive(Clone, Copy, Debug)]
+struct LogFields {
+    src: bool,
+    module: bool,
+    process_id: bool,
+    thread_id: bool,
+    thread_name: bool,
+    target: bool,
+}
+
+impl Default for LogFields {
+    fn default() -> Self {
+        LogFields {
+            src: true,
+            module: true,
+            process_id: true,
+            thread_id: true,
+            thread_name: true,
+            target: true,
+        }
+    }
+}
+
 impl<C: Clock, W: MakeWriter, const F: usize> JsonLoggingLayer<C, W, F> {
-    fn new(clock: C, writer: W, extract_fields: [&'static str; F]) -> Self {
+    fn new(
+        clock: C,
+        writer: W,
+        extract_fields: [&'static str; F],
+        mut options: FormatOptions,
+    ) -> Self {
+        let mut names: IndexSet<_> = extract_fields.into_iter().map(Cow::Borrowed).collect();
+        names.extend(options.extract_fields);
+        options.extract_fields = names;
         JsonLoggingLayer {
             clock,
             skipped_field_indices: papaya::HashMap::default(),
             callsite_ids: papaya::HashMap::default(),
             writer,
-            extract_fields: IndexSet::from_iter(extract_fields),
+            options,
+            dropped_events: AtomicU64::new(0),
+            sample_rates: SampleRates::default(),
+            samplers: papaya::HashMap::default(),
+            span_close_events: false,
             _marker: std::marker::PhantomData,
         }
     }
 
-    #[inline]
-    fn callsite_id(&self, cs: callsite::Identifier) -> CallsiteId {
+    fn with_sample_rates(mut self, sample_rates: SampleRates) -> Self {
+        self.sample_rates = sample_rates;
+        self
+    }
+
+    fn with_span_close_events(mut self, span_close_events: bool) -> Self {
+        self.span_close_events = span_close_events;
+        self
+    }
+
+    /// Number of events that failed to be formatted or written, and were logged as a simplified
+    /// error line instead. Reported in a log line of its own every
+    /// [`DROPPED_EVENTS_REPORT_INTERVAL`] events.
+    #[cfg(test)]
+    fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn callsite_id(&self, cs: callsite::Identifier) -> CallsiteId {
         *self
             .callsite_ids
             .pin()
@@ -233,53 +1137,78 @@ impl<S, C: Clock + 'static, W: MakeWriter + 'static, const F: usize> Layer<S>
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
         use std::io::Write;
 
+        let sampled_count = match self.samplers.pin().get(&event.metadata().callsite()) {
+            None => None,
+            Some(sampler) => match sampler.sample() {
+                None => return,
+                sampled_count => sampled_count,
+            },
+        };
+
         // TODO: consider special tracing subscriber to grab timestamp very
         //       early, before OTel machinery, and add as event extension.
         let now = self.clock.now();
+        let timestamp = self.options.timestamp_format.format(now);
 
-        let res: io::Result<()> = REENTRANCY_GUARD.with(move |entered| {
-            if entered.get() {
-                let mut formatter = EventFormatter::new();
-                formatter.format::<S, F>(
-                    now,
-                    event,
-                    &ctx,
-                    &self.skipped_field_indices,
-                    &self.callsite_ids,
-                    &self.extract_fields,
-                )?;
-                self.writer.make_writer().write_all(formatter.buffer())
-            } else {
-                entered.set(true);
-                defer!(entered.set(false););
-
-                EVENT_FORMATTER.with_borrow_mut(move |formatter| {
-                    formatter.reset();
-                    formatter.format::<S, F>(
-                        now,
-                        event,
-                        &ctx,
-                        &self.skipped_field_indices,
-                        &self.callsite_ids,
-                        &self.extract_fields,
-                    )?;
-                    self.writer.make_writer().write_all(formatter.buffer())
-                })
-            }
+        use tracing_log::NormalizeEvent;
+        let normalized_meta = event.normalized_metadata();
+        let meta = normalized_meta.as_ref().unwrap_or_else(|| event.metadata());
+
+        let skipped_field_indices = self.skipped_field_indices.pin();
+        let mut record = SerializableEvent::<'_, '_, S, F> {
+            timestamp: &timestamp,
+            meta,
+            event,
+            ctx: &ctx,
+            skipped_field_indices: skipped_field_indices.get(&meta.callsite()),
+            callsite_ids: &self.callsite_ids,
+            options: &self.options,
+            max_value_len: None,
+            sampled_count,
+        };
+
+        let res = with_event_formatter(|formatter| {
+            formatter.format(&mut record)?;
+            self.writer.make_writer().write_all(formatter.buffer())?;
+            record_log_volume(event.metadata().level(), formatter.buffer().len());
+            Ok(())
         });
 
-        // In case logging fails we generate a simpler JSON object.
+        // In case logging fails we generate a simpler record, in the same encoding so that
+        // readers keep the framing.
         if let Err(err) = res {
-            if let Ok(mut line) = serde_json::to_vec(&serde_json::json!( {
-                "timestamp": now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            let dropped_events = count_dropped_event(&self.dropped_events);
+            let write_line = |mut record: serde_json::Value| {
+                if !self.options.labels.is_empty() {
+                    record["labels"] = serde_json::json!(self.options.labels);
+                }
+                let mut formatter = EventFormatter::new();
+                if formatter
+                    .write_record(&record, self.options.encoding)
+                    .is_ok()
+                {
+                    self.writer.make_writer().write_all(formatter.buffer()).ok();
+                }
+            };
+
+            write_line(serde_json::json!( {
+                "timestamp": &timestamp,
                 "level": "ERROR",
                 "message": format_args!("cannot log event: {err:?}"),
                 "fields": {
                     "event": format_args!("{event:?}"),
                 },
-            })) {
-                line.push(b'\n');
-                self.writer.make_writer().write_all(&line).ok();
+            }));
+
+            if dropped_events % DROPPED_EVENTS_REPORT_INTERVAL == 0 {
+                write_line(serde_json::json!( {
+                    "timestamp": &timestamp,
+                    "level": "WARN",
+                    "message": "log events could not be logged",
+                    "fields": {
+                        "dropped_events": dropped_events,
+                    },
+                }));
             }
         }
     }
@@ -288,7 +1217,11 @@ fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
     fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
         let span = ctx.span(id).expect("span must exist");
         let fields = SpanFields::default();
-        fields.record_fields(attrs);
+        fields.record_fields(
+            attrs,
+            self.options.float_decimals,
+            &self.options.redact_fields,
+        );
 
         // This could deadlock when there's a panic somewhere in the tracing
         // event handling and a read or write guard is still held. This includes
@@ -296,13 +1229,72 @@ fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<
         let mut exts = span.extensions_mut();
 
         exts.insert(fields);
+        if self.options.fallback_trace_id.is_some() && span.parent().is_none() {
+            exts.insert(FallbackTraceId(rand::random()));
+        }
+        if self.span_close_events {
+            exts.insert(SpanStart(self.clock.instant()));
+        }
+    }
+
+    /// Logs how long the span was open, if enabled.
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        use std::io::Write;
+
+        if !self.span_close_events {
+            return;
+        }
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(SpanStart(started_at)) = span.extensions().get::<SpanStart>().copied() else {
+            return;
+        };
+
+        let duration = self.clock.instant().saturating_duration_since(started_at);
+        let duration_ms = duration.as_secs_f64() * 1000.0;
+        let now = self.clock.now();
+
+        let extract = ExtractedSpanFields::<'_, F>::new(&self.options.extract_fields);
+        for span in span.scope().from_root() {
+            let ext = span.extensions();
+            if let Some(data) = ext.get::<SpanFields>() {
+                for (name, value) in &data.fields.pin() {
+                    if let Some(slot) = extract.slot(name) {
+                        extract.set(slot, value.clone());
+                    }
+                }
+            }
+        }
+
+        let timestamp = self.options.timestamp_format.format(now);
+        let record = SerializableSpanClose {
+            timestamp: &timestamp,
+            fields: SpanCloseFields {
+                span: span.metadata().name(),
+                duration_ms: round_to_decimals(duration_ms, self.options.float_decimals),
+            },
+            extract: &extract,
+            labels: &self.options.labels,
+        };
+        let res = with_event_formatter(|formatter| {
+            formatter.write_record(&record, self.options.encoding)?;
+            self.writer.make_writer().write_all(formatter.buffer())
+        });
+        if res.is_err() {
+            count_dropped_event(&self.dropped_events);
+        }
     }
 
     fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
         let span = ctx.span(id).expect("span must exist");
         let ext = span.extensions();
         if let Some(data) = ext.get::<SpanFields>() {
-            data.record_fields(values);
+            data.record_fields(
+                values,
+                self.options.float_decimals,
+                &self.options.redact_fields,
+            );
         }
     }
 
@@ -316,30 +1308,191 @@ fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
             return Interest::always();
         }
 
-        let mut field_indices = SkippedFieldIndices::default();
-        let mut seen_fields = HashMap::<&'static str, usize>::new();
-        for field in metadata.fields() {
-            use std::collections::hash_map::Entry;
-            match seen_fields.entry(field.name()) {
-                Entry::Vacant(entry) => {
-                    // field not seen yet
-                    entry.insert(field.index());
-                }
-                Entry::Occupied(mut entry) => {
-                    // replace currently stored index
-                    let old_index = entry.insert(field.index());
-                    // ... and append it to list of skippable indices
-                    field_indices.push(old_index);
-                }
-            }
-        }
-
+        let field_indices = SkippedFieldIndices::duplicates(metadata);
         if !field_indices.is_empty() {
             self.skipped_field_indices
                 .pin()
                 .insert(metadata.callsite(), field_indices);
         }
 
+        if let Some(sampler) = self.sample_rates.sampler(metadata) {
+            self.samplers.pin().insert(metadata.callsite(), sampler);
+        }
+
+        Interest::always()
+    }
+}
+
+/// Runs `f` with the thread-local [`EventFormatter`], or a fresh one for nested events. Events
+/// nested deeper than [`MAX_REENTRANCY_DEPTH`] are dropped without calling `f`.
+fn with_event_formatter(f: impl FnOnce(&mut EventFormatter) -> io::Result<()>) -> io::Result<()> {
+    REENTRANCY_DEPTH.with(move |depth| {
+        let outer_depth = depth.get();
+        if outer_depth >= MAX_REENTRANCY_DEPTH {
+            if let Some(metrics) = Metrics::try_get() {
+                metrics.proxy.log_events_dropped_reentrancy_total.inc();
+            }
+            return Ok(());
+        }
+        depth.set(outer_depth + 1);
+        defer!(depth.set(outer_depth););
+
+        if outer_depth > 0 {
+            f(&mut EventFormatter::new())
+        } else {
+            EVENT_FORMATTER.with_borrow_mut(move |formatter| {
+                formatter.reset();
+                f(formatter)
+            })
+        }
+    })
+}
+
+/// Implements tracing layer writing events as [logfmt](https://brandur.org/logfmt) lines:
+/// `key=value` pairs, with span fields flattened into `span.<name>.<field>` keys and extracted
+/// fields as top-level keys.
+struct LogfmtLoggingLayer<C: Clock, W: MakeWriter, const F: usize> {
+    clock: C,
+    skipped_field_indices: papaya::HashMap<callsite::Identifier, SkippedFieldIndices>,
+    writer: W,
+    /// Only the extracted and redacted fields, the float decimals and the labels apply; logfmt
+    /// lines have a fixed layout.
+    options: FormatOptions,
+    /// Number of events that failed to be formatted or written, also counted in the
+    /// `log_events_dropped_total` metric.
+    dropped_events: AtomicU64,
+    sample_rates: SampleRates,
+    /// Samplers of the event callsites that [`Self::sample_rates`] apply to.
+    samplers: papaya::HashMap<callsite::Identifier, CallsiteSampler>,
+    _marker: std::marker::PhantomData<[&'static str; F]>,
+}
+
+impl<C: Clock, W: MakeWriter, const F: usize> LogfmtLoggingLayer<C, W, F> {
+    fn new(
+        clock: C,
+        writer: W,
+        extract_fields: [&'static str; F],
+        mut options: FormatOptions,
+    ) -> Self {
+        let mut names: IndexSet<_> = extract_fields.into_iter().map(Cow::Borrowed).collect();
+        names.extend(options.extract_fields);
+        options.extract_fields = names;
+        LogfmtLoggingLayer {
+            clock,
+            skipped_field_indices: papaya::HashMap::default(),
+            writer,
+            options,
+            dropped_events: AtomicU64::new(0),
+            sample_rates: SampleRates::default(),
+            samplers: papaya::HashMap::default(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    fn with_sample_rates(mut self, sample_rates: SampleRates) -> Self {
+        self.sample_rates = sample_rates;
+        self
+    }
+
+    /// Number of events that failed to be formatted or written, and were logged as a simplified
+    /// error line instead. Reported in a log line of its own every
+    /// [`DROPPED_EVENTS_REPORT_INTERVAL`] events.
+    #[cfg(test)]
+    fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+}
+
+impl<S, C: Clock + 'static, W: MakeWriter + 'static, const F: usize> Layer<S>
+    for LogfmtLoggingLayer<C, W, F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        use std::io::Write;
+
+        let sampled_count = match self.samplers.pin().get(&event.metadata().callsite()) {
+            None => None,
+            Some(sampler) => match sampler.sample() {
+                None => return,
+                sampled_count => sampled_count,
+            },
+        };
+
+        let now = self.clock.now();
+
+        let res = with_event_formatter(|formatter| {
+            formatter.format_logfmt::<S, F>(
+                now,
+                event,
+                &ctx,
+                &self.skipped_field_indices,
+                &self.options,
+                sampled_count,
+            )?;
+            self.writer.make_writer().write_all(formatter.buffer())?;
+            record_log_volume(event.metadata().level(), formatter.buffer().len());
+            Ok(())
+        });
+
+        if let Err(err) = res {
+            let dropped_events = count_dropped_event(&self.dropped_events);
+            let timestamp = now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+            let write_line = |level: &str, msg: &str, fields: &str| {
+                let mut line = format!("timestamp={timestamp} level={level} msg=").into_bytes();
+                push_logfmt_quoted(&mut line, msg);
+                line.extend_from_slice(fields.as_bytes());
+                push_logfmt_labels(&mut line, &self.options.labels);
+                line.push(b'\n');
+                self.writer.make_writer().write_all(&line).ok();
+            };
+
+            write_line("ERROR", &format!("cannot log event: {err:?}"), "");
+            if dropped_events % DROPPED_EVENTS_REPORT_INTERVAL == 0 {
+                write_line(
+                    "WARN",
+                    "log events could not be logged",
+                    &format!(" dropped_events={dropped_events}"),
+                );
+            }
+        }
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("span must exist");
+        let fields = SpanFields::default();
+        fields.record_fields(
+            attrs,
+            self.options.float_decimals,
+            &self.options.redact_fields,
+        );
+        span.extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("span must exist");
+        let ext = span.extensions();
+        if let Some(data) = ext.get::<SpanFields>() {
+            data.record_fields(
+                values,
+                self.options.float_decimals,
+                &self.options.redact_fields,
+            );
+        }
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.is_event() {
+            let field_indices = SkippedFieldIndices::duplicates(metadata);
+            if !field_indices.is_empty() {
+                self.skipped_field_indices
+                    .pin()
+                    .insert(metadata.callsite(), field_indices);
+            }
+            if let Some(sampler) = self.sample_rates.sampler(metadata) {
+                self.samplers.pin().insert(metadata.callsite(), sampler);
+            }
+        }
         Interest::always()
     }
 }
@@ -373,9 +1526,16 @@ struct SpanFields {
 
 impl SpanFields {
     #[inline]
-    fn record_fields<R: tracing_subscriber::field::RecordFields>(&self, fields: R) {
+    fn record_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        fields: R,
+        float_decimals: Option<u8>,
+        redact_fields: &RedactFields,
+    ) {
         fields.record(&mut SpanFieldsRecorder {
             fields: self.fields.pin(),
+            float_decimals,
+            redact_fields,
         });
     }
 }
@@ -383,71 +1543,71 @@ fn record_fields<R: tracing_subscriber::field::RecordFields>(&self, fields: R) {
 /// Implements a tracing field visitor to convert and store values.
 struct SpanFieldsRecorder<'m, S, G> {
     fields: papaya::HashMapRef<'m, &'static str, serde_json::Value, S, G>,
+    float_decimals: Option<u8>,
+    redact_fields: &'m RedactFields,
+}
+
+impl<S: BuildHasher, G: papaya::Guard> SpanFieldsRecorder<'_, S, G> {
+    #[inline]
+    fn insert(&self, field: &tracing::field::Field, value: serde_json::Value) {
+        let value = self.redact_fields.redact_value(field.name(), value);
+        self.fields.insert(field.name(), value);
+    }
 }
 
 impl<S: BuildHasher, G: papaya::Guard> tracing::field::Visit for SpanFieldsRecorder<'_, S, G> {
     #[inline]
     fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(value));
+        let value = round_to_decimals(value, self.float_decimals);
+        self.insert(field, serde_json::Value::from(value));
     }
 
     #[inline]
     fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(value));
+        self.insert(field, serde_json::Value::from(value));
     }
 
     #[inline]
     fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(value));
+        self.insert(field, serde_json::Value::from(value));
     }
 
     #[inline]
     fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
         if let Ok(value) = i64::try_from(value) {
-            self.fields
-                .insert(field.name(), serde_json::Value::from(value));
+            self.insert(field, serde_json::Value::from(value));
         } else {
-            self.fields
-                .insert(field.name(), serde_json::Value::from(format!("{value}")));
+            self.insert(field, serde_json::Value::from(format!("{value}")));
         }
     }
 
     #[inline]
     fn record_u128(&mut self, field: &tracing::field::Field, value: u128) {
         if let Ok(value) = u64::try_from(value) {
-            self.fields
-                .insert(field.name(), serde_json::Value::from(value));
+            self.insert(field, serde_json::Value::from(value));
         } else {
-            self.fields
-                .insert(field.name(), serde_json::Value::from(format!("{value}")));
+            self.insert(field, serde_json::Value::from(format!("{value}")));
         }
     }
 
     #[inline]
     fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(value));
+        self.insert(field, serde_json::Value::from(value));
     }
 
     #[inline]
     fn record_bytes(&mut self, field: &tracing::field::Field, value: &[u8]) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(value));
+        self.insert(field, serde_json::Value::from(value));
     }
 
     #[inline]
     fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(value));
+        self.insert(field, serde_json::Value::from(value));
     }
 
     #[inline]
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(format!("{value:?}")));
+        self.insert(field, serde_json::Value::from(format!("{value:?}")));
     }
 
     #[inline]
@@ -456,11 +1616,22 @@ fn record_error(
         field: &tracing::field::Field,
         value: &(dyn std::error::Error + 'static),
     ) {
-        self.fields
-            .insert(field.name(), serde_json::Value::from(format!("{value}")));
+        self.insert(field, serde_json::Value::from(format!("{value}")));
     }
 }
 
+/// Rounds `value` to the given number of decimals. Values that can't be rounded without
+/// overflowing, and all values if `decimals` is `None`, are returned unchanged.
+#[inline]
+fn round_to_decimals(value: f64, decimals: Option<u8>) -> f64 {
+    let Some(decimals) = decimals else {
+        return value;
+    };
+    let factor = 10f64.powi(i32::from(decimals));
+    let rounded = (value * factor).round() / factor;
+    if rounded.is_finite() { rounded } else { value }
+}
+
 /// List of field indices skipped during logging. Can list duplicate fields or
 /// metafields not meant to be logged.
 #[derive(Clone, Default)]
@@ -469,6 +1640,29 @@ struct SkippedFieldIndices {
 }
 
 impl SkippedFieldIndices {
+    /// Indices of the fields of `metadata` that are shadowed by a later field with the same name.
+    /// Last one wins.
+    fn duplicates(metadata: &Metadata<'_>) -> Self {
+        let mut field_indices = SkippedFieldIndices::default();
+        let mut seen_fields = HashMap::<&str, usize>::new();
+        for field in metadata.fields() {
+            use std::collections::hash_map::Entry;
+            match seen_fields.entry(field.name()) {
+                Entry::Vacant(entry) => {
+                    // field not seen yet
+                    entry.insert(field.index());
+                }
+                Entry::Occupied(mut entry) => {
+                    // replace currently stored index
+                    let old_index = entry.insert(field.index());
+                    // ... and append it to list of skippable indices
+                    field_indices.push(old_index);
+                }
+            }
+        }
+        field_indices
+    }
+
     #[inline]
     fn is_empty(&self) -> bool {
         self.bits == 0
@@ -491,8 +1685,20 @@ fn contains(&self, index: usize) -> bool {
     }
 }
 
+/// Buffers above this capacity are shrunk on [`EventFormatter::reset`].
+const MAX_RETAINED_BUFFER_CAPACITY: usize = 64 * 1024;
+/// Capacity kept after shrinking, large enough for typical log lines.
+const SHRUNK_BUFFER_CAPACITY: usize = 4 * 1024;
+/// Default for [`JsonLoggingLayer::max_line_len`]. Log collectors reject larger lines.
+const DEFAULT_MAX_LINE_LEN: usize = 64 * 1024;
+/// Field values of oversized lines aren't truncated below this length.
+const MIN_TRUNCATED_VALUE_LEN: usize = 64;
+
 /// Formats a tracing event and writes JSON to its internal buffer including a newline.
-// TODO: buffer capacity management, truncate if too large
+///
+/// Every thread keeps its own formatter, so the buffer capacity is bounded: without that, one
+/// huge log line would permanently inflate the memory of each thread that ever logged one. The
+/// cost is a reallocation for every line larger than [`MAX_RETAINED_BUFFER_CAPACITY`].
 struct EventFormatter {
     logline_buffer: Vec<u8>,
 }
@@ -513,21 +1719,81 @@ fn buffer(&self) -> &[u8] {
     #[inline]
     fn reset(&mut self) {
         self.logline_buffer.clear();
+        if self.logline_buffer.capacity() > MAX_RETAINED_BUFFER_CAPACITY {
+            self.logline_buffer.shrink_to(SHRUNK_BUFFER_CAPACITY);
+        }
     }
 
+    /// Writes `record` in the configured encoding. Lines longer than the configured
+    /// `max_line_len` are formatted again with string field values truncated, halving the allowed
+    /// value length until the line fits. The timestamp, level and message are never truncated, so
+    /// lines with huge messages stay longer than `max_line_len`.
     fn format<S, const F: usize>(
+        &mut self,
+        record: &mut SerializableEvent<'_, '_, S, F>,
+    ) -> io::Result<()>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let encoding = record.options.encoding;
+        let max_line_len = record.options.max_line_len;
+
+        let start = self.logline_buffer.len();
+        self.write_record(record, encoding)?;
+
+        let mut max_value_len = max_line_len;
+        while self.logline_buffer.len() - start > max_line_len
+            && max_value_len > MIN_TRUNCATED_VALUE_LEN
+        {
+            max_value_len /= 2;
+            record.max_value_len = Some(max_value_len);
+            self.logline_buffer.truncate(start);
+            self.write_record(record, encoding)?;
+        }
+        Ok(())
+    }
+
+    fn write_record(
+        &mut self,
+        record: &impl serde::ser::Serialize,
+        encoding: RecordEncoding,
+    ) -> io::Result<()> {
+        match encoding {
+            RecordEncoding::Json => {
+                record
+                    .serialize(&mut serde_json::Serializer::new(&mut self.logline_buffer))
+                    .map_err(io::Error::other)?;
+                self.logline_buffer.push(b'\n');
+            }
+            RecordEncoding::MsgPack => {
+                // Reserve the length prefix and fill it in once the record size is known.
+                let start = self.logline_buffer.len();
+                self.logline_buffer.extend_from_slice(&[0; 4]);
+                record
+                    .serialize(&mut rmp_serde::Serializer::new(&mut self.logline_buffer))
+                    .map_err(io::Error::other)?;
+                let len = u32::try_from(self.logline_buffer.len() - start - 4)
+                    .map_err(io::Error::other)?;
+                self.logline_buffer[start..start + 4].copy_from_slice(&len.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats the event as a logfmt line including a newline.
+    fn format_logfmt<S, const F: usize>(
         &mut self,
         now: DateTime<Utc>,
         event: &Event<'_>,
         ctx: &Context<'_, S>,
         skipped_field_indices: &papaya::HashMap<callsite::Identifier, SkippedFieldIndices>,
-        callsite_ids: &papaya::HashMap<callsite::Identifier, CallsiteId>,
-        extract_fields: &IndexSet<&'static str>,
+        options: &FormatOptions,
+        sampled_count: Option<u64>,
     ) -> io::Result<()>
     where
         S: Subscriber + for<'a> LookupSpan<'a>,
     {
-        let timestamp = now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+        use std::io::Write;
 
         use tracing_log::NormalizeEvent;
         let normalized_meta = event.normalized_metadata();
@@ -536,129 +1802,481 @@ fn format<S, const F: usize>(
         let skipped_field_indices = skipped_field_indices.pin();
         let skipped_field_indices = skipped_field_indices.get(&meta.callsite());
 
-        let mut serialize = || {
-            let mut serializer = serde_json::Serializer::new(&mut self.logline_buffer);
+        let buffer = &mut self.logline_buffer;
+        write!(
+            buffer,
+            "timestamp={} level={} msg=",
+            now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            meta.level().as_str(),
+        )?;
 
-            let mut serializer = serializer.serialize_map(None)?;
-
-            // Timestamp comes first, so raw lines can be sorted by timestamp.
-            serializer.serialize_entry("timestamp", &timestamp)?;
-
-            // Level next.
-            serializer.serialize_entry("level", &meta.level().as_str())?;
-
-            // Message next.
-            serializer.serialize_key("message")?;
-            let mut message_extractor =
-                MessageFieldExtractor::new(serializer, skipped_field_indices);
-            event.record(&mut message_extractor);
-            let mut serializer = message_extractor.into_serializer()?;
-
-            // Direct message fields.
-            let mut fields_present = FieldsPresent(false, skipped_field_indices);
-            event.record(&mut fields_present);
-            if fields_present.0 {
-                serializer.serialize_entry(
-                    "fields",
-                    &SerializableEventFields(event, skipped_field_indices),
-                )?;
-            }
-
-            let spans = SerializableSpans {
-                ctx,
-                callsite_ids,
-                extract: ExtractedSpanFields::<'_, F>::new(extract_fields),
-            };
-            serializer.serialize_entry("spans", &spans)?;
-
-            // TODO: thread-local cache?
-            let pid = std::process::id();
-            // Skip adding pid 1 to reduce noise for services running in containers.
-            if pid != 1 {
-                serializer.serialize_entry("process_id", &pid)?;
-            }
-
-            THREAD_ID.with(|tid| serializer.serialize_entry("thread_id", tid))?;
-
-            // TODO: tls cache? name could change
-            if let Some(thread_name) = std::thread::current().name() {
-                if !thread_name.is_empty() && thread_name != "tokio-runtime-worker" {
-                    serializer.serialize_entry("thread_name", thread_name)?;
-                }
-            }
-
-            if let Some(task_id) = tokio::task::try_id() {
-                serializer.serialize_entry("task_id", &format_args!("{task_id}"))?;
-            }
-
-            serializer.serialize_entry("target", meta.target())?;
-
-            // Skip adding module if it's the same as target.
-            if let Some(module) = meta.module_path() {
-                if module != meta.target() {
-                    serializer.serialize_entry("module", module)?;
-                }
-            }
+        let mut message_writer = LogfmtFieldWriter {
+            buffer: &mut *buffer,
+            skipped_field_indices,
+            extract_fields: &options.extract_fields,
+            float_decimals: options.float_decimals,
+            redact_fields: &options.redact_fields,
+            message: true,
+            message_written: false,
+        };
+        event.record(&mut message_writer);
+        if !message_writer.message_written {
+            push_logfmt_quoted(buffer, "");
+        }
 
-            if let Some(file) = meta.file() {
-                if let Some(line) = meta.line() {
-                    serializer.serialize_entry("src", &format_args!("{file}:{line}"))?;
-                } else {
-                    serializer.serialize_entry("src", file)?;
-                }
-            }
+        event.record(&mut LogfmtFieldWriter {
+            buffer: &mut *buffer,
+            skipped_field_indices,
+            extract_fields: &options.extract_fields,
+            float_decimals: options.float_decimals,
+            redact_fields: &options.redact_fields,
+            message: false,
+            message_written: false,
+        });
 
-            {
-                let otel_context = Span::current().context();
-                let otel_spanref = otel_context.span();
-                let span_context = otel_spanref.span_context();
-                if span_context.is_valid() {
-                    serializer.serialize_entry(
-                        "trace_id",
-                        &format_args!("{}", span_context.trace_id()),
-                    )?;
+        let extract = ExtractedSpanFields::<'_, F>::new(&options.extract_fields);
+        if let Some(leaf_span) = ctx.lookup_current() {
+            for span in leaf_span.scope().from_root() {
+                let ext = span.extensions();
+                let Some(data) = ext.get::<SpanFields>() else {
+                    continue;
+                };
+                let fields = data.fields.pin();
+                // Sorted, as the map iterates in no particular order.
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort_unstable_by_key(|(name, _)| **name);
+                for (name, value) in fields {
+                    write!(buffer, " span.{}.{name}=", span.metadata().name())?;
+                    push_logfmt_json_value(buffer, value);
+                    if let Some(slot) = extract.slot(name) {
+                        extract.set(slot, value.clone());
+                    }
                 }
             }
+        }
 
-            if spans.extract.has_values() {
-                // TODO: add fields from event, too?
-                serializer.serialize_entry("extract", &spans.extract)?;
-            }
-
-            serializer.end()
-        };
+        // Event fields are recorded after the span fields, so they take precedence.
+        event.record(&mut EventFieldsExtractor {
+            extract: &extract,
+            float_decimals: options.float_decimals,
+            redact_fields: &options.redact_fields,
+        });
+        extract.try_for_each(|name, value| {
+            write!(buffer, " {name}=")?;
+            push_logfmt_json_value(buffer, value);
+            io::Result::Ok(())
+        })?;
+
+        if let Some(sampled_count) = sampled_count {
+            write!(buffer, " sampled_count={sampled_count}")?;
+        }
+        push_logfmt_labels(buffer, &options.labels);
 
-        serialize().map_err(io::Error::other)?;
-        self.logline_buffer.push(b'\n');
+        buffer.push(b'\n');
         Ok(())
     }
 }
 
-/// Extracts the message field that's mixed will other fields.
-struct MessageFieldExtractor<'a, S: serde::ser::SerializeMap> {
-    serializer: S,
+/// Writes event fields as logfmt. Either only the message, as a quoted value, or all other fields
+/// as ` key=value` pairs.
+struct LogfmtFieldWriter<'a> {
+    buffer: &'a mut Vec<u8>,
     skipped_field_indices: Option<&'a SkippedFieldIndices>,
-    state: Option<Result<(), S::Error>>,
+    /// Not written with the other fields, but with the extracted ones at the end of the line.
+    extract_fields: &'a IndexSet<Cow<'static, str>>,
+    float_decimals: Option<u8>,
+    redact_fields: &'a RedactFields,
+    message: bool,
+    message_written: bool,
 }
 
-impl<'a, S: serde::ser::SerializeMap> MessageFieldExtractor<'a, S> {
+impl LogfmtFieldWriter<'_> {
     #[inline]
-    fn new(serializer: S, skipped_field_indices: Option<&'a SkippedFieldIndices>) -> Self {
-        Self {
-            serializer,
-            skipped_field_indices,
-            state: None,
+    fn accept_field(&self, field: &tracing::field::Field) -> bool {
+        if self
+            .skipped_field_indices
+            .is_some_and(|i| i.contains(field.index()))
+        {
+            return false;
+        }
+        if self.message {
+            !self.message_written && field.name() == MESSAGE_FIELD
+        } else {
+            field.name() != MESSAGE_FIELD
+                && !field.name().starts_with("log.")
+                && !self.extract_fields.contains(field.name())
         }
     }
 
-    #[inline]
-    fn into_serializer(mut self) -> Result<S, S::Error> {
-        match self.state {
-            Some(Ok(())) => {}
-            Some(Err(err)) => return Err(err),
-            None => self.serializer.serialize_value("")?,
+    fn write(&mut self, field: &tracing::field::Field, value: &str) {
+        if self.message {
+            push_logfmt_quoted(self.buffer, value);
+            self.message_written = true;
+        } else {
+            self.buffer.push(b' ');
+            self.buffer.extend_from_slice(field.name().as_bytes());
+            self.buffer.push(b'=');
+            if self.redact_fields.contains(field.name()) {
+                push_logfmt_value(self.buffer, &self.redact_fields.redact(value));
+            } else {
+                push_logfmt_value(self.buffer, value);
+            }
         }
-        Ok(self.serializer)
+    }
+}
+
+impl tracing::field::Visit for LogfmtFieldWriter<'_> {
+    #[inline]
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        if self.accept_field(field) {
+            let value = round_to_decimals(value, self.float_decimals);
+            self.write(field, &value.to_string());
+        }
+    }
+
+    #[inline]
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if self.accept_field(field) {
+            self.write(field, value);
+        }
+    }
+
+    #[inline]
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if self.accept_field(field) {
+            self.write(field, &format!("{value:?}"));
+        }
+    }
+
+    #[inline]
+    fn record_error(
+        &mut self,
+        field: &tracing::field::Field,
+        value: &(dyn std::error::Error + 'static),
+    ) {
+        if self.accept_field(field) {
+            self.write(field, &format!("{value}"));
+        }
+    }
+}
+
+/// Writes a logfmt value, quoted only if it's empty or contains spaces, quotes, `=` or control
+/// characters.
+fn push_logfmt_value(buffer: &mut Vec<u8>, value: &str) {
+    let needs_quotes = value.is_empty()
+        || value
+            .bytes()
+            .any(|b| b <= b' ' || b == b'"' || b == b'=' || b == b'\\' || b == 0x7f);
+    if needs_quotes {
+        push_logfmt_quoted(buffer, value);
+    } else {
+        buffer.extend_from_slice(value.as_bytes());
+    }
+}
+
+/// Writes a quoted logfmt value, escaping quotes, backslashes and control characters.
+fn push_logfmt_quoted(buffer: &mut Vec<u8>, value: &str) {
+    use std::io::Write;
+
+    buffer.push(b'"');
+    for c in value.chars() {
+        match c {
+            '"' => buffer.extend_from_slice(b"\\\""),
+            '\\' => buffer.extend_from_slice(b"\\\\"),
+            '\n' => buffer.extend_from_slice(b"\\n"),
+            '\r' => buffer.extend_from_slice(b"\\r"),
+            '\t' => buffer.extend_from_slice(b"\\t"),
+            c if c.is_control() => {
+                // Writing to a Vec can't fail.
+                let _ = write!(buffer, "\\u{:04x}", u32::from(c));
+            }
+            c => buffer.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    buffer.push(b'"');
+}
+
+/// Writes the constant labels as ` labels.<name>=value` pairs.
+fn push_logfmt_labels(buffer: &mut Vec<u8>, labels: &BTreeMap<&'static str, String>) {
+    for (name, value) in labels {
+        buffer.extend_from_slice(b" labels.");
+        buffer.extend_from_slice(name.as_bytes());
+        buffer.push(b'=');
+        push_logfmt_value(buffer, value);
+    }
+}
+
+/// Writes a span field value: strings as they are, other values in their JSON form.
+fn push_logfmt_json_value(buffer: &mut Vec<u8>, value: &serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => push_logfmt_value(buffer, s),
+        value => push_logfmt_value(buffer, &value.to_string()),
+    }
+}
+
+/// Serializes an event with all its metadata as a map. The encoding is up to the serializer.
+struct SerializableEvent<'a, 'ctx, S, const F: usize>
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    timestamp: &'a Timestamp,
+    meta: &'a Metadata<'a>,
+    event: &'a Event<'a>,
+    ctx: &'a Context<'ctx, S>,
+    skipped_field_indices: Option<&'a SkippedFieldIndices>,
+    callsite_ids: &'a papaya::HashMap<callsite::Identifier, CallsiteId>,
+    options: &'a FormatOptions,
+    /// Length string field values are truncated to, if the line is too long otherwise.
+    max_value_len: Option<usize>,
+    /// Number of events of the same callsite sampled out since the previous logged one.
+    sampled_count: Option<u64>,
+}
+
+impl<S, const F: usize> serde::ser::Serialize for SerializableEvent<'_, '_, S, F>
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::ser::Serializer,
+    {
+        let mut serializer = serializer.serialize_map(None)?;
+
+        // Timestamp comes first, so raw lines can be sorted by timestamp.
+        serializer.serialize_entry("timestamp", self.timestamp)?;
+
+        // Level next.
+        serializer.serialize_entry("level", &self.meta.level().as_str())?;
+
+        // Message next.
+        serializer.serialize_key("message")?;
+        let mut message_extractor = MessageFieldExtractor::new(
+            serializer,
+            self.skipped_field_indices,
+            self.options.error_chains,
+        );
+        self.event.record(&mut message_extractor);
+        let mut serializer = message_extractor.into_serializer()?;
+
+        // Direct message fields.
+        let mut fields_present = FieldsPresent(false, self.skipped_field_indices);
+        self.event.record(&mut fields_present);
+        if fields_present.0 {
+            serializer.serialize_entry(
+                "fields",
+                &SerializableEventFields(
+                    self.event,
+                    self.skipped_field_indices,
+                    self.options.float_decimals,
+                    self.max_value_len,
+                    &self.options.redact_fields,
+                    self.options.error_chains,
+                ),
+            )?;
+        }
+
+        let spans = SerializableSpans {
+            ctx: self.ctx,
+            callsite_ids: self.callsite_ids,
+            extract: ExtractedSpanFields {
+                max_value_len: self.max_value_len,
+                ..ExtractedSpanFields::<'_, F>::new(&self.options.extract_fields)
+            },
+            as_array: self.options.spans_format == SpansFormat::Array,
+            max_value_len: self.max_value_len,
+        };
+        if self.options.spans_format == SpansFormat::Flat {
+            for (key, value) in &spans.flatten() {
+                serializer.serialize_entry(key, &TruncatedValue(value, self.max_value_len))?;
+            }
+        } else {
+            serializer.serialize_entry("spans", &spans)?;
+        }
+
+        // TODO: thread-local cache?
+        let pid = std::process::id();
+        // Skip adding pid 1 to reduce noise for services running in containers.
+        if self.options.log_fields.process_id && pid != 1 {
+            serializer.serialize_entry("process_id", &pid)?;
+        }
+
+        if self.options.log_fields.thread_id {
+            THREAD_ID.with(|tid| serializer.serialize_entry("thread_id", tid))?;
+        }
+
+        if self.options.log_fields.thread_name {
+            THREAD_NAME.with(|thread_name| match thread_name {
+                Some(thread_name) => serializer.serialize_entry("thread_name", thread_name),
+                None => Ok(()),
+            })?;
+        }
+
+        if let Some(task_id) = tokio::task::try_id() {
+            serializer.serialize_entry("task_id", &format_args!("{task_id}"))?;
+        }
+
+        if self.options.log_fields.target {
+            serializer.serialize_entry("target", self.meta.target())?;
+        }
+
+        // Skip adding module if it's the same as target.
+        if self.options.log_fields.module {
+            if let Some(module) = self.meta.module_path() {
+                if module != self.meta.target() {
+                    serializer.serialize_entry("module", module)?;
+                }
+            }
+        }
+
+        if self.options.log_fields.src {
+            if let Some(file) = self.meta.file() {
+                if let Some(line) = self.meta.line() {
+                    serializer.serialize_entry("src", &format_args!("{file}:{line}"))?;
+                } else {
+                    serializer.serialize_entry("src", file)?;
+                }
+            }
+        }
+
+        {
+            let otel_context = Span::current().context();
+            let otel_spanref = otel_context.span();
+            let span_context = otel_spanref.span_context();
+            if span_context.is_valid() {
+                serializer
+                    .serialize_entry("trace_id", &format_args!("{}", span_context.trace_id()))?;
+                serializer
+                    .serialize_entry("span_id", &format_args!("{}", span_context.span_id()))?;
+            } else if let Some(field) = self.options.fallback_trace_id {
+                if let Some(trace_id) = FallbackTraceId::lookup(self.ctx, field) {
+                    serializer.serialize_entry("trace_id", &trace_id)?;
+                }
+            }
+        }
+
+        // Event fields are recorded after the span fields, so they take precedence.
+        self.event.record(&mut EventFieldsExtractor {
+            extract: &spans.extract,
+            float_decimals: self.options.float_decimals,
+            redact_fields: &self.options.redact_fields,
+        });
+        if spans.extract.has_values() {
+            serializer.serialize_entry("extract", &spans.extract)?;
+        }
+
+        if !self.options.labels.is_empty() {
+            serializer.serialize_entry("labels", &self.options.labels)?;
+        }
+
+        if let Some(sampled_count) = self.sampled_count {
+            serializer.serialize_entry("sampled_count", &sampled_count)?;
+        }
+
+        if self.max_value_len.is_some() {
+            serializer.serialize_entry("truncated", &true)?;
+        }
+
+        serializer.end()
+    }
+}
+
+/// Time a span was opened at, stored as span extension if span close events are enabled.
+#[derive(Clone, Copy)]
+struct SpanStart(Instant);
+
+/// The line logged when a span closes. Carries the extracted fields of the span and its parents,
+/// to correlate it with the events of the span.
+struct SerializableSpanClose<'a, const F: usize> {
+    timestamp: &'a Timestamp,
+    fields: SpanCloseFields,
+    extract: &'a ExtractedSpanFields<'a, F>,
+    labels: &'a BTreeMap<&'static str, String>,
+}
+
+#[derive(serde::Serialize)]
+struct SpanCloseFields {
+    span: &'static str,
+    duration_ms: f64,
+}
+
+impl<const F: usize> serde::ser::Serialize for SerializableSpanClose<'_, F> {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::ser::Serializer,
+    {
+        let mut serializer = serializer.serialize_map(None)?;
+        serializer.serialize_entry("timestamp", self.timestamp)?;
+        serializer.serialize_entry("level", "INFO")?;
+        serializer.serialize_entry("message", "span closed")?;
+        serializer.serialize_entry("fields", &self.fields)?;
+        if self.extract.has_values() {
+            serializer.serialize_entry("extract", self.extract)?;
+        }
+        if !self.labels.is_empty() {
+            serializer.serialize_entry("labels", self.labels)?;
+        }
+        serializer.end()
+    }
+}
+
+/// Correlation ID for logs without OTel. Events get the value of the configured span field from
+/// the innermost span that has it, e.g. `request_id`. Outside of such spans, they get an ID that's
+/// generated for, and stored as extension of, their root span.
+#[derive(Clone, Copy)]
+struct FallbackTraceId(u128);
+
+impl FallbackTraceId {
+    fn lookup<S>(ctx: &Context<'_, S>, field: &'static str) -> Option<serde_json::Value>
+    where
+        S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        let leaf_span = ctx.lookup_current()?;
+        let mut root_id = None;
+        for span in leaf_span.scope() {
+            let ext = span.extensions();
+            if let Some(value) = ext
+                .get::<SpanFields>()
+                .and_then(|data| data.fields.pin().get(field).cloned())
+            {
+                return Some(value);
+            }
+            root_id = ext.get::<FallbackTraceId>().copied().or(root_id);
+        }
+        root_id.map(|FallbackTraceId(id)| serde_json::Value::from(format!("{id:032x}")))
+    }
+}
+
+/// Extracts the message field that's mixed will other fields.
+struct MessageFieldExtractor<'a, S: serde::ser::SerializeMap> {
+    serializer: S,
+    skipped_field_indices: Option<&'a SkippedFieldIndices>,
+    error_chains: bool,
+    state: Option<Result<(), S::Error>>,
+}
+
+impl<'a, S: serde::ser::SerializeMap> MessageFieldExtractor<'a, S> {
+    #[inline]
+    fn new(
+        serializer: S,
+        skipped_field_indices: Option<&'a SkippedFieldIndices>,
+        error_chains: bool,
+    ) -> Self {
+        Self {
+            serializer,
+            skipped_field_indices,
+            error_chains,
+            state: None,
+        }
+    }
+
+    #[inline]
+    fn into_serializer(mut self) -> Result<S, S::Error> {
+        match self.state {
+            Some(Ok(())) => {}
+            Some(Err(err)) => return Err(err),
+            None => self.serializer.serialize_value("")?,
+        }
+        Ok(self.serializer)
     }
 
     #[inline]
@@ -742,7 +2360,11 @@ fn record_error(
         value: &(dyn std::error::Error + 'static),
     ) {
         if self.accept_field(field) {
-            self.state = Some(self.serializer.serialize_value(&format_args!("{value}")));
+            self.state = Some(if self.error_chains {
+                self.serializer.serialize_value(&ErrorChain(value, None))
+            } else {
+                self.serializer.serialize_value(&format_args!("{value}"))
+            });
         }
     }
 }
@@ -771,6 +2393,10 @@ fn record_debug(&mut self, field: &tracing::field::Field, _: &dyn std::fmt::Debu
 struct SerializableEventFields<'a, 'event>(
     &'a tracing::Event<'event>,
     Option<&'a SkippedFieldIndices>,
+    Option<u8>,
+    Option<usize>,
+    &'a RedactFields,
+    bool,
 );
 
 impl serde::ser::Serialize for SerializableEventFields<'_, '_> {
@@ -780,7 +2406,8 @@ fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     {
         use serde::ser::SerializeMap;
         let serializer = serializer.serialize_map(None)?;
-        let mut message_skipper = MessageFieldSkipper::new(serializer, self.1);
+        let mut message_skipper =
+            MessageFieldSkipper::new(serializer, self.1, self.2, self.3, self.4, self.5);
         self.0.record(&mut message_skipper);
         let serializer = message_skipper.into_serializer()?;
         serializer.end()
@@ -791,19 +2418,46 @@ fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
 struct MessageFieldSkipper<'a, S: serde::ser::SerializeMap> {
     serializer: S,
     skipped_field_indices: Option<&'a SkippedFieldIndices>,
+    float_decimals: Option<u8>,
+    max_value_len: Option<usize>,
+    redact_fields: &'a RedactFields,
+    error_chains: bool,
     state: Result<(), S::Error>,
 }
 
 impl<'a, S: serde::ser::SerializeMap> MessageFieldSkipper<'a, S> {
     #[inline]
-    fn new(serializer: S, skipped_field_indices: Option<&'a SkippedFieldIndices>) -> Self {
+    fn new(
+        serializer: S,
+        skipped_field_indices: Option<&'a SkippedFieldIndices>,
+        float_decimals: Option<u8>,
+        max_value_len: Option<usize>,
+        redact_fields: &'a RedactFields,
+        error_chains: bool,
+    ) -> Self {
         Self {
             serializer,
             skipped_field_indices,
+            float_decimals,
+            max_value_len,
+            redact_fields,
+            error_chains,
             state: Ok(()),
         }
     }
 
+    /// Serializes the redacted value if the field is to be [redacted](RedactFields). Returns
+    /// whether it was.
+    #[inline]
+    fn redacted(&mut self, field: &tracing::field::Field, value: fmt::Arguments<'_>) -> bool {
+        if !self.redact_fields.contains(field.name()) {
+            return false;
+        }
+        let value = self.redact_fields.redact(&value.to_string());
+        self.state = self.serializer.serialize_entry(field.name(), &value);
+        true
+    }
+
     #[inline]
     fn accept_field(&self, field: &tracing::field::Field) -> bool {
         self.state.is_ok()
@@ -824,68 +2478,73 @@ fn into_serializer(self) -> Result<S, S::Error> {
 impl<S: serde::ser::SerializeMap> tracing::field::Visit for MessageFieldSkipper<'_, S> {
     #[inline]
     fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
-        if self.accept_field(field) {
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
+            let value = round_to_decimals(value, self.float_decimals);
             self.state = self.serializer.serialize_entry(field.name(), &value);
         }
     }
 
     #[inline]
     fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
-        if self.accept_field(field) {
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
             self.state = self.serializer.serialize_entry(field.name(), &value);
         }
     }
 
     #[inline]
     fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
-        if self.accept_field(field) {
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
             self.state = self.serializer.serialize_entry(field.name(), &value);
         }
     }
 
     #[inline]
     fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
-        if self.accept_field(field) {
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
             self.state = self.serializer.serialize_entry(field.name(), &value);
         }
     }
 
     #[inline]
     fn record_u128(&mut self, field: &tracing::field::Field, value: u128) {
-        if self.accept_field(field) {
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
             self.state = self.serializer.serialize_entry(field.name(), &value);
         }
     }
 
     #[inline]
     fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
-        if self.accept_field(field) {
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
             self.state = self.serializer.serialize_entry(field.name(), &value);
         }
     }
 
     #[inline]
     fn record_bytes(&mut self, field: &tracing::field::Field, value: &[u8]) {
-        if self.accept_field(field) {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &format_args!("{value:x?}"));
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value:x?}")) {
+            self.state = self.serializer.serialize_entry(
+                field.name(),
+                &TruncatedStr(format_args!("{value:x?}"), self.max_value_len),
+            );
         }
     }
 
     #[inline]
     fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-        if self.accept_field(field) {
-            self.state = self.serializer.serialize_entry(field.name(), &value);
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
+            self.state = self
+                .serializer
+                .serialize_entry(field.name(), truncate_str(value, self.max_value_len));
         }
     }
 
     #[inline]
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
-        if self.accept_field(field) {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &format_args!("{value:?}"));
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value:?}")) {
+            self.state = self.serializer.serialize_entry(
+                field.name(),
+                &TruncatedStr(format_args!("{value:?}"), self.max_value_len),
+            );
         }
     }
 
@@ -895,16 +2554,94 @@ fn record_error(
         field: &tracing::field::Field,
         value: &(dyn std::error::Error + 'static),
     ) {
-        if self.accept_field(field) {
-            self.state = self.serializer.serialize_value(&format_args!("{value}"));
+        if self.accept_field(field) && !self.redacted(field, format_args!("{value}")) {
+            self.state = if self.error_chains {
+                self.serializer
+                    .serialize_entry(field.name(), &ErrorChain(value, self.max_value_len))
+            } else {
+                self.serializer.serialize_entry(
+                    field.name(),
+                    &TruncatedStr(format_args!("{value}"), self.max_value_len),
+                )
+            };
+        }
+    }
+}
+
+/// Truncates `value` to at most `max_len` bytes, at a char boundary.
+#[inline]
+fn truncate_str(value: &str, max_len: Option<usize>) -> &str {
+    match max_len {
+        Some(max_len) if value.len() > max_len => {
+            let mut end = max_len;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            &value[..end]
+        }
+        _ => value,
+    }
+}
+
+/// Serializes formatted arguments as a string, truncated like [`truncate_str`].
+struct TruncatedStr<'a>(fmt::Arguments<'a>, Option<usize>);
+
+impl serde::ser::Serialize for TruncatedStr<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.1 {
+            None => serializer.collect_str(&self.0),
+            Some(_) => serializer.serialize_str(truncate_str(&self.0.to_string(), self.1)),
+        }
+    }
+}
+
+/// Serializes an error and its sources, down to the root cause, as array of messages truncated
+/// like [`truncate_str`].
+struct ErrorChain<'a>(&'a (dyn std::error::Error + 'static), Option<usize>);
+
+impl serde::ser::Serialize for ErrorChain<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeSeq;
+
+        let mut serializer = serializer.serialize_seq(None)?;
+        let mut error = Some(self.0);
+        while let Some(err) = error {
+            serializer.serialize_element(&TruncatedStr(format_args!("{err}"), self.1))?;
+            error = err.source();
+        }
+        serializer.end()
+    }
+}
+
+/// Serializes a recorded field value, with strings truncated like [`truncate_str`].
+struct TruncatedValue<'a>(&'a serde_json::Value, Option<usize>);
+
+impl serde::ser::Serialize for TruncatedValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            serde_json::Value::String(value) => {
+                serializer.serialize_str(truncate_str(value, self.1))
+            }
+            value => serde::ser::Serialize::serialize(value, serializer),
         }
     }
 }
 
 /// Serializes the span stack from root to leaf (parent of event) as object
 /// with the span names as keys. To prevent collision we append a numberic value
-/// to the name. Also, collects any span fields we're interested in. Last one
-/// wins.
+/// to the name. Each non-root span records the key of its parent as `_parent`.
+/// With `as_array`, serializes it as an array of `{ name, id, fields }` objects
+/// instead, where `id` is the numeric value and the order gives the parents.
+/// Also, collects any span fields we're interested in. Last one wins.
 struct SerializableSpans<'a, 'ctx, Span, const F: usize>
 where
     Span: Subscriber + for<'lookup> LookupSpan<'lookup>,
@@ -912,35 +2649,64 @@ struct SerializableSpans<'a, 'ctx, Span, const F: usize>
     ctx: &'a Context<'ctx, Span>,
     callsite_ids: &'a papaya::HashMap<callsite::Identifier, CallsiteId>,
     extract: ExtractedSpanFields<'a, F>,
+    as_array: bool,
+    max_value_len: Option<usize>,
 }
 
-impl<Span, const F: usize> serde::ser::Serialize for SerializableSpans<'_, '_, Span, F>
+impl<Span, const F: usize> SerializableSpans<'_, '_, Span, F>
 where
     Span: Subscriber + for<'lookup> LookupSpan<'lookup>,
 {
-    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    #[inline]
+    fn callsite_id(&self, span: &SpanRef<'_, Span>) -> CallsiteId {
+        self.callsite_ids
+            .pin()
+            .get(&span.metadata().callsite())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The span fields keyed by `span.<name>.<field>`, for [`SpansFormat::Flat`]. Last one wins.
+    fn flatten(&self) -> IndexMap<String, serde_json::Value> {
+        let mut fields = IndexMap::new();
+        if let Some(leaf_span) = self.ctx.lookup_current() {
+            for span in leaf_span.scope().from_root() {
+                let ext = span.extensions();
+                if let Some(data) = ext.get::<SpanFields>() {
+                    for (name, value) in &data.fields.pin() {
+                        fields.insert(
+                            format!("span.{}.{name}", span.metadata().name()),
+                            value.clone(),
+                        );
+                        if let Some(slot) = self.extract.slot(name) {
+                            self.extract.set(slot, value.clone());
+                        }
+                    }
+                }
+            }
+        }
+        fields
+    }
+
+    fn serialize_array<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
     where
         Ser: serde::ser::Serializer,
     {
-        let mut serializer = serializer.serialize_map(None)?;
+        use serde::ser::SerializeSeq;
+
+        let mut serializer = serializer.serialize_seq(None)?;
 
         if let Some(leaf_span) = self.ctx.lookup_current() {
             for span in leaf_span.scope().from_root() {
-                // Append a numeric callsite ID to the span name to keep the name unique
-                // in the JSON object.
-                let cid = self
-                    .callsite_ids
-                    .pin()
-                    .get(&span.metadata().callsite())
-                    .copied()
-                    .unwrap_or_default();
-
-                // Loki turns the # into an underscore during field name concatenation.
-                serializer.serialize_key(&format_args!("{}#{}", span.metadata().name(), &cid))?;
-
-                serializer.serialize_value(&SerializableSpanFields {
-                    span: &span,
-                    extract: &self.extract,
+                serializer.serialize_element(&SerializableSpanEntry {
+                    name: span.metadata().name(),
+                    id: self.callsite_id(&span).0,
+                    fields: SerializableSpanFields {
+                        span: &span,
+                        parent: None,
+                        extract: &self.extract,
+                        max_value_len: self.max_value_len,
+                    },
                 })?;
             }
         }
@@ -949,13 +2715,67 @@ fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
     }
 }
 
-/// Serializes the span fields as object.
-struct SerializableSpanFields<'a, 'span, Span, const F: usize>
+impl<Span, const F: usize> serde::ser::Serialize for SerializableSpans<'_, '_, Span, F>
 where
-    Span: for<'lookup> LookupSpan<'lookup>,
+    Span: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::ser::Serializer,
+    {
+        if self.as_array {
+            return self.serialize_array(serializer);
+        }
+
+        let mut serializer = serializer.serialize_map(None)?;
+
+        if let Some(leaf_span) = self.ctx.lookup_current() {
+            let mut parent = None;
+            for span in leaf_span.scope().from_root() {
+                // Append a numeric callsite ID to the span name to keep the name unique
+                // in the JSON object.
+                let cid = self.callsite_id(&span);
+
+                // Loki turns the # into an underscore during field name concatenation.
+                serializer.serialize_key(&format_args!("{}#{}", span.metadata().name(), &cid))?;
+
+                serializer.serialize_value(&SerializableSpanFields {
+                    span: &span,
+                    parent,
+                    extract: &self.extract,
+                    max_value_len: self.max_value_len,
+                })?;
+
+                parent = Some((span.metadata().name(), cid));
+            }
+        }
+
+        serializer.end()
+    }
+}
+
+/// One span in the array form of [`SerializableSpans`].
+#[derive(serde::Serialize)]
+#[serde(bound = "")]
+struct SerializableSpanEntry<'a, 'span, Span, const F: usize>
+where
+    Span: for<'lookup> LookupSpan<'lookup>,
+{
+    name: &'static str,
+    id: u32,
+    fields: SerializableSpanFields<'a, 'span, Span, F>,
+}
+
+/// Serializes the span fields as object.
+struct SerializableSpanFields<'a, 'span, Span, const F: usize>
+where
+    Span: for<'lookup> LookupSpan<'lookup>,
 {
     span: &'a SpanRef<'span, Span>,
+    /// Name and callsite ID of the parent span, which make up its key.
+    parent: Option<(&'static str, CallsiteId)>,
     extract: &'a ExtractedSpanFields<'a, F>,
+    max_value_len: Option<usize>,
 }
 
 impl<Span, const F: usize> serde::ser::Serialize for SerializableSpanFields<'_, '_, Span, F>
@@ -968,12 +2788,18 @@ fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     {
         let mut serializer = serializer.serialize_map(None)?;
 
+        if let Some((name, cid)) = self.parent {
+            serializer.serialize_entry("_parent", &format_args!("{name}#{cid}"))?;
+        }
+
         let ext = self.span.extensions();
         if let Some(data) = ext.get::<SpanFields>() {
             for (name, value) in &data.fields.pin() {
-                serializer.serialize_entry(name, value)?;
-                // TODO: replace clone with reference, if possible.
-                self.extract.set(name, value.clone());
+                serializer.serialize_entry(name, &TruncatedValue(value, self.max_value_len))?;
+                if let Some(slot) = self.extract.slot(name) {
+                    // TODO: replace clone with reference, if possible.
+                    self.extract.set(slot, value.clone());
+                }
             }
         }
 
@@ -982,32 +2808,135 @@ fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
 }
 
 struct ExtractedSpanFields<'a, const F: usize> {
-    names: &'a IndexSet<&'static str>,
+    names: &'a IndexSet<Cow<'static, str>>,
     // TODO: replace TryLock with something local thread and interior mutability.
     //       serde API doesn't let us use `mut`.
-    values: TryLock<([Option<serde_json::Value>; F], bool)>,
+    values: TryLock<ExtractedValues<F>>,
+    /// Length string values are truncated to when serialized.
+    max_value_len: Option<usize>,
 }
 
 impl<'a, const F: usize> ExtractedSpanFields<'a, F> {
-    fn new(names: &'a IndexSet<&'static str>) -> Self {
+    fn new(names: &'a IndexSet<Cow<'static, str>>) -> Self {
         ExtractedSpanFields {
             names,
-            values: TryLock::new((array::from_fn(|_| Option::default()), false)),
+            values: TryLock::new(ExtractedValues {
+                inline: array::from_fn(|_| Option::default()),
+                overflow: vec![None; names.len().saturating_sub(F)],
+                has_values: false,
+            }),
+            max_value_len: None,
         }
     }
 
+    /// The slot to [store](Self::set) values of the field `name` in, if it is extracted.
+    #[inline]
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.names.get_index_of(name)
+    }
+
+    /// Stores the value of the field in `index`, as returned by [`Self::slot`]. Every extracted
+    /// name has a slot: beyond the first `F`, values are stored in the overflow.
     #[inline]
-    fn set(&self, name: &'static str, value: serde_json::Value) {
-        if let Some((index, _)) = self.names.get_full(name) {
-            let mut fields = self.values.try_lock().expect("thread-local use");
-            fields.0[index] = Some(value);
-            fields.1 = true;
+    fn set(&self, index: usize, value: serde_json::Value) {
+        let mut values = self.values.try_lock().expect("thread-local use");
+        if index < F {
+            values.inline[index] = Some(value);
+        } else {
+            values.overflow[index - F] = Some(value);
         }
+        values.has_values = true;
     }
 
     #[inline]
     fn has_values(&self) -> bool {
-        self.values.try_lock().expect("thread-local use").1
+        self.values.try_lock().expect("thread-local use").has_values
+    }
+
+    /// Calls `f` with the name and value of every field that has a value, in the order of the
+    /// names.
+    fn try_for_each<E>(
+        &self,
+        mut f: impl FnMut(&str, &serde_json::Value) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let values = self.values.try_lock().expect("thread-local use");
+        let values = values.inline.iter().chain(&values.overflow);
+        for (name, value) in self.names.iter().zip(values) {
+            if let Some(value) = value {
+                f(name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Values of [`ExtractedSpanFields`], by index of their name.
+struct ExtractedValues<const F: usize> {
+    inline: [Option<serde_json::Value>; F],
+    /// Values of the names beyond the first `F`. Empty, and so not allocated, unless field names
+    /// were added at runtime.
+    overflow: Vec<Option<serde_json::Value>>,
+    has_values: bool,
+}
+
+/// Feeds the event fields matching the extracted field names into [`ExtractedSpanFields`].
+struct EventFieldsExtractor<'a, const F: usize> {
+    extract: &'a ExtractedSpanFields<'a, F>,
+    float_decimals: Option<u8>,
+    redact_fields: &'a RedactFields,
+}
+
+impl<const F: usize> EventFieldsExtractor<'_, F> {
+    #[inline]
+    fn record(&self, field: &tracing::field::Field, value: impl FnOnce() -> serde_json::Value) {
+        if let Some(slot) = self.extract.slot(field.name()) {
+            let value = self.redact_fields.redact_value(field.name(), value());
+            self.extract.set(slot, value);
+        }
+    }
+}
+
+impl<const F: usize> tracing::field::Visit for EventFieldsExtractor<'_, F> {
+    #[inline]
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        let float_decimals = self.float_decimals;
+        self.record(field, || {
+            serde_json::Value::from(round_to_decimals(value, float_decimals))
+        });
+    }
+
+    #[inline]
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.record(field, || serde_json::Value::from(value));
+    }
+
+    #[inline]
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.record(field, || serde_json::Value::from(value));
+    }
+
+    #[inline]
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.record(field, || serde_json::Value::from(value));
+    }
+
+    #[inline]
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.record(field, || serde_json::Value::from(value));
+    }
+
+    #[inline]
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.record(field, || serde_json::Value::from(format!("{value:?}")));
+    }
+
+    #[inline]
+    fn record_error(
+        &mut self,
+        field: &tracing::field::Field,
+        value: &(dyn std::error::Error + 'static),
+    ) {
+        self.record(field, || serde_json::Value::from(format!("{value}")));
     }
 }
 
@@ -1018,53 +2947,108 @@ fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     {
         let mut serializer = serializer.serialize_map(None)?;
 
-        let values = self.values.try_lock().expect("thread-local use");
-        for (i, value) in values.0.iter().enumerate() {
-            if let Some(value) = value {
-                let key = self.names[i];
-                serializer.serialize_entry(key, value)?;
-            }
-        }
+        self.try_for_each(|name, value| {
+            serializer.serialize_entry(name, &TruncatedValue(value, self.max_value_len))
+        })?;
 
         serializer.end()
     }
 }
 
+/// Helpers to assert on the output of the logging layers.
 #[cfg(test)]
-mod tests {
-    use std::marker::PhantomData;
+pub(crate) mod test_util {
+    use std::io;
     use std::sync::{Arc, Mutex, MutexGuard};
+    use std::time::Instant;
 
-    use assert_json_diff::assert_json_eq;
-    use tracing::info_span;
+    use chrono::{DateTime, Utc};
+    use tracing_subscriber::layer::Layer;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::registry::Registry;
 
-    use super::*;
+    use super::{Clock, MakeWriter};
 
-    struct TestClock {
+    /// A [`Clock`] that stays at the time it's set to.
+    pub(crate) struct TestClock {
         current_time: Mutex<DateTime<Utc>>,
+        /// The time the clock was created at, and the instant it maps to.
+        start: (DateTime<Utc>, Instant),
+    }
+
+    impl TestClock {
+        /// A clock set to the current time.
+        pub(crate) fn new() -> Arc<Self> {
+            Self::at(Utc::now())
+        }
+
+        pub(crate) fn at(now: DateTime<Utc>) -> Arc<Self> {
+            Arc::new(TestClock {
+                current_time: Mutex::new(now),
+                start: (now, Instant::now()),
+            })
+        }
+
+        pub(crate) fn set(&self, now: DateTime<Utc>) {
+            *self.current_time.lock().expect("poisoned") = now;
+        }
     }
 
     impl Clock for Arc<TestClock> {
         fn now(&self) -> DateTime<Utc> {
             *self.current_time.lock().expect("poisoned")
         }
+
+        /// Follows [`TestClock::set`], but never before the time the clock was created at.
+        fn instant(&self) -> Instant {
+            let (start_time, start_instant) = self.start;
+            let elapsed = (self.now() - start_time).to_std().unwrap_or_default();
+            start_instant + elapsed
+        }
     }
 
-    struct VecWriter<'a> {
-        buffer: MutexGuard<'a, Vec<u8>>,
+    /// Captures the lines written by a layer, to assert on them as parsed JSON or as text.
+    #[derive(Clone, Default)]
+    pub(crate) struct CapturingWriter {
+        buffer: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl CapturingWriter {
+        /// The lines written so far. Panics if one of them isn't valid JSON.
+        pub(crate) fn lines(&self) -> Vec<serde_json::Value> {
+            self.text()
+                .lines()
+                .map(|line| serde_json::from_str(line).expect("valid JSON line"))
+                .collect()
+        }
+
+        /// Everything written so far. Panics if it isn't valid UTF-8.
+        pub(crate) fn text(&self) -> String {
+            String::from_utf8(self.bytes()).expect("valid UTF-8")
+        }
+
+        /// Everything written so far, e.g. for binary encodings.
+        pub(crate) fn bytes(&self) -> Vec<u8> {
+            self.buffer.lock().expect("poisoned").clone()
+        }
     }
 
-    impl MakeWriter for Arc<Mutex<Vec<u8>>> {
+    impl MakeWriter for CapturingWriter {
         fn make_writer(&self) -> impl io::Write {
-            VecWriter {
-                buffer: self.lock().expect("poisoned"),
+            CapturingWriterGuard {
+                buffer: self.buffer.lock().expect("poisoned"),
             }
         }
     }
 
-    impl io::Write for VecWriter<'_> {
+    struct CapturingWriterGuard<'a> {
+        buffer: MutexGuard<'a, Vec<u8>>,
+    }
+
+    impl io::Write for CapturingWriterGuard<'_> {
         fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-            self.buffer.write(buf)
+            self.buffer.extend_from_slice(buf);
+            Ok(buf.len())
         }
 
         fn flush(&mut self) -> io::Result<()> {
@@ -1072,42 +3056,55 @@ fn flush(&mut self) -> io::Result<()> {
         }
     }
 
-    #[test]
-    fn test_field_collection() {
-        let clock = Arc::new(TestClock {
-            current_time: Mutex::new(Utc::now()),
-        });
-        let buffer = Arc::new(Mutex::new(Vec::new()));
-        let log_layer = JsonLoggingLayer {
-            clock: clock.clone(),
-            skipped_field_indices: papaya::HashMap::default(),
-            callsite_ids: papaya::HashMap::default(),
-            writer: buffer.clone(),
-            extract_fields: IndexSet::from_iter(["x"]),
-            _marker: PhantomData::<[&'static str; 1]>,
-        };
+    /// Logs the events of `f` with the layer `make_layer` returns for a [`CapturingWriter`], and
+    /// returns the writer with what the layer wrote.
+    pub(crate) fn capture<L>(
+        make_layer: impl FnOnce(CapturingWriter) -> L,
+        f: impl FnOnce(),
+    ) -> CapturingWriter
+    where
+        L: Layer<Registry> + Send + Sync + 'static,
+    {
+        let writer = CapturingWriter::default();
+        let registry = Registry::default().with(make_layer(writer.clone()));
+        tracing::subscriber::with_default(registry, f);
+        writer
+    }
+}
 
-        let registry = tracing_subscriber::Registry::default().with(log_layer);
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Mutex};
 
-        tracing::subscriber::with_default(registry, || {
-            info_span!("some_span", x = 24).in_scope(|| {
-                info_span!("some_span", x = 40, x = 41, x = 42).in_scope(|| {
-                    tracing::error!(
-                        a = 1,
-                        a = 2,
-                        a = 3,
-                        message = "explicit message field",
-                        "implicit message field"
-                    );
+    use assert_json_diff::assert_json_eq;
+    use tracing::info_span;
+
+    use super::test_util::{CapturingWriter, TestClock, capture};
+    use super::*;
+
+    #[test]
+    fn test_field_collection() {
+        let clock = TestClock::new();
+        let lines = capture(
+            |writer| JsonLoggingLayer::new(clock.clone(), writer, ["x"], FormatOptions::default()),
+            || {
+                info_span!("some_span", x = 24).in_scope(|| {
+                    info_span!("some_span", x = 40, x = 41, x = 42).in_scope(|| {
+                        tracing::error!(
+                            a = 1,
+                            a = 2,
+                            a = 3,
+                            message = "explicit message field",
+                            "implicit message field"
+                        );
+                    });
                 });
-            });
-        });
+            },
+        )
+        .lines();
 
-        let buffer = Arc::try_unwrap(buffer)
-            .expect("no other reference")
-            .into_inner()
-            .expect("poisoned");
-        let actual: serde_json::Value = serde_json::from_slice(&buffer).expect("valid JSON");
+        let actual = &lines[0];
         let expected: serde_json::Value = serde_json::json!(
             {
                 "timestamp": clock.now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
@@ -1121,6 +3118,7 @@ fn test_field_collection() {
                         "x": 24,
                     },
                     "some_span#2": {
+                        "_parent": "some_span#1",
                         "x": 42,
                     }
                 },
@@ -1137,4 +3135,1500 @@ fn test_field_collection() {
 
         assert_json_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_redact_fields() {
+        let writer = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["password"],
+                    FormatOptions {
+                        redact_fields: RedactFields::new(["password", "token"]),
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("connect", password = "hunter2", user = "alice").in_scope(|| {
+                    tracing::info!(token = 1234567, token_id = 5, "connected");
+                });
+            },
+        );
+
+        let line = writer.text();
+        assert!(!line.contains("hunter2"));
+        assert!(!line.contains("1234567"));
+
+        let actual = &writer.lines()[0];
+        assert_eq!(
+            actual["fields"],
+            serde_json::json!({ "token": "[redacted]", "token_id": 5 })
+        );
+        let span = actual["spans"]
+            .as_object()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap();
+        assert_eq!(span["password"], "[redacted]");
+        assert_eq!(span["user"], "alice");
+        assert_eq!(
+            actual["extract"],
+            serde_json::json!({ "password": "[redacted]" })
+        );
+    }
+
+    #[test]
+    fn test_partial_redaction() {
+        let redact_fields = RedactFields::new(["token"]).with_keep_chars(2);
+        assert_eq!(redact_fields.redact("abcdefgh"), "ab[redacted]gh");
+        // Too short to keep anything without giving the value away.
+        assert_eq!(redact_fields.redact("abcd"), "[redacted]");
+        assert_eq!(
+            redact_fields.redact_value("token", serde_json::Value::from(123_456)),
+            "12[redacted]56"
+        );
+        assert_eq!(
+            redact_fields.redact_value("other", serde_json::Value::from(123_456)),
+            123_456
+        );
+    }
+
+    #[test]
+    fn test_sampling() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(TestClock::new(), writer, [], FormatOptions::default())
+                    .with_sample_rates(SampleRates::new([
+                        ("proxy", 2),
+                        ("proxy::logging::tests", 3),
+                        ("other", 100),
+                    ]))
+            },
+            || {
+                for i in 0..7 {
+                    tracing::info!(i, "sampled");
+                    tracing::error!(i, "never sampled");
+                }
+            },
+        )
+        .lines();
+
+        let sampled: Vec<_> = lines
+            .iter()
+            .filter(|line| line["message"] == "sampled")
+            .map(|line| (line["fields"]["i"].clone(), line["sampled_count"].clone()))
+            .collect();
+        assert_eq!(
+            sampled,
+            [
+                (serde_json::json!(0), serde_json::json!(0)),
+                (serde_json::json!(3), serde_json::json!(2)),
+                (serde_json::json!(6), serde_json::json!(2)),
+            ]
+        );
+
+        let errors: Vec<_> = lines
+            .iter()
+            .filter(|line| line["message"] == "never sampled")
+            .collect();
+        assert_eq!(errors.len(), 7);
+        assert!(
+            errors
+                .iter()
+                .all(|line| line.get("sampled_count").is_none())
+        );
+    }
+
+    #[test]
+    fn test_logfmt_sampling() {
+        let actual = capture(
+            |writer| {
+                LogfmtLoggingLayer::new(TestClock::new(), writer, [], FormatOptions::default())
+                    .with_sample_rates(SampleRates::new([("proxy::logging::tests", 3)]))
+            },
+            || {
+                for i in 0..7 {
+                    tracing::info!(i, "sampled");
+                    tracing::error!(i, "never sampled");
+                }
+            },
+        )
+        .text();
+
+        let sampled: Vec<_> = actual
+            .lines()
+            .filter(|line| line.contains("msg=\"sampled\""))
+            .map(|line| line.split_once(" i=").unwrap().1)
+            .collect();
+        assert_eq!(
+            sampled,
+            [
+                "0 sampled_count=0",
+                "3 sampled_count=2",
+                "6 sampled_count=2"
+            ]
+        );
+
+        let errors: Vec<_> = actual
+            .lines()
+            .filter(|line| line.contains("msg=\"never sampled\""))
+            .collect();
+        assert_eq!(errors.len(), 7);
+        assert!(errors.iter().all(|line| !line.contains("sampled_count")));
+    }
+
+    #[test]
+    fn test_span_close_events() {
+        let started_at = Utc::now();
+        let clock = TestClock::at(started_at);
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(clock.clone(), writer, ["x"], FormatOptions::default())
+                    .with_span_close_events(true)
+            },
+            || {
+                let span = info_span!("some_span", x = 24);
+                span.in_scope(|| tracing::info!("inside"));
+                clock.set(started_at + chrono::TimeDelta::milliseconds(1500));
+                drop(span);
+            },
+        )
+        .lines();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["message"], "inside");
+        assert_json_eq!(
+            lines[1],
+            serde_json::json!({
+                "timestamp": clock.now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+                "level": "INFO",
+                "message": "span closed",
+                "fields": {
+                    "span": "some_span",
+                    "duration_ms": 1500.0,
+                },
+                "extract": {
+                    "x": 24,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_spans_as_array() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["x"],
+                    FormatOptions {
+                        spans_format: SpansFormat::Array,
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("some_span", x = 24).in_scope(|| {
+                    info_span!("other_span", y = 1).in_scope(|| {
+                        info_span!("some_span", x = 40, x = 41, x = 42).in_scope(|| {
+                            tracing::info!("nested");
+                        });
+                    });
+                });
+            },
+        )
+        .lines();
+
+        let actual = &lines[0];
+        let spans = actual["spans"].as_array().expect("spans are an array");
+
+        // Ordered from root to leaf, repeated names stay apart.
+        let names: Vec<_> = spans.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["some_span", "other_span", "some_span"]);
+        assert_ne!(spans[0]["id"], spans[2]["id"]);
+        assert_eq!(spans[0]["fields"], serde_json::json!({ "x": 24 }));
+        assert_eq!(spans[1]["fields"], serde_json::json!({ "y": 1 }));
+        assert_eq!(spans[2]["fields"], serde_json::json!({ "x": 42 }));
+
+        // Extraction works the same as with the map.
+        assert_eq!(actual["extract"], serde_json::json!({ "x": 42 }));
+    }
+
+    #[test]
+    fn test_flat_spans() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["x"],
+                    FormatOptions {
+                        spans_format: SpansFormat::Flat,
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("some_span", x = 24, z = "root").in_scope(|| {
+                    info_span!("other_span", y = 1).in_scope(|| {
+                        info_span!("some_span", x = 42).in_scope(|| {
+                            tracing::info!("nested");
+                        });
+                    });
+                });
+            },
+        )
+        .lines();
+
+        let actual = lines[0].as_object().expect("line is an object");
+        assert!(!actual.contains_key("spans"));
+
+        let span_fields: serde_json::Map<_, _> = actual
+            .iter()
+            .filter(|(key, _)| key.starts_with("span."))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        assert_json_eq!(
+            serde_json::Value::Object(span_fields),
+            serde_json::json!({
+                "span.some_span.x": 42,
+                "span.some_span.z": "root",
+                "span.other_span.y": 1,
+            })
+        );
+
+        // Extraction works the same as with the map.
+        assert_eq!(actual["extract"], serde_json::json!({ "x": 42 }));
+    }
+
+    #[test]
+    fn test_logfmt() {
+        let clock = TestClock::new();
+        let actual = capture(
+            |writer| {
+                LogfmtLoggingLayer::new(clock.clone(), writer, ["x"], FormatOptions::default())
+            },
+            || {
+                info_span!("some_span", x = 24).in_scope(|| {
+                    info_span!("other_span", y = "a b").in_scope(|| {
+                        tracing::error!(a = 1, a = 2, s = "say \"hi\"", "hello world");
+                    });
+                });
+            },
+        )
+        .text();
+
+        let expected = format!(
+            "timestamp={} level=ERROR msg=\"hello world\" a=2 s=\"say \\\"hi\\\"\" \
+             span.some_span.x=24 span.other_span.y=\"a b\" x=24\n",
+            clock
+                .now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_logfmt_fields_are_written_once_in_order() {
+        let actual = capture(
+            |writer| {
+                LogfmtLoggingLayer::new(TestClock::new(), writer, ["x"], FormatOptions::default())
+            },
+            || {
+                info_span!("some_span", x = 24, c = 3, a = 1, b = 2).in_scope(|| {
+                    tracing::info!(x = 25, y = 26, "hello");
+                });
+            },
+        )
+        .text();
+
+        assert_eq!(actual.matches(" x=").count(), 1, "{actual}");
+        assert!(
+            actual.ends_with(
+                " y=26 span.some_span.a=1 span.some_span.b=2 span.some_span.c=3 \
+                 span.some_span.x=24 x=25\n"
+            ),
+            "{actual}"
+        );
+    }
+
+    #[test]
+    fn test_logfmt_redact_fields() {
+        let actual = capture(
+            |writer| {
+                LogfmtLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["password"],
+                    FormatOptions {
+                        redact_fields: RedactFields::new(["password", "token"]),
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("connect", password = "hunter2", user = "alice").in_scope(|| {
+                    tracing::info!(token = 1234567, token_id = 5, "connected");
+                });
+            },
+        )
+        .text();
+
+        assert!(!actual.contains("hunter2"), "{actual}");
+        assert!(!actual.contains("1234567"), "{actual}");
+        assert!(actual.contains(" token=[redacted] token_id=5 "), "{actual}");
+        assert!(
+            actual.contains(" span.connect.password=[redacted]"),
+            "{actual}"
+        );
+        assert!(actual.contains(" span.connect.user=alice"), "{actual}");
+        assert!(actual.ends_with(" password=[redacted]\n"), "{actual}");
+    }
+
+    #[test]
+    fn test_logfmt_quoting() {
+        let quoted = |value: &str| {
+            let mut buffer = Vec::new();
+            push_logfmt_value(&mut buffer, value);
+            String::from_utf8(buffer).expect("valid UTF-8")
+        };
+        assert_eq!(quoted("plain"), "plain");
+        assert_eq!(quoted(""), r#""""#);
+        assert_eq!(quoted("a=b"), r#""a=b""#);
+        assert_eq!(quoted("a\\b"), r#""a\\b""#);
+        assert_eq!(quoted("line\nbreak\u{7}"), r#""line\nbreak\u0007""#);
+    }
+
+    #[test]
+    fn test_buffer_capacity_is_released() {
+        let huge = "x".repeat(4 * MAX_RETAINED_BUFFER_CAPACITY);
+        let lines = capture(
+            |writer| JsonLoggingLayer::new(TestClock::new(), writer, [], FormatOptions::default()),
+            || {
+                tracing::info!(huge, "huge line");
+                let capacity = EVENT_FORMATTER.with_borrow(|f| f.logline_buffer.capacity());
+                assert!(capacity > MAX_RETAINED_BUFFER_CAPACITY);
+
+                tracing::info!("small line");
+                let capacity = EVENT_FORMATTER.with_borrow(|f| f.logline_buffer.capacity());
+                assert!(capacity <= MAX_RETAINED_BUFFER_CAPACITY);
+            },
+        )
+        .lines();
+
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn test_long_lines_are_truncated() {
+        let huge = "é".repeat(4096);
+        let text = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["x"],
+                    FormatOptions {
+                        max_line_len: 1024,
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("some_span", x = huge.as_str()).in_scope(|| {
+                    tracing::info!(huge, small = "kept", "short message");
+                    tracing::info!("{huge}");
+                });
+            },
+        )
+        .text();
+        let mut lines = text.lines();
+
+        let line = lines.next().unwrap();
+        assert!(line.len() <= 1024, "line is {} bytes", line.len());
+        let actual: serde_json::Value = serde_json::from_str(line).expect("valid JSON");
+        assert_eq!(actual["message"], "short message");
+        assert_eq!(actual["truncated"], true);
+        assert_eq!(actual["fields"]["small"], "kept");
+        let truncated = actual["fields"]["huge"].as_str().unwrap();
+        assert!(!truncated.is_empty() && huge.starts_with(truncated));
+        let span = actual["spans"]
+            .as_object()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap();
+        assert!(span["x"].as_str().unwrap().len() < huge.len());
+        assert!(actual["extract"]["x"].as_str().unwrap().len() < huge.len());
+
+        // The message is never truncated.
+        let actual: serde_json::Value =
+            serde_json::from_str(lines.next().unwrap()).expect("valid JSON");
+        assert_eq!(actual["message"], huge);
+        assert_eq!(actual["truncated"], true);
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let now = DateTime::parse_from_rfc3339("2024-05-06T07:08:09.123456Z")
+            .unwrap()
+            .to_utc();
+
+        for (timestamp_format, expected) in [
+            (
+                TimestampFormat::Utc,
+                serde_json::json!("2024-05-06T07:08:09.123456Z"),
+            ),
+            (
+                TimestampFormat::FixedOffset(FixedOffset::east_opt(2 * 3600).unwrap()),
+                serde_json::json!("2024-05-06T09:08:09.123456+02:00"),
+            ),
+            (
+                TimestampFormat::EpochMillis,
+                serde_json::json!(1_714_979_289_123_i64),
+            ),
+        ] {
+            let lines = capture(
+                |writer| {
+                    JsonLoggingLayer::new(
+                        TestClock::at(now),
+                        writer,
+                        [],
+                        FormatOptions {
+                            timestamp_format,
+                            ..FormatOptions::default()
+                        },
+                    )
+                },
+                || tracing::info!("hello"),
+            )
+            .lines();
+            assert_eq!(lines[0]["timestamp"], expected, "{timestamp_format:?}");
+        }
+    }
+
+    #[test]
+    fn test_log_fields() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    [],
+                    FormatOptions {
+                        log_fields: LogFields {
+                            src: false,
+                            thread_id: false,
+                            ..LogFields::default()
+                        },
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || tracing::info!("without src and thread_id"),
+        )
+        .lines();
+
+        let actual = lines[0].as_object().unwrap();
+        assert!(!actual.contains_key("src"));
+        assert!(!actual.contains_key("thread_id"));
+        assert_eq!(actual["target"], "proxy::logging::tests");
+        assert_eq!(actual["message"], "without src and thread_id");
+        assert!(actual.contains_key("timestamp"));
+        assert!(actual.contains_key("level"));
+    }
+
+    #[test]
+    fn test_thread_name() {
+        let writer = CapturingWriter::default();
+        let log_layer = JsonLoggingLayer::new(
+            TestClock::new(),
+            writer.clone(),
+            [],
+            FormatOptions::default(),
+        );
+        let dispatch =
+            tracing::Dispatch::new(tracing_subscriber::Registry::default().with(log_layer));
+
+        for name in ["named-thread", "tokio-runtime-worker"] {
+            let dispatch = dispatch.clone();
+            std::thread::Builder::new()
+                .name(name.to_owned())
+                .spawn(move || {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        tracing::info!("first");
+                        tracing::info!("second, with the cached name");
+                    });
+                })
+                .expect("spawn thread")
+                .join()
+                .expect("thread succeeds");
+        }
+
+        let thread_names: Vec<_> = writer
+            .lines()
+            .iter()
+            .map(|line| line.get("thread_name").cloned())
+            .collect();
+        assert_eq!(
+            thread_names,
+            [
+                Some("named-thread".into()),
+                Some("named-thread".into()),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_float_decimals() {
+        fn capture_line(float_decimals: Option<u8>) -> serde_json::Value {
+            let lines = capture(
+                |writer| {
+                    JsonLoggingLayer::new(
+                        TestClock::new(),
+                        writer,
+                        [],
+                        FormatOptions {
+                            float_decimals,
+                            ..FormatOptions::default()
+                        },
+                    )
+                },
+                || {
+                    info_span!("some_span", rate = 0.123_456_789_f64).in_scope(|| {
+                        tracing::info!(elapsed_ms = 12.999_999_999_998_f64, "timed");
+                    });
+                },
+            )
+            .lines();
+            lines[0].clone()
+        }
+
+        fn span_rate(line: &serde_json::Value) -> &serde_json::Value {
+            let spans = line["spans"].as_object().unwrap();
+            &spans.values().next().unwrap()["rate"]
+        }
+
+        let rounded = capture_line(Some(3));
+        assert_eq!(rounded["fields"]["elapsed_ms"], 13.0);
+        assert_eq!(span_rate(&rounded), 0.123);
+
+        let full = capture_line(None);
+        assert_eq!(full["fields"]["elapsed_ms"], 12.999_999_999_998);
+        assert_eq!(span_rate(&full), 0.123_456_789);
+    }
+
+    #[test]
+    fn test_error_fields() {
+        let err = io::Error::other("connection reset");
+        let cause: &(dyn std::error::Error + 'static) = &err;
+        let lines = capture(
+            |writer| JsonLoggingLayer::new(TestClock::new(), writer, [], FormatOptions::default()),
+            || {
+                info_span!("some_span", cause).in_scope(|| {
+                    tracing::warn!(error = cause, attempt = 2, "retrying");
+                });
+            },
+        )
+        .lines();
+
+        let actual = &lines[0];
+        assert_eq!(actual["message"], "retrying");
+        assert_json_eq!(
+            actual["fields"],
+            serde_json::json!({"error": "connection reset", "attempt": 2})
+        );
+        let spans = actual["spans"].as_object().unwrap();
+        assert_eq!(spans.values().next().unwrap()["cause"], "connection reset");
+    }
+
+    #[test]
+    fn test_error_chains() {
+        #[derive(Debug, thiserror::Error)]
+        #[error("cannot connect to compute")]
+        struct ConnectError(#[source] io::Error);
+
+        let err = ConnectError(io::Error::other("connection reset"));
+        let err: &(dyn std::error::Error + 'static) = &err;
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    [],
+                    FormatOptions {
+                        error_chains: true,
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                tracing::warn!(error = err, attempt = 2, "retrying");
+                tracing::warn!(message = err);
+            },
+        )
+        .lines();
+
+        let chain = serde_json::json!(["cannot connect to compute", "connection reset"]);
+        assert_json_eq!(
+            lines[0]["fields"],
+            serde_json::json!({"error": chain, "attempt": 2})
+        );
+        assert_eq!(lines[1]["message"], chain);
+    }
+
+    #[test]
+    fn test_fallback_trace_id() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    [],
+                    FormatOptions {
+                        fallback_trace_id: Some("request_id"),
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("connect").in_scope(|| {
+                    tracing::info!("accepted");
+                    info_span!("request", request_id = "req-1").in_scope(|| {
+                        tracing::info!("first");
+                        info_span!("inner").in_scope(|| tracing::info!("second"));
+                    });
+                    tracing::info!("closed");
+                });
+                info_span!("connect").in_scope(|| tracing::info!("other connection"));
+                tracing::info!("outside of spans");
+            },
+        )
+        .lines();
+
+        let trace_ids: Vec<_> = lines.iter().map(|line| &line["trace_id"]).collect();
+
+        // The request ID wins within the request span.
+        assert_eq!(trace_ids[1], "req-1");
+        assert_eq!(trace_ids[2], "req-1");
+
+        // Outside of it, the generated ID of the root span is stable.
+        let generated = trace_ids[0].as_str().expect("generated trace_id");
+        assert_eq!(generated.len(), 32);
+        assert_eq!(trace_ids[3], generated);
+        assert_ne!(trace_ids[4], generated);
+        assert!(trace_ids[4].is_string());
+
+        assert!(lines[5].get("trace_id").is_none());
+    }
+
+    #[test]
+    fn test_otel_span_ids() {
+        use opentelemetry::trace::{TraceContextExt, TracerProvider};
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let tracer = opentelemetry_sdk::trace::TracerProvider::builder()
+            .build()
+            .tracer("test");
+
+        let mut span_context = None;
+        let lines = capture(
+            |writer| {
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .and_then(JsonLoggingLayer::new(
+                        TestClock::new(),
+                        writer,
+                        [],
+                        FormatOptions::default(),
+                    ))
+            },
+            || {
+                info_span!("traced").in_scope(|| {
+                    tracing::info!("inside");
+                    span_context = Some(Span::current().context().span().span_context().clone());
+                });
+            },
+        )
+        .lines();
+        let span_context = span_context.expect("span was entered");
+        assert!(span_context.is_valid());
+
+        let actual = &lines[0];
+        assert_eq!(actual["trace_id"], span_context.trace_id().to_string());
+        assert_eq!(actual["span_id"], span_context.span_id().to_string());
+        assert_eq!(actual["span_id"].as_str().map(str::len), Some(16));
+    }
+
+    #[test]
+    fn test_extract_event_fields() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["request_id", "conn_id"],
+                    FormatOptions::default(),
+                )
+            },
+            || {
+                tracing::info!(request_id = %"no-span", "request");
+                info_span!("connect", conn_id = 7, request_id = "from-span").in_scope(|| {
+                    tracing::info!(request_id = %"from-event", "request");
+                });
+            },
+        )
+        .lines();
+
+        assert_json_eq!(
+            lines[0]["extract"],
+            serde_json::json!({"request_id": "no-span"})
+        );
+        // The event value takes precedence over the span value.
+        assert_json_eq!(
+            lines[1]["extract"],
+            serde_json::json!({"request_id": "from-event", "conn_id": 7})
+        );
+    }
+
+    /// Logs an event from within every write, like an exporter that logs while exporting.
+    #[derive(Clone, Default)]
+    struct ReentrantWriter {
+        lines: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl MakeWriter for ReentrantWriter {
+        fn make_writer(&self) -> impl io::Write {
+            self.clone()
+        }
+    }
+
+    impl io::Write for ReentrantWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.lines.lock().expect("poisoned").push(buf.to_vec());
+            tracing::info!("nested");
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reentrancy_depth_is_bounded() {
+        let writer = ReentrantWriter::default();
+        let log_layer = JsonLoggingLayer::new(
+            TestClock::new(),
+            writer.clone(),
+            [],
+            FormatOptions::default(),
+        );
+
+        let registry = tracing_subscriber::Registry::default().with(log_layer);
+
+        let dropped = &Metrics::get().proxy.log_events_dropped_reentrancy_total;
+        let dropped_before = dropped.get_metric().count.load(Ordering::Relaxed);
+
+        tracing::subscriber::with_default(registry, || {
+            tracing::info!("outer");
+        });
+
+        let lines = writer.lines.lock().expect("poisoned");
+        assert_eq!(lines.len(), usize::from(MAX_REENTRANCY_DEPTH));
+        let first: serde_json::Value = serde_json::from_slice(&lines[0]).expect("valid JSON");
+        assert_eq!(first["message"], "outer");
+        let last: serde_json::Value =
+            serde_json::from_slice(lines.last().unwrap()).expect("valid JSON");
+        assert_eq!(last["message"], "nested");
+
+        assert_eq!(
+            dropped.get_metric().count.load(Ordering::Relaxed),
+            dropped_before + 1
+        );
+        assert_eq!(REENTRANCY_DEPTH.get(), 0);
+    }
+
+    /// Fails every write, but keeps what was attempted to be written.
+    #[derive(Clone, Default)]
+    struct FailingWriter {
+        attempts: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl MakeWriter for FailingWriter {
+        fn make_writer(&self) -> impl io::Write {
+            self.clone()
+        }
+    }
+
+    impl io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.attempts.lock().expect("poisoned").push(buf.to_vec());
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dropped_events_are_counted() {
+        let writer = FailingWriter::default();
+        let log_layer = JsonLoggingLayer::new(
+            TestClock::new(),
+            writer.clone(),
+            [],
+            FormatOptions::default(),
+        );
+
+        let dispatch =
+            tracing::Dispatch::new(tracing_subscriber::Registry::default().with(log_layer));
+        let dropped_events = || {
+            dispatch
+                .downcast_ref::<JsonLoggingLayer<Arc<TestClock>, FailingWriter, 0>>()
+                .expect("layer is part of the subscriber")
+                .dropped_events()
+        };
+
+        tracing::dispatcher::with_default(&dispatch, || tracing::info!("lost"));
+        assert_eq!(dropped_events(), 1);
+
+        let reported = |writer: &FailingWriter| {
+            let attempts = writer.attempts.lock().expect("poisoned");
+            attempts
+                .iter()
+                .filter_map(|line| serde_json::from_slice::<serde_json::Value>(line).ok())
+                .filter_map(|line| line["fields"]["dropped_events"].as_u64())
+                .collect::<Vec<_>>()
+        };
+        assert!(reported(&writer).is_empty());
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            for _ in 1..DROPPED_EVENTS_REPORT_INTERVAL {
+                tracing::info!("lost");
+            }
+        });
+        assert_eq!(dropped_events(), DROPPED_EVENTS_REPORT_INTERVAL);
+        assert_eq!(reported(&writer), [DROPPED_EVENTS_REPORT_INTERVAL]);
+    }
+
+    /// Fails the first `fail` writes, and keeps what the others wrote.
+    #[derive(Clone, Default)]
+    struct FlakyWriter {
+        state: Arc<Mutex<(usize, Vec<u8>)>>,
+    }
+
+    impl MakeWriter for FlakyWriter {
+        fn make_writer(&self) -> impl io::Write {
+            self.clone()
+        }
+    }
+
+    impl io::Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut state = self.state.lock().expect("poisoned");
+            let (fail, written) = &mut *state;
+            if *fail > 0 {
+                *fail -= 1;
+                return Err(io::Error::other("disk full"));
+            }
+            written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_msgpack_dropped_event_keeps_framing() {
+        let writer = FlakyWriter::default();
+        writer.state.lock().expect("poisoned").0 = 1;
+        let log_layer = JsonLoggingLayer::new(
+            TestClock::new(),
+            writer.clone(),
+            [],
+            FormatOptions {
+                encoding: RecordEncoding::MsgPack,
+                ..FormatOptions::default()
+            },
+        );
+
+        let dispatch =
+            tracing::Dispatch::new(tracing_subscriber::Registry::default().with(log_layer));
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::info!("lost");
+            tracing::info!("kept");
+        });
+
+        let written = writer.state.lock().expect("poisoned").1.clone();
+        let mut rest = written.as_slice();
+        let mut messages = Vec::new();
+        while !rest.is_empty() {
+            let (len, tail) = rest.split_at(4);
+            let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+            let (record, tail) = tail.split_at(len);
+            let record: serde_json::Value =
+                rmp_serde::from_slice(record).expect("valid MessagePack");
+            messages.push(record["message"].as_str().unwrap().to_owned());
+            rest = tail;
+        }
+        assert_eq!(messages.len(), 2, "{messages:?}");
+        assert!(messages[0].starts_with("cannot log event"), "{messages:?}");
+        assert_eq!(messages[1], "kept");
+    }
+
+    #[test]
+    fn test_logfmt_dropped_events_are_counted() {
+        let writer = FailingWriter::default();
+        let log_layer = LogfmtLoggingLayer::new(
+            TestClock::new(),
+            writer.clone(),
+            [],
+            FormatOptions::default(),
+        );
+
+        let dispatch =
+            tracing::Dispatch::new(tracing_subscriber::Registry::default().with(log_layer));
+        let dropped = &Metrics::get().proxy.log_events_dropped_total;
+        let dropped_before = dropped.get_metric().count.load(Ordering::Relaxed);
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            for _ in 0..DROPPED_EVENTS_REPORT_INTERVAL {
+                tracing::info!("lost");
+            }
+        });
+
+        let layer = dispatch
+            .downcast_ref::<LogfmtLoggingLayer<Arc<TestClock>, FailingWriter, 0>>()
+            .expect("layer is part of the subscriber");
+        assert_eq!(layer.dropped_events(), DROPPED_EVENTS_REPORT_INTERVAL);
+        // Other tests drop events concurrently.
+        assert!(
+            dropped.get_metric().count.load(Ordering::Relaxed) - dropped_before
+                >= DROPPED_EVENTS_REPORT_INTERVAL
+        );
+
+        let attempts = writer.attempts.lock().expect("poisoned");
+        let reports: Vec<_> = attempts
+            .iter()
+            .filter_map(|line| std::str::from_utf8(line).ok())
+            .filter(|line| line.contains("level=WARN"))
+            .collect();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].ends_with(&format!(
+            " dropped_events={DROPPED_EVENTS_REPORT_INTERVAL}\n"
+        )));
+    }
+
+    #[test]
+    fn test_labels() {
+        let labels = BTreeMap::from([
+            ("region", "eu".to_owned()),
+            ("deployment", "prod".to_owned()),
+        ]);
+        let options = || FormatOptions {
+            labels: labels.clone(),
+            ..FormatOptions::default()
+        };
+        let lines = capture(
+            |writer| JsonLoggingLayer::new(TestClock::new(), writer, [], options()),
+            || tracing::info!(level = 1, "labelled"),
+        )
+        .lines();
+
+        let writer = FailingWriter::default();
+        let failing_layer = JsonLoggingLayer::new(TestClock::new(), writer.clone(), [], options());
+        let registry = tracing_subscriber::Registry::default().with(failing_layer);
+        tracing::subscriber::with_default(registry, || {
+            for _ in 0..DROPPED_EVENTS_REPORT_INTERVAL {
+                tracing::info!("lost");
+            }
+        });
+
+        let logfmt = capture(
+            |writer| LogfmtLoggingLayer::new(TestClock::new(), writer, [], options()),
+            || tracing::info!("labelled"),
+        )
+        .text();
+        assert!(
+            logfmt.ends_with(" labels.deployment=prod labels.region=eu\n"),
+            "{logfmt}"
+        );
+
+        let expected = serde_json::json!({"deployment": "prod", "region": "eu"});
+        let actual = &lines[0];
+        assert_eq!(actual["labels"], expected);
+        assert_eq!(actual["level"], "INFO");
+        assert_eq!(actual["fields"]["level"], 1);
+
+        let attempts = writer.attempts.lock().expect("poisoned");
+        let fallback: Vec<serde_json::Value> = attempts
+            .iter()
+            .filter_map(|line| serde_json::from_slice(line).ok())
+            .collect();
+        let cannot_log = fallback
+            .iter()
+            .find(|line| line["level"] == "ERROR")
+            .expect("fallback line");
+        assert_eq!(
+            cannot_log["message"]
+                .as_str()
+                .map(|m| m.starts_with("cannot log")),
+            Some(true)
+        );
+        assert_eq!(cannot_log["labels"], expected);
+        let report = fallback
+            .iter()
+            .find(|line| line["level"] == "WARN")
+            .expect("dropped events report");
+        assert_eq!(
+            report["fields"]["dropped_events"],
+            DROPPED_EVENTS_REPORT_INTERVAL
+        );
+        assert_eq!(report["labels"], expected);
+    }
+
+    /// Fails all writes while `fail` is set.
+    #[derive(Clone, Default)]
+    struct ToggleWriter {
+        fail: Arc<AtomicBool>,
+        inner: CapturingWriter,
+    }
+
+    impl MakeWriter for ToggleWriter {
+        fn make_writer(&self) -> impl io::Write {
+            self.clone()
+        }
+    }
+
+    impl io::Write for ToggleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            use std::io::Write;
+
+            if self.fail.load(Ordering::Relaxed) {
+                return Err(io::Error::other("broken pipe"));
+            }
+            self.inner.make_writer().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fallback_writer() {
+        let primary = ToggleWriter::default();
+        let secondary = capture(
+            |secondary| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    FallbackWriter::new(primary.clone(), Some(secondary), 2),
+                    [],
+                    FormatOptions::default(),
+                )
+            },
+            || {
+                primary.fail.store(true, Ordering::Relaxed);
+                // Fails twice, for the event and the error line about it.
+                tracing::info!("a");
+                tracing::info!("b");
+                primary.fail.store(false, Ordering::Relaxed);
+                tracing::info!("c");
+                primary.fail.store(true, Ordering::Relaxed);
+                tracing::info!("d");
+            },
+        );
+
+        let messages = |writer: &CapturingWriter| {
+            writer
+                .lines()
+                .iter()
+                .map(|line| line["message"].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(messages(&secondary), ["b"]);
+        assert_eq!(messages(&primary.inner), ["c"]);
+    }
+
+    /// Accepts `capacity` bytes in total, then fails.
+    #[derive(Clone, Default)]
+    struct ShortWriter {
+        capacity: usize,
+        inner: CapturingWriter,
+    }
+
+    impl MakeWriter for ShortWriter {
+        fn make_writer(&self) -> impl io::Write {
+            self.clone()
+        }
+    }
+
+    impl io::Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            use std::io::Write;
+
+            let left = self.capacity - self.inner.bytes().len();
+            if left == 0 {
+                return Err(io::Error::other("disk full"));
+            }
+            self.inner.make_writer().write(&buf[..buf.len().min(left)])
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fallback_writer_partial_write() {
+        use std::io::Write;
+
+        let primary = ShortWriter {
+            capacity: 5,
+            ..ShortWriter::default()
+        };
+        let secondary = CapturingWriter::default();
+        let writer = FallbackWriter::new(primary.clone(), Some(secondary.clone()), 0);
+        writer
+            .make_writer()
+            .write_all(b"hello world\n")
+            .expect("falls back");
+
+        assert_eq!(primary.inner.bytes(), b"hello");
+        assert_eq!(secondary.bytes(), b" world\n");
+
+        // Without a secondary, the partial write is reported as such.
+        let primary = ShortWriter {
+            capacity: 5,
+            ..ShortWriter::default()
+        };
+        let writer = FallbackWriter::new(primary.clone(), None::<CapturingWriter>, 0);
+        assert_eq!(writer.make_writer().write(b"hello world\n").ok(), Some(5));
+        assert!(writer.make_writer().write(b" world\n").is_err());
+    }
+
+    #[test]
+    fn test_log_volume() {
+        // Other tests log concurrently, but not at these levels.
+        let volume = |level: LogLevel| {
+            let metrics = &Metrics::get().proxy;
+            let events = &metrics.log_events_total;
+            let bytes = &metrics.log_bytes_total;
+            (
+                events
+                    .get_metric(events.with_labels(level))
+                    .count
+                    .load(Ordering::Relaxed),
+                bytes
+                    .get_metric(bytes.with_labels(level))
+                    .count
+                    .load(Ordering::Relaxed),
+            )
+        };
+        let (debug_events_before, debug_bytes_before) = volume(LogLevel::Debug);
+        let (trace_events_before, trace_bytes_before) = volume(LogLevel::Trace);
+
+        let buffer = capture(
+            |writer| JsonLoggingLayer::new(TestClock::new(), writer, [], FormatOptions::default()),
+            || {
+                tracing::debug!("first");
+                tracing::debug!("second");
+                tracing::trace!("third");
+            },
+        )
+        .bytes();
+
+        let (debug_events, debug_bytes) = volume(LogLevel::Debug);
+        let (trace_events, trace_bytes) = volume(LogLevel::Trace);
+        let debug_bytes = debug_bytes - debug_bytes_before;
+        let trace_bytes = trace_bytes - trace_bytes_before;
+        assert_eq!(
+            (
+                debug_events - debug_events_before,
+                trace_events - trace_events_before
+            ),
+            (2, 1)
+        );
+
+        let first_len = buffer.iter().position(|b| *b == b'\n').expect("newline") + 1;
+        assert!(debug_bytes > first_len as u64);
+        assert_eq!(debug_bytes + trace_bytes, buffer.len() as u64);
+    }
+
+    #[test]
+    fn test_runtime_extract_fields() {
+        let lines = capture(
+            |writer| {
+                JsonLoggingLayer::new(
+                    TestClock::new(),
+                    writer,
+                    ["request_id"],
+                    FormatOptions {
+                        extract_fields: IndexSet::from_iter([
+                            Cow::Borrowed("tenant_id"),
+                            Cow::Borrowed("x"),
+                        ]),
+                        ..FormatOptions::default()
+                    },
+                )
+            },
+            || {
+                info_span!("outer", tenant_id = "t1", x = 1).in_scope(|| {
+                    info_span!("inner", request_id = "r1", x = 2).in_scope(|| {
+                        tracing::info!(x = 3, "with event field");
+                        tracing::info!("without event field");
+                    });
+                });
+                tracing::info!("outside of spans");
+            },
+        )
+        .lines();
+
+        // Last one wins.
+        let extract = lines[0]["extract"].as_object().expect("extract is present");
+        assert_eq!(
+            extract.iter().collect::<Vec<_>>(),
+            [
+                (&"request_id".to_owned(), &serde_json::json!("r1")),
+                (&"tenant_id".to_owned(), &serde_json::json!("t1")),
+                (&"x".to_owned(), &serde_json::json!(3)),
+            ]
+        );
+        assert_eq!(lines[1]["extract"]["x"], 2);
+        assert!(lines[2].get("extract").is_none());
+    }
+
+    #[test]
+    fn test_extract_slots() {
+        let names = IndexSet::from_iter([Cow::Borrowed("request_id"), Cow::Borrowed("x")]);
+        let extract = ExtractedSpanFields::<'_, 1>::new(&names);
+
+        assert_eq!(extract.slot("session_id"), None);
+        assert!(!extract.has_values());
+
+        // The second name doesn't fit into the inline slots, but still has one
+        extract.set(extract.slot("x").unwrap(), serde_json::Value::from(1));
+        extract.set(
+            extract.slot("request_id").unwrap(),
+            serde_json::Value::from("abc"),
+        );
+        assert!(extract.has_values());
+
+        let mut values = Vec::new();
+        extract
+            .try_for_each(|name, value| {
+                values.push((name.to_owned(), value.clone()));
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(
+            values,
+            [
+                ("request_id".to_owned(), serde_json::json!("abc")),
+                ("x".to_owned(), serde_json::json!(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_msgpack_matches_json() {
+        let clock = TestClock::new();
+        let capture_bytes = |encoding| {
+            capture(
+                |writer| {
+                    JsonLoggingLayer::new(
+                        clock.clone(),
+                        writer,
+                        ["x"],
+                        FormatOptions {
+                            encoding,
+                            ..FormatOptions::default()
+                        },
+                    )
+                },
+                || {
+                    info_span!("some_span", x = 24).in_scope(|| {
+                        tracing::error!(a = 1, b = "two", "some message");
+                    });
+                },
+            )
+            .bytes()
+        };
+
+        let json = capture_bytes(RecordEncoding::Json);
+        let mut json: serde_json::Value = serde_json::from_slice(&json).expect("valid JSON");
+
+        let msgpack = capture_bytes(RecordEncoding::MsgPack);
+        let (len, record) = msgpack.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        assert_eq!(record.len(), len);
+        let mut msgpack: serde_json::Value =
+            rmp_serde::from_slice(record).expect("valid MessagePack");
+
+        // Span keys carry a callsite ID that is allocated per layer, so compare span values only.
+        let span_values = |v: &mut serde_json::Value| -> Vec<serde_json::Value> {
+            let spans = v.as_object_mut().unwrap().remove("spans").unwrap();
+            spans.as_object().unwrap().values().cloned().collect()
+        };
+        assert_eq!(span_values(&mut msgpack), span_values(&mut json));
+        assert_eq!(msgpack["message"], "some message");
+        assert_json_eq!(msgpack, json);
+    }
+
+    #[test]
+    fn test_span_parents() {
+        let lines = capture(
+            |writer| JsonLoggingLayer::new(TestClock::new(), writer, [], FormatOptions::default()),
+            || {
+                info_span!("outer").in_scope(|| {
+                    info_span!("middle").in_scope(|| {
+                        info_span!("middle", x = 1).in_scope(|| {
+                            tracing::info!("nested");
+                        });
+                    });
+                });
+            },
+        )
+        .lines();
+
+        let spans = lines[0]["spans"].as_object().unwrap();
+        assert_eq!(spans.len(), 3);
+
+        // Walk up from the leaf, which is the only span with fields.
+        let (leaf, leaf_fields) = spans.iter().find(|(_, v)| v.get("x").is_some()).unwrap();
+        assert!(leaf.starts_with("middle#"));
+        let middle = leaf_fields["_parent"].as_str().unwrap();
+        assert!(middle.starts_with("middle#"));
+        assert_ne!(middle, leaf);
+        let outer = spans[middle]["_parent"].as_str().unwrap();
+        assert!(outer.starts_with("outer#"));
+        assert!(spans[outer].get("_parent").is_none());
+    }
+
+    #[test]
+    fn test_logging_guard_shutdown_timeout() {
+        use std::sync::Condvar;
+
+        /// Whether the stalled shutdown was released, and whether it returned.
+        static STALLED: (Mutex<(bool, bool)>, Condvar) =
+            (Mutex::new((false, false)), Condvar::new());
+
+        // Stands in for an exporter whose collector doesn't respond until released.
+        fn stalled_shutdown() {
+            let (state, cond) = &STALLED;
+            let mut state = cond
+                .wait_while(state.lock().expect("poisoned"), |(released, _)| !*released)
+                .expect("poisoned");
+            state.1 = true;
+            cond.notify_all();
+        }
+
+        let guard = LoggingGuard {
+            shutdown_timeout: Duration::from_millis(100),
+            shutdown: stalled_shutdown,
+            filter: None,
+        };
+        let started_at = std::time::Instant::now();
+        drop(guard);
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+
+        // Let the abandoned shutdown thread finish rather than leak it.
+        let (state, cond) = &STALLED;
+        state.lock().expect("poisoned").0 = true;
+        cond.notify_all();
+        let (_, returned) = *cond
+            .wait_timeout_while(
+                state.lock().expect("poisoned"),
+                Duration::from_secs(10),
+                |(_, returned)| !*returned,
+            )
+            .expect("poisoned")
+            .0;
+        assert!(returned);
+
+        // A responsive exporter is waited for as before.
+        fn quick_shutdown() {}
+        let guard = LoggingGuard {
+            shutdown_timeout: Duration::from_secs(60),
+            shutdown: quick_shutdown,
+            filter: None,
+        };
+        let started_at = std::time::Instant::now();
+        drop(guard);
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_reload_filter() {
+        let env_filter = EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .parse_lossy("");
+        let (env_filter, handle) = reload::Layer::new(env_filter);
+        let guard = LoggingGuard {
+            shutdown_timeout: Duration::from_secs(1),
+            shutdown: || {},
+            filter: Some(FilterReloadHandle {
+                handle,
+                add_directives: add_proxy_directives,
+            }),
+        };
+        let subscriber = tracing_subscriber::Registry::default().with(env_filter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(target: "proxy::serverless", tracing::Level::DEBUG));
+
+            guard.reload_filter("info,proxy::serverless=debug").unwrap();
+            assert!(tracing::enabled!(target: "proxy::serverless", tracing::Level::DEBUG));
+            assert!(!tracing::enabled!(target: "proxy::auth", tracing::Level::DEBUG));
+
+            // The proxy directives are kept.
+            guard.reload_filter("debug").unwrap();
+            assert!(tracing::enabled!(target: "proxy::auth", tracing::Level::DEBUG));
+            assert!(!tracing::enabled!(target: "aws_config::imds", tracing::Level::DEBUG));
+
+            // Invalid directives leave the filter alone.
+            guard.reload_filter("proxy=loud").unwrap_err();
+            assert!(tracing::enabled!(target: "proxy::auth", tracing::Level::DEBUG));
+        });
+    }
+
+    #[test]
+    fn test_trace_sampler() {
+        use tracing_utils::Sampler;
+
+        assert!(trace_sampler(None, None).unwrap().is_none());
+        assert!(trace_sampler(None, Some("0.1")).unwrap().is_none());
+        assert!(matches!(
+            trace_sampler(Some("always_off"), None).unwrap(),
+            Some(Sampler::AlwaysOff)
+        ));
+
+        let Some(Sampler::ParentBased(root)) =
+            trace_sampler(Some("parentbased_traceidratio"), Some("0.25")).unwrap()
+        else {
+            panic!("expected a parent based sampler");
+        };
+        assert!(matches!(*root, Sampler::TraceIdRatioBased(ratio) if ratio == 0.25));
+
+        // Without an argument, the ratio defaults to 1.
+        assert!(matches!(
+            trace_sampler(Some("traceidratio"), None).unwrap(),
+            Some(Sampler::TraceIdRatioBased(ratio)) if ratio == 1.0
+        ));
+
+        for arg in ["1.5", "-0.1", "NaN", "half"] {
+            trace_sampler(Some("parentbased_traceidratio"), Some(arg)).unwrap_err();
+        }
+        trace_sampler(Some("sometimes"), None).unwrap_err();
+    }
 }
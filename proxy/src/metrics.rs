@@ -52,6 +52,16 @@ pub fn get() -> &'static Self {
         SELF.get()
             .expect("proxy metrics must be installed by the main() function")
     }
+
+    /// Like [`Self::get`], but returns `None` instead of panicking if the metrics aren't
+    /// installed yet. For code that may run before `main()` installs them, e.g. logging.
+    pub fn try_get() -> Option<&'static Self> {
+        #[cfg(test)]
+        return Some(Self::get());
+
+        #[cfg(not(test))]
+        SELF.get()
+    }
 }
 
 #[derive(MetricGroup)]
@@ -142,6 +152,18 @@ pub struct ProxyMetrics {
     /// Number of connection requests affected by authentication rate limits
     pub requests_auth_rate_limits_total: Counter,
 
+    /// Number of log events that failed to be formatted or written
+    pub log_events_dropped_total: Counter,
+
+    /// Number of log events dropped because logging recursed too deeply, e.g. during OTel export
+    pub log_events_dropped_reentrancy_total: Counter,
+
+    /// Number of log events written (per level)
+    pub log_events_total: CounterVec<StaticLabelSet<LogLevel>>,
+
+    /// Number of bytes of log events written (per level)
+    pub log_bytes_total: CounterVec<StaticLabelSet<LogLevel>>,
+
     /// HLL approximate cardinality of endpoints that are connecting
     pub connecting_endpoints: HyperLogLogVec<StaticLabelSet<Protocol>, 32>,
 
@@ -230,6 +252,28 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+#[derive(FixedCardinalityLabel, Copy, Clone)]
+#[label(singleton = "level")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<&tracing::Level> for LogLevel {
+    fn from(level: &tracing::Level) -> Self {
+        match *level {
+            tracing::Level::TRACE => LogLevel::Trace,
+            tracing::Level::DEBUG => LogLevel::Debug,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::WARN => LogLevel::Warn,
+            tracing::Level::ERROR => LogLevel::Error,
+        }
+    }
+}
+
 #[derive(FixedCardinalityLabel, Copy, Clone)]
 pub enum Bool {
     True,
@@ -48,6 +48,10 @@ pub(crate) fn api_error_into_response(this: ApiError) -> Response<BoxBody<Bytes,
             err.to_string(),
             StatusCode::REQUEST_TIMEOUT,
         ),
+        ApiError::GatewayTimeout(_) => HttpErrorBody::response_from_msg_and_status(
+            this.to_string(),
+            StatusCode::GATEWAY_TIMEOUT,
+        ),
         ApiError::Cancelled => HttpErrorBody::response_from_msg_and_status(
             this.to_string(),
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -58,7 +58,7 @@ async fn error_from_body(self) -> Result<Self> {
 
         let url = self.url().to_owned();
         Err(match self.json::<HttpErrorBody>().await {
-            Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+            Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
             Err(_) => {
                 Error::ReceiveErrorBody(format!("http error ({}) at {}.", status.as_u16(), url))
             }
@@ -677,19 +677,22 @@ pub fn make_router(
 ) -> RouterBuilder<hyper::Body, ApiError> {
     let mut router = endpoint::make_router();
     if conf.http_auth.is_some() {
-        router = router.middleware(auth_middleware(|request| {
-            const ALLOWLIST_ROUTES: &[&str] =
-                &["/v1/status", "/metrics", "/profile/cpu", "/profile/heap"];
-            if ALLOWLIST_ROUTES.contains(&request.uri().path()) {
-                None
-            } else {
-                // Option<Arc<SwappableJwtAuth>> is always provided as data below, hence unwrap().
-                request
-                    .data::<Option<Arc<SwappableJwtAuth>>>()
-                    .unwrap()
-                    .as_deref()
-            }
-        }))
+        router = router.middleware(auth_middleware(
+            |request| {
+                const ALLOWLIST_ROUTES: &[&str] =
+                    &["/v1/status", "/metrics", "/profile/cpu", "/profile/heap"];
+                if ALLOWLIST_ROUTES.contains(&request.uri().path()) {
+                    None
+                } else {
+                    // Option<Arc<SwappableJwtAuth>> is always provided as data below, hence unwrap().
+                    request
+                        .data::<Option<Arc<SwappableJwtAuth>>>()
+                        .unwrap()
+                        .as_deref()
+                }
+            },
+            |_| None,
+        ))
     }
 
     // NB: on any changes do not forget to update the OpenAPI spec
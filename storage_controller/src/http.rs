@@ -1970,14 +1970,17 @@ pub fn make_router(
         .middleware(prologue_metrics_middleware())
         .middleware(epilogue_metrics_middleware());
     if auth.is_some() {
-        router = router.middleware(auth_middleware(|request| {
-            let state = get_state(request);
-            if state.allowlist_routes.contains(&request.uri().path()) {
-                None
-            } else {
-                state.auth.as_deref()
-            }
-        }));
+        router = router.middleware(auth_middleware(
+            |request| {
+                let state = get_state(request);
+                if state.allowlist_routes.contains(&request.uri().path()) {
+                    None
+                } else {
+                    state.auth.as_deref()
+                }
+            },
+            |_| None,
+        ));
     }
 
     router
@@ -49,7 +49,7 @@ async fn error_from_body(self) -> Result<Self> {
 
         let url = self.url().to_owned();
         Err(match self.json::<HttpErrorBody>().await {
-            Ok(HttpErrorBody { msg }) => StorageControllerPeerError::ApiError(status, msg),
+            Ok(HttpErrorBody { msg, .. }) => StorageControllerPeerError::ApiError(status, msg),
             Err(err) => StorageControllerPeerError::DeserializationError(status, url, err),
         })
     }
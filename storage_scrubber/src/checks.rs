@@ -7,6 +7,7 @@
 use pageserver::tenant::checks::check_valid_layermap;
 use pageserver::tenant::layer_map::LayerMap;
 use pageserver::tenant::remote_timeline_client::index::LayerFileMetadata;
+use pageserver::tenant::remote_timeline_client::index_codec::IndexCodecs;
 use pageserver::tenant::remote_timeline_client::manifest::TenantManifest;
 use pageserver::tenant::remote_timeline_client::{
     parse_remote_index_path, parse_remote_tenant_manifest_path, remote_layer_path,
@@ -97,17 +98,18 @@ pub(crate) async fn branch_cleanup_and_check_errors(
                     // Ignore missing file error if index_part downloaded is different from the one when listing the layer files.
                     let ignore_error = index_part_snapshot_time < index_part_last_modified_time
                         && !cfg!(debug_assertions);
-                    if !IndexPart::KNOWN_VERSIONS.contains(&index_part.version()) {
-                        result
-                            .errors
-                            .push(format!("index_part.json version: {}", index_part.version()))
+                    if !IndexPart::KNOWN_VERSIONS.contains(&index_part.format_version()) {
+                        result.errors.push(format!(
+                            "index_part.json version: {}",
+                            index_part.format_version()
+                        ))
                     }
 
                     let mut newest_versions = IndexPart::KNOWN_VERSIONS.iter().rev().take(3);
-                    if !newest_versions.any(|ip| ip == &index_part.version()) {
+                    if !newest_versions.any(|ip| ip == &index_part.format_version()) {
                         info!(
                             "index_part.json version is not latest: {}",
-                            index_part.version()
+                            index_part.format_version()
                         );
                     }
 
@@ -510,7 +512,7 @@ async fn list_timeline_blobs_impl(
                 }
             };
         let index_part_snapshot_time = index_part_object_key.last_modified;
-        match serde_json::from_slice(&index_part_bytes) {
+        match IndexCodecs::installed().decode(&index_part_bytes) {
             Ok(index_part) => {
                 return Ok(ListTimelineBlobsResult::Ready(RemoteTimelineBlobData {
                     blob_data: BlobDataParseResult::Parsed {
@@ -54,7 +54,7 @@ fn update_data(&mut self, data: &RemoteTimelineBlobData) {
         {
             *self
                 .indices_by_version
-                .entry(index_part.version())
+                .entry(index_part.format_version())
                 .or_insert(0) += 1;
         }
     }
@@ -148,6 +148,7 @@ PAGESERVER_GLOBAL_METRICS: tuple[str, ...] = (
     *histogram("pageserver_io_operations_seconds"),
     "pageserver_smgr_query_started_global_count_total",
     "pageserver_tenant_states_count",
+    "pageserver_timelines",
     "pageserver_circuit_breaker_broken_total",
     "pageserver_circuit_breaker_unbroken_total",
     counter("pageserver_tenant_throttling_count_accounted_start_global"),
@@ -26,14 +26,16 @@ from fixtures.pg_version import PgVersion
 from fixtures.utils import EnhancedJSONEncoder, Fn
 
 if TYPE_CHECKING:
+    from collections.abc import Iterator
     from datetime import datetime
 
 
 class PageserverApiException(Exception):
-    def __init__(self, message, status_code: int):
+    def __init__(self, message, status_code: int, code: str | None = None):
         super().__init__(message)
         self.message = message
         self.status_code = status_code
+        self.code = code
 
 
 @dataclass
@@ -230,12 +232,14 @@ class TenantConfig:
 class TimelinesInfoAndOffloaded:
     timelines: list[dict[str, Any]]
     offloaded: list[dict[str, Any]]
+    warnings: list[dict[str, Any]]
 
     @classmethod
     def from_json(cls, d: dict[str, Any]) -> TimelinesInfoAndOffloaded:
         return TimelinesInfoAndOffloaded(
             timelines=d["timelines"],
             offloaded=d["offloaded"],
+            warnings=d.get("warnings", []),
         )
 
 
@@ -300,11 +304,14 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         try:
             res.raise_for_status()
         except requests.RequestException as e:
+            code = None
             try:
-                msg = res.json()["msg"]
+                body = res.json()
+                msg = body["msg"]
+                code = body.get("code")
             except:  # noqa: E722
                 msg = ""
-            raise PageserverApiException(msg, res.status_code) from e
+            raise PageserverApiException(msg, res.status_code, code) from e
 
     def check_status(self):
         self.get(f"http://localhost:{self.port}/v1/status").raise_for_status()
@@ -329,6 +336,58 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         assert res_json is None
         return res_json
 
+    def config(self) -> dict[str, Any]:
+        res = self.get(f"http://localhost:{self.port}/v1/config")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def put_log_level(self, directive: str) -> str:
+        res = self.put(
+            f"http://localhost:{self.port}/v1/log_level", json={"directive": directive}
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json["previous_directive"], str)
+        return res_json["previous_directive"]
+
+    def logs_tail(self, n: int | None = None) -> list[str]:
+        params = {}
+        if n is not None:
+            params["n"] = n
+        res = self.get(f"http://localhost:{self.port}/v1/logs/tail", params=params)
+        self.verbose_error(res)
+        return [json.loads(line) for line in res.text.splitlines()]
+
+    def list_tasks(
+        self,
+        kind: str | None = None,
+        tenant_id: TenantId | None = None,
+        limit: int | None = None,
+        cursor: int | None = None,
+    ) -> dict[str, Any]:
+        params: dict[str, Any] = {}
+        if kind is not None:
+            params["kind"] = kind
+        if tenant_id is not None:
+            params["tenant_id"] = str(tenant_id)
+        if limit is not None:
+            params["limit"] = limit
+        if cursor is not None:
+            params["cursor"] = cursor
+        res = self.get(f"http://localhost:{self.port}/v1/tasks", params=params)
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def shutdown(self, force: bool = False):
+        params = {"force": "true"} if force else {}
+        res = self.post(f"http://localhost:{self.port}/v1/shutdown", params=params)
+        self.verbose_error(res)
+        assert res.status_code == 202
+
     def reload_auth_validation_keys(self):
         res = self.post(f"http://localhost:{self.port}/v1/reload_auth_validation_keys")
         self.verbose_error(res)
@@ -390,6 +449,19 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         self.verbose_error(res)
         return res.json()
 
+    def timeline_reconcile_index(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+    ) -> dict[str, Any]:
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/reconcile_index",
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def tenant_location_conf(
         self,
         tenant_id: TenantId | TenantShardId,
@@ -435,6 +507,13 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         self.verbose_error(res)
         return res
 
+    def tenant_disk_usage(self, tenant_id: TenantId | TenantShardId) -> dict[str, Any]:
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/disk_usage")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def tenant_status(
         self, tenant_id: TenantId | TenantShardId, activate: bool = False
     ) -> dict[Any, Any]:
@@ -460,6 +539,16 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         self.verbose_error(res)
         return TenantConfig.from_json(res.json())
 
+    def tenant_trace(self, tenant_id: TenantId | TenantShardId, enabled: bool) -> dict[str, Any]:
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/trace",
+            params={"enabled": "true" if enabled else "false"},
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def tenant_heatmap_upload(self, tenant_id: TenantId | TenantShardId):
         res = self.post(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/heatmap_upload")
         self.verbose_error(res)
@@ -598,12 +687,48 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         assert isinstance(res_json, list)
         return res_json
 
+    def timeline_list_with_warnings(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        include_non_incremental_logical_size: bool = False,
+    ) -> tuple[list[dict[str, Any]], list[dict[str, Any]]]:
+        """
+        Returns the timelines and the warnings from the `pageserver-timeline-list-warnings` header.
+        """
+        params = {"warnings": "true"}
+        if include_non_incremental_logical_size:
+            params["include-non-incremental-logical-size"] = "true"
+
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline", params=params
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, list)
+        warnings = json.loads(res.headers.get("pageserver-timeline-list-warnings", "[]"))
+        return res_json, warnings
+
+    def timeline_list_ndjson(self, tenant_id: TenantId | TenantShardId) -> list[dict[str, Any]]:
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline",
+            params={"format": "ndjson"},
+        )
+        self.verbose_error(res)
+        assert res.headers["Content-Type"] == "application/x-ndjson"
+        return [json.loads(line) for line in res.text.splitlines()]
+
     def timeline_and_offloaded_list(
         self,
         tenant_id: TenantId | TenantShardId,
+        include_non_incremental_logical_size: bool = False,
     ) -> TimelinesInfoAndOffloaded:
+        params = {}
+        if include_non_incremental_logical_size:
+            params["include-non-incremental-logical-size"] = "true"
+
         res = self.get(
             f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline_and_offloaded",
+            params=params,
         )
         self.verbose_error(res)
         res_json = res.json()
@@ -646,6 +771,28 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         assert isinstance(res_json, dict)
         return res_json
 
+    def timeline_branch(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        ancestor_timeline_id: TimelineId,
+        new_timeline_id: TimelineId,
+        ancestor_start_lsn: Lsn,
+        **kwargs,
+    ) -> dict[Any, Any]:
+        body = {
+            "new_timeline_id": str(new_timeline_id),
+            "ancestor_start_lsn": str(ancestor_start_lsn),
+        }
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{ancestor_timeline_id}/branch",
+            json=body,
+            **kwargs,
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def timeline_detail(
         self,
         tenant_id: TenantId | TenantShardId,
@@ -673,6 +820,17 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         assert isinstance(res_json, dict)
         return res_json
 
+    def timeline_wal_lag(
+        self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId
+    ) -> dict[str, Any]:
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/wal_lag"
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def timeline_delete(
         self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId, **kwargs
     ):
@@ -688,15 +846,35 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         res_json = res.json()
         assert res_json is None
 
+    def timelines_delete(
+        self, tenant_id: TenantId | TenantShardId, timeline_ids: list[TimelineId]
+    ) -> dict[TimelineId, dict[str, Any]]:
+        """
+        Deletes several timelines in one request. Like `timeline_delete`, the deletions complete
+        in the background. Returns the outcome per timeline.
+        """
+        res = self.delete(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline",
+            json={"timeline_ids": [str(timeline_id) for timeline_id in timeline_ids]},
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        return {
+            TimelineId(timeline_id): result for timeline_id, result in res_json["results"].items()
+        }
+
     def timeline_gc(
         self,
         tenant_id: TenantId | TenantShardId,
         timeline_id: TimelineId,
         gc_horizon: int | None,
+        dry_run: bool = False,
     ) -> dict[str, Any]:
         """
         Unlike most handlers, this will wait for the layers to be actually
         complete registering themselves to the deletion queue.
+
+        With `dry_run`, nothing is collected: the response tells what GC would do.
         """
         self.is_testing_enabled_or_skip()
 
@@ -705,7 +883,7 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         )
         res = self.put(
             f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/do_gc",
-            json={"gc_horizon": gc_horizon},
+            json={"gc_horizon": gc_horizon, "dry_run": dry_run},
         )
         log.info(f"Got GC request response code: {res.status_code}")
         self.verbose_error(res)
@@ -883,6 +1061,17 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         res_json = res.json()
         return res_json
 
+    def timeline_get_timestamp_by_lsn(
+        self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId, lsn: Lsn
+    ):
+        log.info(f"Requesting timestamp by lsn {lsn}, tenant {tenant_id}, timeline {timeline_id}")
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/get_timestamp_by_lsn?lsn={lsn}",
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        return res_json
+
     def timeline_layer_map_info(self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId):
         log.info(f"Requesting layer map info of tenant {tenant_id}, timeline {timeline_id}")
         res = self.get(
@@ -892,6 +1081,46 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         res_json = res.json()
         return res_json
 
+    def tenant_checkpoint(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        compact: bool | None = None,
+        **kwargs,
+    ) -> dict[Any, Any]:
+        self.is_testing_enabled_or_skip()
+        query = {}
+        if compact is not None:
+            query["compact"] = "true" if compact else "false"
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/checkpoint",
+            params=query,
+            **kwargs,
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def tenant_compact(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        force_image_layer_creation: bool = False,
+        **kwargs,
+    ) -> dict[Any, Any]:
+        self.is_testing_enabled_or_skip()
+        query = {}
+        if force_image_layer_creation:
+            query["force_image_layer_creation"] = "true"
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/compact",
+            params=query,
+            **kwargs,
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def timeline_checkpoint(
         self,
         tenant_id: TenantId | TenantShardId,
@@ -902,10 +1131,13 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         wait_until_flushed=True,
         wait_until_uploaded=False,
         compact: bool | None = None,
+        mode: str | None = None,
         **kwargs,
     ):
         self.is_testing_enabled_or_skip()
         query = {}
+        if mode is not None:
+            query["mode"] = mode
         if force_repartition:
             query["force_repartition"] = "true"
         if force_image_layer_creation:
@@ -974,6 +1206,32 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
             return res_json
         return None
 
+    def job(self, job_id: int) -> dict[str, Any]:
+        res = self.get(f"http://localhost:{self.port}/v1/jobs/{job_id}")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def job_events(self, job_id: int) -> Iterator[tuple[str, dict[str, Any]]]:
+        """
+        Follow the server-sent events stream of a job, yielding (event, data) pairs until the
+        pageserver closes the stream. Closing the generator disconnects.
+        """
+        res = self.get(f"http://localhost:{self.port}/v1/jobs/{job_id}/events", stream=True)
+        self.verbose_error(res)
+        assert res.headers["Content-Type"] == "text/event-stream"
+
+        with res:
+            event = None
+            for line in res.iter_lines(decode_unicode=True):
+                if line.startswith("event: "):
+                    event = line.removeprefix("event: ")
+                elif line.startswith("data: "):
+                    assert event is not None
+                    yield event, json.loads(line.removeprefix("data: "))
+                    event = None
+
     def timeline_download_remote_layers(
         self,
         tenant_id: TenantId | TenantShardId,
@@ -1069,6 +1327,19 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         assert res.status_code == 200
         return ScanDisposableKeysResponse.from_json(res.json())
 
+    def layer_map_extents(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+    ) -> list[dict[str, Any]]:
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/layer_map",
+        )
+        self.verbose_error(res)
+        layers = res.json()["layers"]
+        assert isinstance(layers, list)
+        return layers
+
     def download_layer(
         self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId, layer_name: str
     ):
@@ -1086,6 +1357,21 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
                 continue
             self.download_layer(tenant_id, timeline_id, layer.layer_file_name)
 
+    def timeline_prefetch(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+        request: dict[str, Any],
+    ) -> dict[str, Any]:
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/prefetch",
+            json=request,
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def detach_ancestor(
         self,
         tenant_id: TenantId | TenantShardId,
@@ -1118,6 +1404,15 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
 
         assert res.status_code in (200, 304)
 
+    def timeline_evict_layer(
+        self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId, layer_name: str
+    ):
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/evict_layer",
+            json={"layer_file_name": layer_name},
+        )
+        self.verbose_error(res)
+
     def evict_all_layers(self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId):
         info = self.layer_map_info(tenant_id, timeline_id)
         for layer in info.historic_layers:
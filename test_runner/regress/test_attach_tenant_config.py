@@ -196,6 +196,7 @@ def test_fully_custom_config(positive_env: NeonEnv):
             "numerator": 0,
             "denominator": 10,
         },
+        "trace_read_requests": True,
     }
 
     vps_http = env.storage_controller.pageserver_api()
@@ -6,6 +6,7 @@ from pathlib import Path
 
 import psycopg2
 import pytest
+import requests
 from fixtures.common_types import TenantId, TimelineId
 from fixtures.neon_fixtures import (
     NeonEnv,
@@ -72,6 +73,39 @@ def test_pageserver_auth(neon_env_builder: NeonEnvBuilder):
         env.pageserver.tenant_create(TenantId.generate(), auth_token=tenant_token)
 
 
+def test_pageserver_basic_auth(neon_env_builder: NeonEnvBuilder):
+    """
+    A configured basic auth credential is accepted with pageserver scope, next to JWTs.
+    """
+    neon_env_builder.auth_enabled = True
+    neon_env_builder.pageserver_config_override = (
+        'http_basic_auth = { username = "ops", password = "correct-horse" }'
+    )
+    env = neon_env_builder.init_start()
+
+    url = f"http://localhost:{env.pageserver.service_port.http}/v1/tenant"
+
+    # valid basic credential is allowed, including pageserver-wide APIs
+    res = requests.get(url, auth=("ops", "correct-horse"))
+    res.raise_for_status()
+    assert env.initial_tenant in {TenantId(t["id"]) for t in res.json()}
+
+    # wrong password or unknown user are rejected
+    for auth in [("ops", "wrong"), ("someone", "correct-horse")]:
+        res = requests.get(url, auth=auth)
+        assert res.status_code == 401, res.text
+
+    # no credentials at all still get rejected
+    assert requests.get(url).status_code == 401
+
+    # JWTs keep working alongside
+    pageserver_token = env.auth_keys.generate_pageserver_token()
+    pageserver_http_client = env.pageserver.http_client(pageserver_token)
+    assert env.initial_tenant in {TenantId(t["id"]) for t in pageserver_http_client.tenant_list()}
+    tenant_token = env.auth_keys.generate_tenant_token(env.initial_tenant)
+    assert_client_authorized(env, env.pageserver.http_client(tenant_token))
+
+
 def test_compute_auth_to_pageserver(neon_env_builder: NeonEnvBuilder):
     neon_env_builder.auth_enabled = True
     neon_env_builder.num_safekeepers = 3
@@ -5,7 +5,7 @@ from typing import TYPE_CHECKING
 import pytest
 from fixtures.common_types import Lsn, TimelineId
 from fixtures.log_helper import log
-from fixtures.pageserver.http import TimelineCreate406
+from fixtures.pageserver.http import PageserverApiException, TimelineCreate406
 from fixtures.utils import print_gc_result, query_scalar
 
 if TYPE_CHECKING:
@@ -172,3 +172,50 @@ def test_branch_behind(neon_env_builder: NeonEnvBuilder):
     assert query_scalar(more_cur, "SELECT count(*) FROM foo") == 200100
 
     assert query_scalar(main_cur, "SELECT count(*) FROM foo") == 400100
+
+
+def test_branch_endpoint(neon_env_builder: NeonEnvBuilder):
+    """
+    The explicit branch endpoint requires an existing ancestor and rejects branch points that
+    the ancestor no longer retains with a 400.
+    """
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={"pitr_interval": "0 sec", "lsn_lease_length": "0s"}
+    )
+    env.pageserver.allowed_errors.append(".*invalid branch start lsn.*")
+
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    pageserver_http = env.pageserver.http_client()
+
+    with env.endpoints.create_start("main") as endpoint:
+        endpoint.safe_psql("CREATE TABLE foo (t text)")
+        gced_lsn = Lsn(endpoint.safe_psql("SELECT pg_current_wal_insert_lsn()")[0][0])
+        endpoint.safe_psql(
+            """
+            INSERT INTO foo
+                SELECT 'long string to consume some space' || g
+                FROM generate_series(1, 100000) g
+            """
+        )
+        branch_lsn = Lsn(endpoint.safe_psql("SELECT pg_current_wal_insert_lsn()")[0][0])
+
+    # Valid branch
+    new_timeline_id = TimelineId.generate()
+    info = pageserver_http.timeline_branch(tenant_id, timeline_id, new_timeline_id, branch_lsn)
+    assert TimelineId(info["timeline_id"]) == new_timeline_id
+    assert TimelineId(info["ancestor_timeline_id"]) == timeline_id
+
+    # Branch point below the GC cutoff
+    pageserver_http.timeline_checkpoint(tenant_id, timeline_id)
+    print_gc_result(pageserver_http.timeline_gc(tenant_id, timeline_id, 0))
+    with pytest.raises(PageserverApiException, match="invalid branch start lsn") as e:
+        pageserver_http.timeline_branch(tenant_id, timeline_id, TimelineId.generate(), gced_lsn)
+    assert e.value.status_code == 400
+
+    # Missing ancestor
+    with pytest.raises(PageserverApiException) as e:
+        pageserver_http.timeline_branch(
+            tenant_id, TimelineId.generate(), TimelineId.generate(), branch_lsn
+        )
+    assert e.value.status_code == 404
@@ -114,11 +114,19 @@ def test_timeline_init_break_before_checkpoint(neon_env_builder: NeonEnvBuilder)
     old_tenant_timelines = env.neon_cli.timeline_list(tenant_id)
     initial_timeline_dirs = [d for d in timelines_dir.iterdir()]
 
+    def get_timeline_count() -> int:
+        return int(pageserver_http.get_metrics().query_one("pageserver_timelines").value)
+
+    initial_timeline_count = get_timeline_count()
+
     # Introduce failpoint during timeline init (some intermediate files are on disk), before it's checkpointed.
     pageserver_http.configure_failpoints(("before-checkpoint-new-timeline", "return"))
     with pytest.raises(Exception, match="before-checkpoint-new-timeline"):
         _ = pageserver_http.timeline_create(PgVersion.NOT_SET, tenant_id, TimelineId.generate())
 
+    # The timeline that failed to initialize is not counted as loaded
+    assert get_timeline_count() == initial_timeline_count
+
     # Restart the page server
     env.pageserver.restart(immediate=True)
 
@@ -2,6 +2,7 @@ from __future__ import annotations
 
 import time
 
+import pytest
 from fixtures.log_helper import log
 from fixtures.neon_fixtures import (
     NeonEnvBuilder,
@@ -9,8 +10,9 @@ from fixtures.neon_fixtures import (
     wait_for_last_flush_lsn,
 )
 from fixtures.pageserver.common_types import parse_layer_file_name
+from fixtures.pageserver.http import PageserverApiException
 from fixtures.pageserver.utils import wait_for_upload
-from fixtures.remote_storage import RemoteStorageKind
+from fixtures.remote_storage import LocalFsStorage, RemoteStorageKind
 from fixtures.utils import skip_in_debug_build
 
 
@@ -308,3 +310,115 @@ def test_gc_of_remote_layers(neon_env_builder: NeonEnvBuilder):
 
     log.info("after running GC, ensure that resident size is still zero")
     ensure_resident_and_remote_size_metrics()
+
+
+def test_evict_single_layer(neon_env_builder: NeonEnvBuilder):
+    """
+    Evict one layer through the evict_layer endpoint: it must be gone locally, but still be
+    referenced by the remote index so that it can be downloaded again.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # disable gc and compaction background loops because they perform on-demand downloads
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    client = env.pageserver.http_client()
+    endpoint = env.endpoints.create_start("main")
+
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 100000) g")
+    current_lsn = flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+    client.timeline_checkpoint(tenant_id, timeline_id)
+    wait_for_upload(client, tenant_id, timeline_id, current_lsn)
+
+    layer_map = client.layer_map_info(tenant_id=tenant_id, timeline_id=timeline_id)
+    layer = next(layer for layer in layer_map.historic_layers if not layer.remote)
+    layer_name = parse_layer_file_name(layer.layer_file_name)
+    assert env.pageserver.layer_exists(tenant_id, timeline_id, layer_name)
+
+    client.timeline_evict_layer(tenant_id, timeline_id, layer.layer_file_name)
+
+    assert not env.pageserver.layer_exists(tenant_id, timeline_id, layer_name)
+    assert isinstance(env.pageserver_remote_storage, LocalFsStorage)
+    index = env.pageserver_remote_storage.index_content(tenant_id, timeline_id)
+    assert layer.layer_file_name in index["layer_metadata"]
+
+    # The layer is no longer resident, so there is nothing to evict
+    with pytest.raises(PageserverApiException) as e:
+        client.timeline_evict_layer(tenant_id, timeline_id, layer.layer_file_name)
+    assert e.value.status_code == 409
+
+    # A well-formed name that the remote index does not know about
+    unknown = (
+        "000000000000000000000000000000000000-000000000000000000000000000000000001"
+        "__0000000000000001-0000000000000002"
+    )
+    with pytest.raises(PageserverApiException) as e:
+        client.timeline_evict_layer(tenant_id, timeline_id, unknown)
+    assert e.value.status_code == 404
+
+
+def test_prefetch_layers(neon_env_builder: NeonEnvBuilder):
+    """
+    Prefetching downloads evicted layers ahead of reads, leaves resident ones alone, and reports
+    names the timeline doesn't know about.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # disable gc and compaction background loops because they perform on-demand downloads
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    with env.endpoints.create_start("main") as endpoint:
+        endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 100000) g")
+        current_lsn = flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+    client.timeline_checkpoint(tenant_id, timeline_id)
+    wait_for_upload(client, tenant_id, timeline_id, current_lsn)
+
+    layer_map = client.layer_map_info(tenant_id, timeline_id)
+    evicted, resident = (layer.layer_file_name for layer in layer_map.historic_layers[:2])
+    client.evict_layer(tenant_id, timeline_id, evicted)
+    assert not env.pageserver.layer_exists(tenant_id, timeline_id, parse_layer_file_name(evicted))
+
+    unknown = (
+        "000000000000000000000000000000000000-000000000000000000000000000000000001"
+        "__0000000000000001-0000000000000002"
+    )
+    res = client.timeline_prefetch(
+        tenant_id, timeline_id, {"layers": [evicted, resident, unknown, "not-a-layer"]}
+    )
+    assert res == {
+        "downloaded": [evicted],
+        "already_resident": [resident],
+        "not_found": [unknown, "not-a-layer"],
+    }
+    assert env.pageserver.layer_exists(tenant_id, timeline_id, parse_layer_file_name(evicted))
+
+    # By range: the whole keyspace at all LSNs covers every layer
+    client.evict_layer(tenant_id, timeline_id, evicted)
+    res = client.timeline_prefetch(
+        tenant_id,
+        timeline_id,
+        {
+            "range": {
+                "key_range": {"start": "0" * 36, "end": "F" * 36},
+                "lsn_range": {"start": "0/0", "end": "FFFFFFFF/FFFFFFFF"},
+            }
+        },
+    )
+    assert res["downloaded"] == [evicted]
+    assert len(res["already_resident"]) == len(layer_map.historic_layers) - 1
+    assert res["not_found"] == []
@@ -307,3 +307,48 @@ def test_timestamp_of_lsn_empty_branch(neon_env_builder: NeonEnvBuilder):
                 head_lsn,
             )
         assert err.value.status_code == 412
+
+
+def test_timestamp_by_lsn_api(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that get_timestamp_by_lsn tells apart LSNs before the timeline, after its last record,
+    with commits up to them, and without.
+    """
+    env = neon_env_builder.init_start()
+
+    # A branch that postgres never ran on has no commits
+    empty_timeline_id = env.create_branch("test_timestamp_by_lsn_empty")
+    detail = env.pageserver.http_client().timeline_detail(
+        tenant_id=env.initial_tenant, timeline_id=empty_timeline_id
+    )
+    empty_head_lsn = Lsn(detail["last_record_lsn"])
+
+    timeline_id = env.create_branch("test_timestamp_by_lsn")
+    endpoint = env.endpoints.create_start("test_timestamp_by_lsn")
+    cur = endpoint.connect().cursor()
+    cur.execute("SET synchronous_commit=on")
+    cur.execute("CREATE TABLE foo (x integer)")
+    cur.execute("INSERT INTO foo VALUES (1)")
+    after_timestamp = query_scalar(cur, "SELECT clock_timestamp()").replace(tzinfo=UTC)
+    last_flush_lsn = wait_for_last_flush_lsn(env, endpoint, env.initial_tenant, timeline_id)
+
+    with env.pageserver.http_client() as client:
+        result = client.timeline_get_timestamp_by_lsn(
+            env.initial_tenant, timeline_id, last_flush_lsn
+        )
+        assert result["kind"] == "present"
+        timestamp = datetime.fromisoformat(result["timestamp"]).replace(tzinfo=UTC)
+        assert timestamp <= after_timestamp
+
+        result = client.timeline_get_timestamp_by_lsn(env.initial_tenant, timeline_id, Lsn(64))
+        assert result == {"kind": "past"}
+
+        result = client.timeline_get_timestamp_by_lsn(
+            env.initial_tenant, timeline_id, Lsn(int(last_flush_lsn) * 20 + 80_000)
+        )
+        assert result == {"kind": "future"}
+
+        result = client.timeline_get_timestamp_by_lsn(
+            env.initial_tenant, empty_timeline_id, empty_head_lsn
+        )
+        assert result == {"kind": "nodata"}
@@ -489,6 +489,77 @@ def test_download_remote_layers_api(
         assert query_scalar(cur, "select count(*) from testtab") == table_len
 
 
+def test_download_remote_layers_events(neon_env_builder: NeonEnvBuilder):
+    """
+    Follow the job of the download_remote_layers task through its event stream: a progress
+    event per phase, and a final completion event after which the stream closes.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # disable gc and compaction background loops because they perform on-demand downloads
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    endpoint = env.endpoints.create_start("main")
+    endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 100000) g")
+    current_lsn = flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+    client.timeline_checkpoint(tenant_id, timeline_id)
+    wait_for_upload(client, tenant_id, timeline_id, current_lsn)
+
+    # Stop safekeepers so that nothing downloads layers on demand behind our back
+    for sk in env.safekeepers:
+        sk.stop()
+
+    client.evict_all_layers(tenant_id, timeline_id)
+
+    # Hold the downloads so that the task is observed while running
+    client.configure_failpoints(("before-downloading-layer-stream-pausable", "pause"))
+    spawned = client.timeline_spawn_download_remote_layers(
+        tenant_id, timeline_id, max_concurrent_downloads=10
+    )
+
+    job_id = spawned["job_id"]
+
+    # The downloads are held, so the job can't get past the downloading phase
+    events = client.job_events(job_id)
+    seen_phases = []
+    for event, data in events:
+        assert event == "progress"
+        assert data["job_id"] == job_id
+        assert data["kind"] == "download_remote_layers"
+        assert data["state"] == "running"
+        if data["phase"] not in seen_phases:
+            seen_phases.append(data["phase"])
+        if data["phase"] == "downloading":
+            break
+    assert seen_phases == ["scheduled", "listing", "downloading"]
+
+    # Disconnecting only ends the subscription
+    events.close()
+    assert client.job(job_id)["state"] == "running"
+
+    client.configure_failpoints(("before-downloading-layer-stream-pausable", "off"))
+    rest = list(client.job_events(job_id))
+
+    assert rest[-1][0] == "completed"
+    completed = rest[-1][1]
+    assert completed["state"] == "completed"
+    assert all(event == "progress" for event, _ in rest[:-1])
+
+    info = client.timeline_poll_download_remote_layers_status(tenant_id, timeline_id, spawned)
+    assert info is not None
+    assert info["state"] == "Completed"
+    assert info["failed_download_count"] == 0
+    assert completed["layers_processed"] == info["total_layer_count"]
+
+
 def test_compaction_downloads_on_demand_without_image_creation(neon_env_builder: NeonEnvBuilder):
     """
     Create a few layers, then evict, then make sure compaction runs successfully.
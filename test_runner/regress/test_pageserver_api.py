@@ -2,12 +2,21 @@ from __future__ import annotations
 
 from typing import TYPE_CHECKING
 
-from fixtures.common_types import Lsn, TenantId, TimelineId
+import pytest
+from fixtures.common_types import KEY_MAX, KEY_MIN, Lsn, TenantId, TimelineId
 from fixtures.neon_fixtures import (
     DEFAULT_BRANCH_NAME,
     NeonEnv,
     NeonEnvBuilder,
+    wait_for_last_flush_lsn,
 )
+from fixtures.pageserver.common_types import (
+    DeltaLayerName,
+    ImageLayerName,
+    parse_layer_file_name,
+)
+from fixtures.pageserver.http import PageserverApiException
+from fixtures.pg_version import PgVersion
 from fixtures.utils import run_only_on_default_postgres, wait_until
 
 if TYPE_CHECKING:
@@ -78,6 +87,11 @@ def test_pageserver_http_get_wal_receiver_not_found(neon_simple_env: NeonEnv):
         assert timeline_details.get("last_received_msg_ts") is None, (
             "Should not be able to connect to WAL streaming without PG compute node running"
         )
+        assert timeline_details.get("wal_lag_bytes") is None
+        assert client.timeline_wal_lag(tenant_id, timeline_id) == {
+            "wal_lag_bytes": None,
+            "wal_lag_seconds": None,
+        }
 
 
 def expect_updated_msg_lsn(
@@ -127,6 +141,10 @@ def test_pageserver_http_get_wal_receiver_success(neon_simple_env: NeonEnv):
         endpoint.safe_psql("INSERT INTO t VALUES (1, 'hey')")
         wait_until(lambda: expect_updated_msg_lsn(client, tenant_id, timeline_id, lsn))
 
+        wal_lag = client.timeline_wal_lag(tenant_id, timeline_id)
+        assert wal_lag["wal_lag_bytes"] is not None and wal_lag["wal_lag_bytes"] >= 0
+        assert wal_lag["wal_lag_seconds"] is not None and wal_lag["wal_lag_seconds"] >= 0
+
 
 def test_pageserver_http_api_client(neon_simple_env: NeonEnv):
     env = neon_simple_env
@@ -164,3 +182,471 @@ def test_pageserver_http_index_part_force_patch(neon_env_builder: NeonEnvBuilder
             {"rel_size_migration": "legacy"},
         )
         assert client.timeline_detail(tenant_id, timeline_id)["rel_size_migration"] == "legacy"
+
+
+@run_only_on_default_postgres("compares against the pageserver's built-in default pg version")
+def test_pageserver_http_config(neon_env_builder: NeonEnvBuilder):
+    neon_env_builder.auth_enabled = True
+    env = neon_env_builder.init_start()
+
+    pageserver_token = env.auth_keys.generate_pageserver_token()
+    with env.pageserver.http_client(auth_token=pageserver_token) as client:
+        config = client.config()
+        assert config["id"] == env.pageserver.id
+        assert config["default_pg_version"] == int(PgVersion.DEFAULT)
+        assert config["http_auth_type"] == "NeonJWT"
+        # Secrets must never be part of the response
+        assert "control_plane_api_token" not in config
+        assert "auth_validation_public_key_path" not in config
+
+    # Exposing the configuration is an admin-only operation
+    tenant_token = env.auth_keys.generate_tenant_token(env.initial_tenant)
+    with env.pageserver.http_client(auth_token=tenant_token) as client:
+        with pytest.raises(PageserverApiException, match="Forbidden"):
+            client.config()
+
+
+def test_pageserver_http_log_level(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+
+    def directives(s: str) -> set[str]:
+        # The pageserver may print the directives in a different order than they were given
+        return set(s.split(","))
+
+    with env.pageserver.http_client() as client:
+        raised = "info,pageserver::http=debug"
+        original = client.put_log_level(raised)
+        # Setting it again returns what we applied above, i.e. the change took effect
+        assert directives(client.put_log_level(raised)) == directives(raised)
+
+        # Invalid directives are rejected and leave the current filter in place
+        with pytest.raises(PageserverApiException, match="invalid log filter directive") as e:
+            client.put_log_level("pageserver=loud")
+        assert e.value.status_code == 400
+
+        assert directives(client.put_log_level(original)) == directives(raised)
+
+
+def test_pageserver_http_layer_map_extents(neon_env_builder: NeonEnvBuilder):
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # keep the layer set stable while we look at it
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    with env.endpoints.create_start("main") as endpoint:
+        endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 10000) g")
+        wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+
+    with env.pageserver.http_client() as client:
+        client.timeline_checkpoint(tenant_id, timeline_id, force_image_layer_creation=True)
+
+        layer_names = {
+            layer.layer_file_name
+            for layer in client.layer_map_info(tenant_id, timeline_id).historic_layers
+        }
+        extents = client.layer_map_extents(tenant_id, timeline_id)
+
+    assert {extent["layer_file_name"] for extent in extents} == layer_names
+    for extent in extents:
+        name = parse_layer_file_name(extent["layer_file_name"])
+        assert int(extent["key_start"], 16) == name.key_start.as_int()
+        assert int(extent["key_end"], 16) == name.key_end.as_int()
+        if isinstance(name, DeltaLayerName):
+            assert extent["kind"] == "Delta"
+            assert extent["lsn_start"] == name.lsn_start.as_int()
+            assert extent["lsn_end"] == name.lsn_end.as_int()
+        else:
+            assert isinstance(name, ImageLayerName)
+            assert extent["kind"] == "Image"
+            assert extent["lsn_start"] == name.lsn.as_int()
+            assert extent["lsn_end"] == name.lsn.as_int() + 1
+
+    # The initial L0 delta layer covers the whole key space, and we forced an image layer
+    assert any(
+        extent["kind"] == "Delta"
+        and int(extent["key_start"], 16) == KEY_MIN.as_int()
+        and int(extent["key_end"], 16) == KEY_MAX.as_int()
+        for extent in extents
+    )
+    assert any(extent["kind"] == "Image" for extent in extents)
+
+
+def test_pageserver_http_tenant_disk_usage(neon_env_builder: NeonEnvBuilder):
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # keep the layer set stable while we look at it
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    with env.endpoints.create_start("main") as endpoint:
+        endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 10000) g")
+        wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+
+    client = env.pageserver.http_client()
+    client.timeline_checkpoint(
+        tenant_id, timeline_id, force_image_layer_creation=True, wait_until_uploaded=True
+    )
+
+    def buckets(usage) -> list[int]:
+        return [
+            usage[layer_type][residency]
+            for layer_type in ["image", "delta"]
+            for residency in ["resident_size", "remote_only_size"]
+        ]
+
+    usage = client.tenant_disk_usage(tenant_id)
+    total = client.tenant_status(tenant_id)["current_physical_size"]
+    assert usage["total_physical_size"] == total
+    assert sum(buckets(usage)) == total
+    assert usage["image"]["resident_size"] > 0
+    assert usage["delta"]["resident_size"] > 0
+    assert usage["image"]["remote_only_size"] == 0
+    assert usage["delta"]["remote_only_size"] == 0
+
+    # Evicting the image layers moves them to the remote-only bucket
+    image_layers = [
+        layer
+        for layer in client.layer_map_info(tenant_id, timeline_id).historic_layers
+        if layer.kind == "Image"
+    ]
+    for layer in image_layers:
+        client.evict_layer(tenant_id, timeline_id, layer.layer_file_name)
+
+    evicted = client.tenant_disk_usage(tenant_id)
+    assert evicted["total_physical_size"] == total
+    assert sum(buckets(evicted)) == total
+    assert evicted["image"] == {
+        "resident_size": 0,
+        "remote_only_size": usage["image"]["resident_size"],
+    }
+    assert evicted["delta"] == usage["delta"]
+
+
+def test_pageserver_http_timeline_detail_etag(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    with env.pageserver.http_client() as client:
+        url = f"http://localhost:{client.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}"
+
+        res = client.get(url)
+        client.verbose_error(res)
+        etag = res.headers["ETag"]
+        # Weak: the body's sizes aren't covered, and it may be sent compressed or not
+        assert etag.startswith('W/"')
+        res = client.get(url, headers={"Accept-Encoding": "identity"})
+        assert res.headers["ETag"] == etag
+
+        # Nothing changed: the same ETag, and a matching If-None-Match gets no body
+        res = client.get(url)
+        assert res.headers["ETag"] == etag
+        res = client.get(url, headers={"If-None-Match": etag})
+        assert res.status_code == 304
+        assert res.content == b""
+        assert res.headers["ETag"] == etag
+
+        # The same timeline with other query parameters is a different response
+        sized_url = f"{url}?include-non-incremental-logical-size=true"
+        res = client.get(sized_url, headers={"If-None-Match": etag})
+        assert res.status_code == 200
+        sized_etag = res.headers["ETag"]
+        assert sized_etag != etag
+        res = client.get(sized_url, headers={"If-None-Match": sized_etag})
+        assert res.status_code == 304
+        assert res.headers["ETag"] == sized_etag
+
+        # Advance the LSN: the ETag changes and a stale If-None-Match gets the full response
+        with env.endpoints.create_start("main") as endpoint:
+            endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 1000) g")
+            wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+
+        res = client.get(url, headers={"If-None-Match": etag})
+        assert res.status_code == 200
+        assert res.headers["ETag"] != etag
+        assert TimelineId(res.json()["timeline_id"]) == timeline_id
+
+
+def test_pageserver_http_timeline_detail_since_lsn(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    with env.pageserver.http_client() as client:
+        url = f"http://localhost:{client.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}"
+        last_record_lsn = Lsn(client.timeline_detail(tenant_id, timeline_id)["last_record_lsn"])
+
+        # The LSN hasn't advanced past the one the client knows about: no body
+        res = client.get(url, params={"since_lsn": str(last_record_lsn)})
+        assert res.status_code == 204
+        assert res.content == b""
+
+        # An older LSN gets the full response
+        res = client.get(url, params={"since_lsn": str(Lsn(0))})
+        assert res.status_code == 200
+        assert Lsn(res.json()["last_record_lsn"]) == last_record_lsn
+
+        with env.endpoints.create_start("main") as endpoint:
+            endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 1000) g")
+            wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+
+        res = client.get(url, params={"since_lsn": str(last_record_lsn)})
+        assert res.status_code == 200
+        assert Lsn(res.json()["last_record_lsn"]) > last_record_lsn
+
+        res = client.get(url, params={"since_lsn": "not-an-lsn"})
+        assert res.status_code == 400
+
+
+def test_pageserver_http_tenant_checkpoint(neon_env_builder: NeonEnvBuilder):
+    env = neon_env_builder.init_start()
+    env.pageserver.allowed_errors.append(".*checkpoint failed.*failpoint.*")
+    tenant_id = env.initial_tenant
+    client = env.pageserver.http_client()
+
+    timeline_ids = [env.initial_timeline, env.create_branch("child")]
+    for branch_name, timeline_id in zip(["main", "child"], timeline_ids, strict=True):
+        with env.endpoints.create_start(branch_name) as endpoint:
+            endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 1000) g")
+            wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+
+    def checkpoint_results(compact: bool) -> dict[TimelineId, str | None]:
+        res = client.tenant_checkpoint(tenant_id, compact=compact)
+        return {TimelineId(t["timeline_id"]): t["error"] for t in res["timelines"]}
+
+    # All timelines are checkpointed
+    assert checkpoint_results(compact=True) == {timeline_id: None for timeline_id in timeline_ids}
+    for timeline_id in timeline_ids:
+        detail = client.timeline_detail(tenant_id, timeline_id)
+        assert Lsn(detail["disk_consistent_lsn"]) == Lsn(detail["last_record_lsn"])
+
+    # A failure on one timeline is reported, and does not prevent checkpointing the other
+    failing, succeeding = timeline_ids
+    client.configure_failpoints(("tenant-checkpoint-timeline", f"return({failing})"))
+    results = checkpoint_results(compact=False)
+    client.configure_failpoints(("tenant-checkpoint-timeline", "off"))
+
+    assert results[succeeding] is None
+    error = results[failing]
+    assert error is not None and "failpoint" in error
+
+
+def test_pageserver_http_tenant_compact(neon_env_builder: NeonEnvBuilder):
+    env = neon_env_builder.init_start()
+    tenant_id = env.initial_tenant
+    client = env.pageserver.http_client()
+
+    timeline_ids = [env.initial_timeline, env.create_branch("child")]
+    for branch_name, timeline_id in zip(["main", "child"], timeline_ids, strict=True):
+        with env.endpoints.create_start(branch_name) as endpoint:
+            endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 1000) g")
+            wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+
+    res = client.tenant_compact(tenant_id, force_image_layer_creation=True)
+    assert {TimelineId(t["timeline_id"]) for t in res["timelines"]} == set(timeline_ids)
+    for t in res["timelines"]:
+        assert t["error"] is None
+        assert t["elapsed_ms"] >= 0
+
+
+def test_pageserver_http_logs_tail(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    client = env.pageserver.http_client()
+
+    # Configuring a failpoint logs its name, which makes for easy to find lines.
+    markers = [f"logs-tail-marker-{i}" for i in range(5)]
+    for marker in markers:
+        client.configure_failpoints((marker, "off"))
+
+    lines = client.logs_tail(n=1000)
+    positions = []
+    for marker in markers:
+        matching = [i for i, line in enumerate(lines) if f"cfg failpoint: {marker} " in line]
+        assert len(matching) == 1, f"expected one line for {marker}"
+        positions.append(matching[0])
+    # Newest last
+    assert positions == sorted(positions)
+
+    assert len(client.logs_tail(n=2)) == 2
+
+
+def test_pageserver_http_timeline_list_ndjson(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    client = env.pageserver.http_client()
+
+    for i in range(3):
+        env.create_branch(f"branch-{i}")
+
+    streamed = client.timeline_list_ndjson(tenant_id)
+    listed = client.timeline_list(tenant_id)
+    assert len(streamed) == len(listed) == 4
+    assert {t["timeline_id"] for t in streamed} == {t["timeline_id"] for t in listed}
+    for timeline in streamed:
+        assert TenantId(timeline["tenant_id"]) == tenant_id
+
+    # The Accept header doesn't select the format
+    res = client.get(
+        f"http://localhost:{client.port}/v1/tenant/{tenant_id}/timeline",
+        headers={"Accept": "application/x-ndjson"},
+    )
+    client.verbose_error(res)
+    assert res.headers["Content-Type"] == "application/json"
+    assert len(res.json()) == 4
+
+    res = client.get(
+        f"http://localhost:{client.port}/v1/tenant/{tenant_id}/timeline",
+        params={"format": "xml"},
+    )
+    with pytest.raises(PageserverApiException, match="invalid format"):
+        client.verbose_error(res)
+
+
+def test_pageserver_http_checkpoint_mode(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    client = env.pageserver.http_client()
+
+    for mode in ["forced", "flush"]:
+        client.timeline_checkpoint(tenant_id, timeline_id, mode=mode)
+
+    with pytest.raises(PageserverApiException, match="unknown checkpoint mode") as e:
+        client.timeline_checkpoint(tenant_id, timeline_id, mode="lazy")
+    assert e.value.status_code == 400
+
+
+def test_pageserver_http_error_codes(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    client = env.pageserver.http_client()
+
+    with pytest.raises(PageserverApiException) as e:
+        client.tenant_status(TenantId.generate())
+    assert e.value.status_code == 404
+    assert e.value.code == "tenant_not_found"
+
+    # Re-creating a branch with different parameters conflicts with the existing one
+    branch_id = TimelineId.generate()
+    client.timeline_create(
+        env.pg_version, tenant_id, branch_id, ancestor_timeline_id=env.initial_timeline
+    )
+    with pytest.raises(PageserverApiException) as e:
+        client.timeline_create(env.pg_version, tenant_id, branch_id)
+    assert e.value.status_code == 409
+    assert e.value.code == "timeline_conflict"
+
+    # The initial timeline has the branch as a child, and can't be deleted
+    with pytest.raises(PageserverApiException) as e:
+        client.timeline_delete(tenant_id, env.initial_timeline)
+    assert e.value.status_code == 412
+    assert e.value.code == "timeline_has_children"
+
+    with pytest.raises(PageserverApiException) as e:
+        client.timeline_detail(tenant_id, TimelineId.generate())
+    assert e.value.status_code == 404
+    assert e.value.code == "timeline_not_found"
+
+    # Errors without a specific code don't report one
+    with pytest.raises(PageserverApiException, match="unknown checkpoint mode") as e:
+        client.timeline_checkpoint(tenant_id, env.initial_timeline, mode="lazy")
+    assert e.value.code is None
+
+
+def test_pageserver_http_malformed_path_params(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    client = env.pageserver.http_client()
+
+    # A UUID with dashes is not a valid hex id
+    bogus = "3fa85f64-5717-4562-b3fc-2c963f66afa6"
+    for path, param in [
+        (f"/v1/tenant/{bogus}", "tenant_shard_id"),
+        (f"/v1/tenant/{bogus}/timeline", "tenant_shard_id"),
+        (f"/v1/tenant/{tenant_id}/timeline/{bogus}", "timeline_id"),
+        (f"/v1/tenant/{tenant_id}/timeline/{bogus}/layer", "timeline_id"),
+        (f"/v1/tenant/{tenant_id}/timeline/{bogus}/get_timestamp_of_lsn?lsn=0/0", "timeline_id"),
+        (f"/v1/tenant/{bogus}/timeline/{timeline_id}/get_lsn_by_timestamp", "tenant_shard_id"),
+    ]:
+        res = client.get(f"{client.base_url}{path}")
+        assert res.status_code == 400, f"{path}: {res.text}"
+        assert f"cannot parse path param {param}" in res.json()["msg"], f"{path}: {res.text}"
+
+
+def test_pageserver_http_list_tasks(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_a = env.initial_tenant
+    tenant_b, _ = env.create_tenant()
+    client = env.pageserver.http_client()
+
+    # Each tenant runs its background loops once active
+    def background_loops_running():
+        tasks = client.list_tasks()
+        assert tasks["next_cursor"] is None
+        running = {(t["tenant_shard_id"], t["kind"]) for t in tasks["tasks"]}
+        for tenant_id in [tenant_a, tenant_b]:
+            for kind in ["Compaction", "GarbageCollector"]:
+                assert (str(tenant_id), kind) in running
+        task_ids = [t["task_id"] for t in tasks["tasks"]]
+        assert task_ids == sorted(task_ids)
+
+    wait_until(background_loops_running)
+
+    # Filters narrow the listing
+    compaction = client.list_tasks(kind="Compaction")["tasks"]
+    assert {t["tenant_shard_id"] for t in compaction} == {str(tenant_a), str(tenant_b)}
+    assert all(t["kind"] == "Compaction" for t in compaction)
+    of_tenant_b = client.list_tasks(tenant_id=tenant_b)["tasks"]
+    assert len(of_tenant_b) > 0
+    assert all(t["tenant_shard_id"] == str(tenant_b) for t in of_tenant_b)
+    assert client.list_tasks(kind="Compaction", tenant_id=tenant_b)["tasks"] == [
+        t for t in compaction if t["tenant_shard_id"] == str(tenant_b)
+    ]
+
+    # Pages follow each other without overlap
+    first = client.list_tasks(kind="Compaction", limit=1)
+    assert len(first["tasks"]) == 1
+    assert first["next_cursor"] == first["tasks"][0]["task_id"]
+    second = client.list_tasks(kind="Compaction", limit=1, cursor=first["next_cursor"])
+    assert second["next_cursor"] is None
+    assert first["tasks"] + second["tasks"] == compaction
+
+    with pytest.raises(PageserverApiException) as e:
+        client.list_tasks(kind="NoSuchKind")
+    assert e.value.status_code == 400
+
+
+def test_pageserver_http_shutdown_drains(neon_env_builder: NeonEnvBuilder):
+    env = neon_env_builder.init_start()
+    tenant_id = env.initial_tenant
+    env.pageserver.allowed_errors.append(".*pageserver is draining for shutdown.*")
+    client = env.pageserver.http_client()
+
+    # Hold the process up after draining, so that we can observe the draining state.
+    client.configure_failpoints(("http-shutdown-before-signal", "pause"))
+    client.shutdown()
+
+    with pytest.raises(PageserverApiException) as e:
+        client.tenant_status(tenant_id)
+    assert e.value.status_code == 503
+    res = client.get(f"http://localhost:{client.port}/v1/tenant/{tenant_id}")
+    assert res.status_code == 503
+    assert int(res.headers["Retry-After"]) > 0
+    # Allowlisted endpoints keep working, including the shutdown endpoint itself
+    client.check_status()
+    client.shutdown()
+
+    # A termination signal still shuts down the drained process
+    env.pageserver.stop()
+    env.pageserver.start()
+    client.tenant_status(tenant_id)
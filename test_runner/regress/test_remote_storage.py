@@ -1,5 +1,6 @@
 from __future__ import annotations
 
+import json
 import os
 import queue
 import shutil
@@ -803,6 +804,49 @@ def wait_upload_queue_empty(
     )
 
 
+def test_reconcile_index(neon_env_builder: NeonEnvBuilder):
+    """
+    Drop a layer from the remote index behind the pageserver's back, and check that reconciling
+    rewrites the index to match the layers the timeline has loaded.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    env = neon_env_builder.init_start()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    client = env.pageserver.http_client()
+
+    with env.endpoints.create_start("main", tenant_id=tenant_id) as endpoint:
+        endpoint.safe_psql("CREATE TABLE foo AS SELECT generate_series(1, 10000) g")
+        current_lsn = wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    client.timeline_checkpoint(tenant_id, timeline_id)
+    wait_for_upload(client, tenant_id, timeline_id, current_lsn)
+
+    remote_storage = env.pageserver_remote_storage
+    assert isinstance(remote_storage, LocalFsStorage)
+
+    # Corrupt the remote index: forget about one of the layers
+    index_path = remote_storage.index_path(tenant_id, timeline_id)
+    index = remote_storage.index_content(tenant_id, timeline_id)
+    dropped = next(iter(index["layer_metadata"]))
+    del index["layer_metadata"][dropped]
+    # Like an index written before checksums, which is loaded without verifying it
+    del index["checksum"]
+    index_path.write_text(json.dumps(index))
+    env.pageserver.allowed_errors.append(".*index part has no checksum.*")
+
+    res = client.timeline_reconcile_index(tenant_id, timeline_id)
+    assert res == {"added_layers": 1, "removed_layers": 0}
+
+    local_layers = {
+        layer.layer_file_name
+        for layer in client.layer_map_info(tenant_id, timeline_id).historic_layers
+    }
+    index = remote_storage.index_content(tenant_id, timeline_id)
+    assert set(index["layer_metadata"].keys()) == local_layers
+    assert dropped in index["layer_metadata"]
+
+
 def get_queued_count(
     client: PageserverHttpClient,
     tenant_id: TenantId,
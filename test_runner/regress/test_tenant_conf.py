@@ -4,7 +4,8 @@ import json
 from typing import TYPE_CHECKING
 
 import pytest
-from fixtures.common_types import Lsn
+from fixtures.common_types import Lsn, TenantId
+from fixtures.pageserver.http import PageserverApiException
 from fixtures.pageserver.utils import assert_tenant_state, wait_for_upload
 from fixtures.remote_storage import LocalFsStorage, RemoteStorageKind
 from fixtures.utils import run_only_on_default_postgres, wait_until
@@ -412,3 +413,39 @@ def test_tenant_config_patch(neon_env_builder: NeonEnvBuilder, ps_managed_by: st
         assert_tenant_conf_semantically_equal(overrides_on_ps, tenant_conf_after_put)
     assert_tenant_conf_semantically_equal(tenant_conf_after_put, put)
     crnt_tenant_conf = tenant_conf_after_put
+
+
+def test_tenant_trace_toggle(neon_env_builder: NeonEnvBuilder):
+    neon_env_builder.auth_enabled = True
+    env = neon_env_builder.init_start()
+    tenant_id = env.initial_tenant
+
+    client = env.pageserver.http_client(env.auth_keys.generate_tenant_token(tenant_id))
+
+    ratio = {"numerator": 1, "denominator": 10}
+    client.patch_tenant_config(tenant_id, {"sampling_ratio": ratio})
+
+    res = client.tenant_trace(tenant_id, enabled=True)
+    assert res == {"enabled": True}
+    effective = client.tenant_config(tenant_id).effective_config
+    assert effective["trace_read_requests"] is True
+    assert effective["sampling_ratio"] == ratio
+
+    # The toggle survives a restart
+    env.pageserver.restart()
+    wait_until(lambda: assert_tenant_state(client, tenant_id, "Active"))
+    overrides = client.tenant_config(tenant_id).tenant_specific_overrides
+    assert overrides["trace_read_requests"] is True
+
+    # Turning it off leaves the configured ratio in effect
+    res = client.tenant_trace(tenant_id, enabled=False)
+    assert res == {"enabled": False}
+    effective = client.tenant_config(tenant_id).effective_config
+    assert effective["trace_read_requests"] is False
+    assert effective["sampling_ratio"] == ratio
+
+    # Another tenant's token may not toggle it
+    other_token = env.auth_keys.generate_tenant_token(TenantId.generate())
+    other_client = env.pageserver.http_client(other_token)
+    with pytest.raises(PageserverApiException, match="Forbidden: JWT authentication error"):
+        other_client.tenant_trace(tenant_id, enabled=True)
@@ -59,6 +59,92 @@ def test_empty_tenant_size(neon_env_builder: NeonEnvBuilder):
     assert size >= initial_size and size - initial_size < 1024
 
 
+def test_tenant_size_etag(neon_simple_env: NeonEnv):
+    env = neon_simple_env
+    tenant_id = env.initial_tenant
+    http_client = env.pageserver.http_client()
+    url = f"http://localhost:{http_client.port}/v1/tenant/{tenant_id}/synthetic_size"
+
+    res = http_client.get(url)
+    http_client.verbose_error(res)
+    etag = res.headers["ETag"]
+    assert etag.startswith('W/"')
+
+    res = http_client.get(url, headers={"If-None-Match": etag})
+    assert res.status_code == 304
+    assert res.headers["ETag"] == etag
+
+    # Pretty-printing renders different bytes, so it is a different response
+    res = http_client.get(url, params={"pretty": "true"}, headers={"If-None-Match": etag})
+    assert res.status_code == 200
+    pretty_etag = res.headers["ETag"]
+    assert pretty_etag != etag
+    res = http_client.get(url, params={"pretty": "true"}, headers={"If-None-Match": pretty_etag})
+    assert res.status_code == 304
+
+
+def test_tenant_size_retries_transient_errors(neon_env_builder: NeonEnvBuilder):
+    """
+    Gathering size inputs is retried on transient errors, so a couple of failures do not fail
+    the request, while persistent ones still do.
+    """
+    env = neon_env_builder.init_start()
+    env.pageserver.allowed_errors.extend(
+        [
+            ".*gather tenant size inputs .*failed.*",
+            ".*failpoint: gather-size-inputs-transient-error.*",
+        ]
+    )
+    http_client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+
+    http_client.configure_failpoints(("gather-size-inputs-transient-error", "2*return->off"))
+    assert http_client.tenant_size(tenant_id) > 0
+
+    http_client.configure_failpoints(("gather-size-inputs-transient-error", "return"))
+    with pytest.raises(PageserverApiException, match="gather-size-inputs-transient-error") as e:
+        http_client.tenant_size(tenant_id)
+    assert e.value.status_code == 500
+    http_client.configure_failpoints(("gather-size-inputs-transient-error", "off"))
+
+
+def test_tenant_size_pretty(neon_simple_env: NeonEnv):
+    """
+    `?pretty=true` only changes the formatting of the response, not its contents.
+    """
+    env = neon_simple_env
+    http_client = env.pageserver.http_client()
+    url = f"http://localhost:{http_client.port}/v1/tenant/{env.initial_tenant}/synthetic_size"
+
+    compact = http_client.get(url, params={"inputs_only": "true"})
+    http_client.verbose_error(compact)
+    pretty = http_client.get(url, params={"inputs_only": "true", "pretty": "true"})
+    http_client.verbose_error(pretty)
+
+    assert pretty.json() == compact.json()
+    assert "\n" not in compact.text
+    assert len(pretty.text.splitlines()) > 1
+
+
+def test_tenant_size_without_inputs(neon_simple_env: NeonEnv):
+    """
+    `?inputs=false` drops the model inputs, but not the size.
+    """
+    env = neon_simple_env
+    http_client = env.pageserver.http_client()
+    url = f"http://localhost:{http_client.port}/v1/tenant/{env.initial_tenant}/synthetic_size"
+
+    (size, _) = http_client.tenant_size_and_modelinputs(env.initial_tenant)
+
+    res = http_client.get(url, params={"inputs": "false"})
+    http_client.verbose_error(res)
+    assert "inputs" not in res.json()
+    assert res.json()["size"] == size
+
+    res = http_client.get(url, params={"inputs": "false", "inputs_only": "true"})
+    assert res.status_code == 400
+
+
 def test_branched_empty_timeline_size(neon_simple_env: NeonEnv, test_output_dir: Path):
     """
     Issue found in production. Because the ancestor branch was under
@@ -288,15 +288,24 @@ def test_pageserver_metrics_removed_after_detach(neon_env_builder: NeonEnvBuilde
                 samples.append(sample)
         return samples
 
+    def get_ps_timeline_count() -> int:
+        ps_metrics = env.pageserver.http_client().get_metrics()
+        return int(ps_metrics.query_one("pageserver_timelines").value)
+
+    # The initial tenant's timeline, and two timelines in each of the created tenants
+    assert get_ps_timeline_count() == 5
+
     for tenant in [tenant_1, tenant_2]:
         pre_detach_samples = set([x.name for x in get_ps_metric_samples_for_tenant(tenant)])
         expected = set(PAGESERVER_PER_TENANT_METRICS)
         assert pre_detach_samples == expected
+        pre_detach_timelines = get_ps_timeline_count()
 
         env.pageserver.http_client().tenant_detach(tenant)
 
         post_detach_samples = set([x.name for x in get_ps_metric_samples_for_tenant(tenant)])
         assert post_detach_samples == set()
+        assert get_ps_timeline_count() == pre_detach_timelines - 2
 
 
 def test_pageserver_with_empty_tenants(neon_env_builder: NeonEnvBuilder):
@@ -121,6 +121,43 @@ def test_timeline_delete(neon_simple_env: NeonEnv):
         ps_http.timeline_detail(env.initial_tenant, leaf_timeline_id)
 
 
+def test_timelines_delete(neon_simple_env: NeonEnv):
+    """
+    Delete several timelines in one request: a parent and its child, listed parent first, and a
+    timeline that doesn't exist.
+    """
+    env = neon_simple_env
+    ps_http = env.pageserver.http_client()
+
+    parent_timeline_id = env.create_branch("test_timelines_delete_parent")
+    child_timeline_id = env.create_branch(
+        "test_timelines_delete_child", ancestor_branch_name="test_timelines_delete_parent"
+    )
+    unknown_timeline_id = TimelineId.generate()
+
+    # The child is deleted before its parent regardless of the order in the request, and
+    # repeated ids are only deleted once.
+    results = ps_http.timelines_delete(
+        env.initial_tenant,
+        [parent_timeline_id, child_timeline_id, unknown_timeline_id, parent_timeline_id],
+    )
+    assert results.keys() == {parent_timeline_id, child_timeline_id, unknown_timeline_id}
+    assert results[parent_timeline_id] == {"status": "accepted"}
+    assert results[child_timeline_id] == {"status": "accepted"}
+
+    # An unknown timeline fails on its own, without failing the rest of the request
+    assert results[unknown_timeline_id]["status"] == "failed"
+    assert "timeline not found" in results[unknown_timeline_id]["error"]
+
+    for timeline_id in [parent_timeline_id, child_timeline_id]:
+        wait_timeline_detail_404(ps_http, env.initial_tenant, timeline_id)
+    assert not env.pageserver.timeline_dir(env.initial_tenant, parent_timeline_id).exists()
+    assert not env.pageserver.timeline_dir(env.initial_tenant, child_timeline_id).exists()
+
+    # The initial timeline was not touched
+    ps_http.timeline_detail(env.initial_tenant, env.initial_timeline)
+
+
 class Check(enum.Enum):
     RETRY_WITHOUT_RESTART = enum.auto()
     RETRY_WITH_RESTART = enum.auto()
@@ -1147,3 +1147,96 @@ def test_lazy_attach_activation(neon_env_builder: NeonEnvBuilder, activation_met
             ("walreceiver-after-ingest", "off"),
         ]
     )
+
+
+def test_non_incremental_logical_size_concurrency_limit(neon_env_builder: NeonEnvBuilder):
+    """
+    Non-incremental logical size calculations requested through the HTTP API are bounded by
+    `concurrent_non_incremental_logical_size_queries`, other requests wait for a permit.
+    """
+    limit = 2
+    neon_env_builder.pageserver_config_override = (
+        f"concurrent_non_incremental_logical_size_queries={limit}"
+    )
+    env = neon_env_builder.init_start()
+    tenant_id = env.initial_tenant
+    timeline_ids = [env.create_branch(f"branch_{i}") for i in range(limit * 2 + 1)]
+
+    client = env.pageserver.http_client()
+
+    def running() -> float | None:
+        return client.get_metric_value(
+            "pageserver_concurrent_non_incremental_logical_size_queries"
+        )
+
+    def detail(timeline_id: TimelineId):
+        with env.pageserver.http_client() as client:
+            return client.timeline_detail(
+                tenant_id, timeline_id, include_non_incremental_logical_size=True
+            )
+
+    # Hold the calculations once they got their permit
+    client.configure_failpoints(("non-incremental-logical-size-pausable", "pause"))
+
+    with concurrent.futures.ThreadPoolExecutor(max_workers=len(timeline_ids)) as executor:
+        futs = [executor.submit(detail, timeline_id) for timeline_id in timeline_ids]
+
+        def all_permits_taken():
+            assert running() == limit
+
+        wait_until(all_permits_taken)
+
+        # The remaining requests are queued up for a permit rather than running
+        time.sleep(1)
+        assert running() == limit
+        assert not any(f.done() for f in futs)
+
+        client.configure_failpoints(("non-incremental-logical-size-pausable", "off"))
+        for f in futs:
+            assert f.result()["current_logical_size_non_incremental"] is not None
+
+    assert running() == 0
+
+
+def test_timeline_list_warnings(neon_env_builder: NeonEnvBuilder):
+    """
+    A failed non-incremental logical size calculation for one timeline leaves its size unset and
+    is reported as a warning, instead of failing the whole listing.
+    """
+    env = neon_env_builder.init_start()
+    env.pageserver.allowed_errors.append(".*non-incremental logical size failed.*")
+    tenant_id = env.initial_tenant
+    failing_timeline_id = env.create_branch("failing")
+
+    client = env.pageserver.http_client()
+
+    listing = client.timeline_and_offloaded_list(
+        tenant_id, include_non_incremental_logical_size=True
+    )
+    assert listing.warnings == []
+    _, plain_warnings = client.timeline_list_with_warnings(
+        tenant_id, include_non_incremental_logical_size=True
+    )
+    assert plain_warnings == []
+
+    client.configure_failpoints(
+        ("non-incremental-logical-size", f"return({failing_timeline_id})")
+    )
+    listing = client.timeline_and_offloaded_list(
+        tenant_id, include_non_incremental_logical_size=True
+    )
+    plain_timelines, plain_warnings = client.timeline_list_with_warnings(
+        tenant_id, include_non_incremental_logical_size=True
+    )
+    client.configure_failpoints(("non-incremental-logical-size", "off"))
+
+    for warnings, timelines in [
+        (listing.warnings, listing.timelines),
+        (plain_warnings, plain_timelines),
+    ]:
+        assert len(warnings) == 1
+        assert warnings[0]["timeline_id"] == str(failing_timeline_id)
+        assert "failpoint" in warnings[0]["message"]
+        sizes = {t["timeline_id"]: t.get("current_logical_size_non_incremental") for t in timelines}
+        assert sizes[str(failing_timeline_id)] is None
+        assert sizes[str(env.initial_timeline)] is not None